@@ -8,25 +8,46 @@ use serde::{
 };
 
 use crate::{
+    block_hash::{
+        encode_timestamp,
+        is_timestamp_hashable,
+    },
+    merkle_root,
     BlockHash,
+    BlockHeader,
+    ChainId,
+    Encode,
+    Encoder,
     Key,
+    MerkleProof,
     SignVerifier,
     Transaction,
+    BATCH_HASH_VERSION,
+    CHAIN_ID_HASH_VERSION,
+    DELEGATION_HASH_VERSION,
+    FEE_HASH_VERSION,
+    LEGACY_HASH_VERSION,
+    UNDELEGATION_HASH_VERSION,
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Block {
     pub hash: BlockHash,
     pub previous_hash: Option<BlockHash>,
-    pub timestamp: String,
+    /// Hashed and encoded in it's `Display` format, as it was stored before being typed
+    pub timestamp: DateTime<Utc>,
     pub transactions: Vec<Transaction>,
     pub key: Key,
     pub signature: Key,
     pub index: Option<usize>,
 }
 
+#[derive(Debug)]
 pub enum BlocksErrors {
     WrongHash,
+    WrongSignature,
+    WrongPreviousHash,
+    LegacyHash,
 }
 
 impl Block {
@@ -38,7 +59,6 @@ impl Block {
         key: &Key,
         signature: &Key,
     ) -> Self {
-        let timestamp = timestamp.to_string();
         let signature = signature.clone();
         let hash = hash.clone();
         let previous_hash = previous_hash.clone();
@@ -54,9 +74,76 @@ impl Block {
     }
 
     pub fn verify_integrity(&self) -> Result<(), BlocksErrors> {
-        let must_hash = BlockHash::new(
+        let version = self.hash.version;
+
+        if !self.hash.is_known_version() {
+            return Err(BlocksErrors::WrongHash);
+        }
+
+        // Hashes of older versions don't commit to the fees
+        if version < FEE_HASH_VERSION && self.transactions.iter().any(|tx| tx.get_fee() > 0) {
+            return Err(BlocksErrors::WrongHash);
+        }
+
+        // Nor to the commissions, and they didn't have delegations
+        if version < DELEGATION_HASH_VERSION
+            && self.transactions.iter().any(|tx| {
+                matches!(tx, Transaction::STAKE { commission, .. } if *commission > 0)
+                    || matches!(tx, Transaction::DELEGATE { .. })
+            })
+        {
+            return Err(BlocksErrors::WrongHash);
+        }
+
+        // Unstakes came together with the chain id
+        if version < CHAIN_ID_HASH_VERSION
+            && self
+                .transactions
+                .iter()
+                .any(|tx| matches!(tx, Transaction::UNSTAKE { .. }))
+        {
+            return Err(BlocksErrors::WrongHash);
+        }
+
+        // And batches after the delegations
+        if version < BATCH_HASH_VERSION
+            && self
+                .transactions
+                .iter()
+                .any(|tx| matches!(tx, Transaction::BATCH { .. }))
+        {
+            return Err(BlocksErrors::WrongHash);
+        }
+
+        // Undelegations came last
+        if version < UNDELEGATION_HASH_VERSION
+            && self
+                .transactions
+                .iter()
+                .any(|tx| matches!(tx, Transaction::UNDELEGATE { .. }))
+        {
+            return Err(BlocksErrors::WrongHash);
+        }
+
+        // Timestamps are hashed as milliseconds from this version on
+        if !is_timestamp_hashable(version, &self.timestamp) {
+            return Err(BlocksErrors::WrongHash);
+        }
+
+        // The Merkle root only commits to the transaction hashes, so they must match their content
+        if version != LEGACY_HASH_VERSION
+            && self
+                .transactions
+                .iter()
+                .any(|tx| tx.hash_with_version(version) != tx.get_hash())
+        {
+            return Err(BlocksErrors::WrongHash);
+        }
+
+        let must_hash = BlockHash::with_version(
+            version,
             &self.transactions,
-            self.timestamp.clone(),
+            self.timestamp,
             self.previous_hash.clone(),
             self.key.clone(),
         );
@@ -68,7 +155,84 @@ impl Block {
         }
     }
 
-    pub fn verify_sign_with(&self, acc: &impl SignVerifier) -> bool {
-        acc.verify_signature(&self.signature, self.hash.unite())
+    /*
+     * Get the header of the block, without the transactions
+     */
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            hash: self.hash.clone(),
+            previous_hash: self.previous_hash.clone(),
+            timestamp: self.timestamp,
+            merkle_root: self.merkle_root(),
+            key: self.key.clone(),
+            signature: self.signature.clone(),
+            index: self.index,
+        }
+    }
+
+    /*
+     * Get the hashes of the transactions, in the order they are in the block
+     */
+    pub fn transaction_hashes(&self) -> Vec<String> {
+        self.transactions.iter().map(|tx| tx.get_hash()).collect()
+    }
+
+    /*
+     * Calculate the Merkle root of the block transactions
+     */
+    pub fn merkle_root(&self) -> String {
+        merkle_root(&self.transaction_hashes())
+    }
+
+    /*
+     * Create a proof that the given transaction is in the block.
+     * Blocks with a legacy hash don't commit to a Merkle root, so there is no proof for them.
+     */
+    pub fn merkle_proof(&self, transaction_hash: &str) -> Option<MerkleProof> {
+        if self.hash.version == LEGACY_HASH_VERSION {
+            return None;
+        }
+
+        let transaction_hashes = self.transaction_hashes();
+        let index = transaction_hashes
+            .iter()
+            .position(|hash| hash == transaction_hash)?;
+
+        MerkleProof::generate(&transaction_hashes, index)
+    }
+
+    /*
+     * Verify a transaction of the block, the genesis block can have the unsigned stakes of it's chain spec
+     */
+    pub fn verify_transaction(&self, transaction: &Transaction, chain: &ChainId) -> bool {
+        if self.previous_hash.is_none() {
+            transaction.verify_genesis_with_version(self.hash.version, chain)
+        } else {
+            transaction.verify_with_version(self.hash.version, chain)
+        }
+    }
+
+    /*
+     * Make sure the block was signed by the given account for the given chain
+     */
+    pub fn verify_sign_with(&self, acc: &impl SignVerifier, chain: &ChainId) -> bool {
+        acc.verify_signature(
+            &self.signature,
+            chain.signing_data_with_version(self.hash.version, &self.hash.unite()),
+        )
+    }
+}
+
+impl Encode for Block {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.hash.encode(encoder);
+        encoder.option(self.previous_hash.as_ref(), |encoder, previous_hash| {
+            previous_hash.encode(encoder);
+        });
+        encode_timestamp(encoder, self.hash.version, &self.timestamp);
+        encoder
+            .list(&self.transactions)
+            .bytes(&self.key.0)
+            .bytes(&self.signature.0);
     }
 }
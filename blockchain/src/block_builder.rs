@@ -1,11 +1,13 @@
 use chrono::{
     DateTime,
+    SubsecRound,
     Utc,
 };
 
 use crate::{
     Block,
     BlockHash,
+    ChainId,
     Key,
     Transaction,
     Wallet,
@@ -43,7 +45,8 @@ impl BlockBuilder {
     }
 
     pub fn timestamp(&mut self, timestamp: DateTime<Utc>) -> &mut Self {
-        self.timestamp = Some(timestamp);
+        // Blocks are hashed with the timestamp in milliseconds, see `TIMESTAMP_HASH_VERSION`
+        self.timestamp = Some(timestamp.trunc_subsecs(3));
         self
     }
 
@@ -52,15 +55,20 @@ impl BlockBuilder {
         self
     }
 
-    pub fn sign_with(&mut self, acc: &Wallet) -> &mut Self {
-        self.signature = Some(acc.sign_data(self.hash.as_ref().unwrap().unite()));
+    /*
+     * Sign the block for the given chain, it must be hashed already
+     */
+    pub fn sign_with(&mut self, acc: &Wallet, chain: &ChainId) -> &mut Self {
+        let hash = self.hash.as_ref().unwrap();
+        self.signature =
+            Some(acc.sign_data(chain.signing_data_with_version(hash.version, &hash.unite())));
         self
     }
 
     pub fn hash_it(&mut self) -> &mut Self {
         self.hash = Some(BlockHash::new(
             &self.transactions,
-            self.timestamp.unwrap().to_string(),
+            self.timestamp.unwrap(),
             self.previous_hash.clone(),
             self.key.as_ref().unwrap().clone(),
         ));
@@ -1,71 +1,186 @@
-use std::sync::{
-    Arc,
-    Mutex,
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc,
+        Mutex,
+    },
 };
 
 use crate::{
+    migrate,
+    validation::{
+        validate_link,
+        validate_structure,
+    },
+    AddressHistoryEntry,
     Block,
     BlockHash,
+    BlockHeader,
+    BlockStore,
+    BlockTree,
+    BlockUndo,
+    BlockValidationError,
+    ChainId,
     Chainstate,
     Configuration,
+    ConsensusParams,
+    MerkleProof,
     Metrics,
-    PublicAddress,
+    PruningMode,
     Transaction,
+    TransactionLocation,
+    SCHEMA_VERSION,
 };
 
+/// How many blocks or headers can be requested in a single range
+pub static MAX_RANGE: usize = 500;
+
 #[derive(Clone)]
 pub struct Blockchain {
     pub chain: Vec<Block>,
     pub index: usize,
     pub last_block_hash: Option<BlockHash>,
     pub config: Arc<Mutex<Configuration>>,
+    pub store: Arc<dyn BlockStore>,
     pub state: Chainstate,
     pub metrics: Arc<Mutex<Metrics>>,
+    /// Side branches competing with the main chain
+    pub forks: BlockTree,
+    /// Undo records of the last blocks (up to `max_fork_depth`), the newest at the back
+    pub undo_journal: VecDeque<BlockUndo>,
 }
 
 #[derive(Debug)]
 pub enum BlockchainErrors {
-    InvalidPrevioushHash(String, String),
-    InvalidSignature,
     InvalidHash,
     CouldntLoadBlock(String),
     CouldntAddBlock(String),
-    MultipleCoinbase(String),
-    InvalidCoinbaseAddress(String),
-    InvalidBlockForger(String),
+    CouldntOpenStorage(String),
+    CouldntPersistBlock(String),
+    CouldntPersistSnapshot(String),
+    CouldntExportChain(String),
+    CouldntImportChain(String),
+    InvalidBlock(BlockValidationError),
+    CouldntPruneBlocks(String),
+    PrunedBlock(usize),
+    CouldntMigrateStorage(String),
+    UnsupportedSchemaVersion(u32),
+    /// Hash of the missing previous block
+    UnknownPreviousBlock(String),
+    /// A block that isn't the first one has no previous hash
+    InvalidPreviousHash(String),
+    ForkTooDeep(String),
+    CouldntRollbackBlocks(String),
+    InvalidChainSpec(String),
+    InvalidConfiguration(String),
+    /// The requested range starts before the first block or it's longer than `MAX_RANGE`
+    InvalidRange(usize, usize),
 }
 
 impl Blockchain {
+    /*
+     * Create a blockchain using the storage set in the configuration
+     */
     pub async fn new(config: Configuration, metrics: Arc<Mutex<Metrics>>) -> Self {
-        let chain = config.get_blocks().await.unwrap();
+        let store = config.storage.open(config.id).unwrap();
+        Self::with_store(config, store, metrics).await
+    }
 
-        tracing::info!("(Node.{}) Loaded blockchain from database", config.id);
+    /*
+     * Create a blockchain that uses the given storage
+     */
+    pub async fn with_store(
+        config: Configuration,
+        store: Arc<dyn BlockStore>,
+        metrics: Arc<Mutex<Metrics>>,
+    ) -> Self {
+        config.verify().unwrap();
 
-        let index = chain.len() as usize;
+        // Upgrade databases saved by older versions before reading anything from them
+        let schema_version = migrate(store.as_ref()).unwrap();
 
-        let last_block_hash = if !chain.is_empty() {
-            Some(chain[chain.len() - 1].hash.clone())
-        } else {
-            None
-        };
+        if schema_version < SCHEMA_VERSION {
+            tracing::info!(
+                "(Node.{}) Storage schema upgraded from version {} to {}",
+                config.id,
+                schema_version,
+                SCHEMA_VERSION
+            );
+        }
 
-        // Make sure the integrity of the chain is OK
-        assert!(verify_integrity(&chain).is_ok());
+        let index = store.get_height();
 
-        let config = Arc::new(Mutex::new(config));
+        let mut state = Chainstate::with_consensus(store.clone(), config.consensus.clone());
+
+        let snapshot_height = state.load_from_chain();
+
+        tracing::info!(
+            "(Node.{}) Loaded chainstate from the snapshot at height {}",
+            config.id,
+            snapshot_height
+        );
+
+        let recent_blocks = config.recent_blocks.max(1);
+
+        /*
+         * The blocks after the snapshot are loaded (including the snapshot's block to check they are linked)
+         * together with the recent blocks that are kept in memory, as long as they haven't been pruned
+         */
+        let first_recent_block = (index + 1)
+            .saturating_sub(recent_blocks)
+            .max(store.get_pruned_height() + 1);
+        let mut chain =
+            store.get_blocks_range(first_recent_block.min(snapshot_height.max(1)), index);
+
+        tracing::info!("(Node.{}) Loaded blockchain from database", config.id);
+
+        let last_block_hash = chain.last().map(|block| block.hash.clone());
+
+        /*
+         * Make sure the integrity of the chain is OK
+         * The blocks up to the snapshot were already verified when they were added
+         */
+        for block in &chain {
+            assert!(block.verify_integrity().is_ok());
+        }
+        assert!(verify_integrity(&chain, &config.chain()).is_ok());
+
+        // Only keep the recent blocks in memory
+        chain.drain(..chain.len().saturating_sub(recent_blocks));
 
-        let mut state = Chainstate::new(config.clone());
+        let genesis_block = config
+            .chain_spec
+            .as_ref()
+            .map(|chain_spec| chain_spec.genesis_block().unwrap());
 
-        state.load_from_chain().await;
+        let config = Arc::new(Mutex::new(config));
 
-        Self {
+        let mut blockchain = Self {
             chain,
             index,
             last_block_hash,
             config,
+            store,
             state,
             metrics,
+            forks: BlockTree::default(),
+            undo_journal: VecDeque::new(),
+        };
+
+        // Chains launched from a spec start with it's genesis block
+        if let Some(genesis_block) = genesis_block {
+            if index == 0 {
+                blockchain.add_block(&genesis_block).unwrap();
+            } else {
+                let stored_genesis = blockchain.store.get_header_by_height(1).unwrap();
+                assert!(
+                    stored_genesis.hash == genesis_block.hash,
+                    "The stored chain doesn't start with the genesis block of the chain spec"
+                );
+            }
         }
+
+        blockchain
     }
 
     /*
@@ -86,7 +201,13 @@ impl Blockchain {
             if self.last_block_hash.as_ref() == Some(&block.hash) {
                 false
             } else if let Some(block_hash) = self.last_block_hash.as_ref() {
-                if block_hash.clone() != block.clone().previous_hash.unwrap() {
+                // Only the first block has no previous block
+                let previous_hash = match &block.previous_hash {
+                    Some(previous_hash) => previous_hash,
+                    None => return Err(BlockchainErrors::InvalidPreviousHash(block.hash.unite())),
+                };
+
+                if block_hash != previous_hash {
                     tracing::warn!(
                         "(Node.{}) Tried to add a faulty block ({}) to the chain.",
                         self.config.lock().unwrap().id,
@@ -102,19 +223,44 @@ impl Blockchain {
         };
 
         if block_can_be_added {
-            // Add the block to the database
-            self.config.lock().unwrap().add_block(&block);
-
-            // Update chainstate with the new transactions
-            for tx in transactions.iter() {
-                self.state.effect_transaction(tx);
+            /*
+             * Add the block to the database before touching the chainstate, this way
+             * the chain in memory never gets ahead of what has been persisted
+             */
+            if let Err(err) = self.store.add_block(&block) {
+                tracing::error!(
+                    "(Node.{}) Couldn't persist the block ({}): {:?}",
+                    self.config.lock().unwrap().id,
+                    block.hash.unite(),
+                    err
+                );
+                return Err(err);
             }
 
+            // Update chainstate with the new transactions, keeping how to revert them
+            let undo = self.state.apply_block(&block);
+            self.push_undo(undo);
+
             self.index += 1;
 
-            // Keep the chain at length 1 for now
-            if !self.chain.is_empty() {
-                self.chain.remove(0);
+            // Periodically save the chainstate so the node doesn't need to replay the whole chain on startup
+            let snapshot_interval = self.config.lock().unwrap().snapshot_interval;
+            if snapshot_interval > 0 && self.index.is_multiple_of(snapshot_interval) {
+                let snapshot = self.state.snapshot(self.index);
+                match self.store.save_snapshot(&snapshot) {
+                    Ok(_) => self.prune(),
+                    Err(err) => tracing::warn!(
+                        "(Node.{}) Couldn't save the chainstate snapshot: {:?}",
+                        self.config.lock().unwrap().id,
+                        err
+                    ),
+                }
+            }
+
+            // Only keep the recent blocks in memory
+            let recent_blocks = self.config.lock().unwrap().recent_blocks.max(1);
+            if self.chain.len() >= recent_blocks {
+                self.chain.drain(..=self.chain.len() - recent_blocks);
             }
 
             self.chain.push(block.clone());
@@ -141,10 +287,58 @@ impl Blockchain {
         }
     }
 
+    /*
+     * Save the undo record of the last applied block, only the ones a reorganization can reach are kept
+     */
+    pub(crate) fn push_undo(&mut self, undo: BlockUndo) {
+        let max_fork_depth = self.config.lock().unwrap().max_fork_depth;
+
+        self.undo_journal.push_back(undo);
+
+        while self.undo_journal.len() > max_fork_depth {
+            self.undo_journal.pop_front();
+        }
+    }
+
+    /*
+     * Drop the transactions of the blocks that are out of the retention window.
+     * Blocks are never pruned up to the last snapshot, so the chainstate can still be loaded from it.
+     */
+    fn prune(&self) {
+        let keep_blocks = match self.config.lock().unwrap().pruning {
+            PruningMode::Archive => return,
+            PruningMode::Pruned { keep_blocks } => keep_blocks,
+        };
+
+        let up_to = self.index.saturating_sub(keep_blocks).min(self.index - 1);
+
+        if up_to > 0 {
+            if let Err(err) = self.store.prune_blocks(up_to) {
+                tracing::warn!(
+                    "(Node.{}) Couldn't prune the blocks up to {}: {:?}",
+                    self.config.lock().unwrap().id,
+                    up_to,
+                    err
+                );
+            }
+        }
+    }
+
+    /*
+     * Make sure the block at the given height still has it's transactions
+     */
+    fn check_not_pruned(&self, height: usize) -> Result<(), BlockchainErrors> {
+        if height <= self.store.get_pruned_height() {
+            Err(BlockchainErrors::PrunedBlock(height))
+        } else {
+            Ok(())
+        }
+    }
+
     /*
      * Return the chain iterator
      */
-    pub fn iter(&self) -> std::slice::Iter<Block> {
+    pub fn iter(&self) -> std::slice::Iter<'_, Block> {
         self.chain.iter()
     }
 
@@ -155,89 +349,246 @@ impl Blockchain {
         self.chain.last()
     }
 
+    /*
+     * Consensus parameters in effect for the next block of the chain
+     */
+    pub fn consensus_params(&self) -> ConsensusParams {
+        self.state.consensus_params()
+    }
+
+    /*
+     * Chain the transactions and blocks must be signed for
+     */
+    pub fn chain_id(&self) -> ChainId {
+        self.config.lock().unwrap().chain()
+    }
+
+    /*
+     * Return up to the last `n` blocks kept in memory, from the oldest to the newest
+     */
+    pub fn recent(&self, n: usize) -> &[Block] {
+        &self.chain[self.chain.len().saturating_sub(n)..]
+    }
+
+    /*
+     * Get a block from the ones kept in memory
+     */
+    fn get_recent_block(&self, height: usize) -> Option<&Block> {
+        let first_height = self.index + 1 - self.chain.len();
+
+        if height >= first_height {
+            self.chain.get(height - first_height)
+        } else {
+            None
+        }
+    }
+
     /*
      * Verify the integrity of the blockchain
      */
     pub fn verify_integrity(&self) -> Result<(), BlockchainErrors> {
-        verify_integrity(&self.chain)
+        verify_integrity(&self.chain, &self.chain_id())
     }
 
     /*
-     * Get a block that hash the same previous hash
+     * Get the block at the given height
      */
-    pub async fn get_block_with_prev_hash(&self, prev_hash: String) -> Option<Block> {
-        self.config
-            .lock()
-            .unwrap()
-            .get_block_with_prev_hash(prev_hash)
-            .await
+    pub fn get_block_by_height(&self, height: usize) -> Result<Option<Block>, BlockchainErrors> {
+        if let Some(block) = self.get_recent_block(height) {
+            return Ok(Some(block.clone()));
+        }
+
+        self.check_not_pruned(height)?;
+        Ok(self.store.get_block_by_height(height))
     }
 
     /*
-     * Get a block by it's corresponding hash
+     * Get the blocks from the height `from` up to `to` (both included)
      */
-    pub async fn get_block_with_hash(&self, hash: String) -> Option<Block> {
-        self.config.lock().unwrap().get_block_with_hash(hash).await
+    pub fn get_blocks_range(&self, from: usize, to: usize) -> Result<Vec<Block>, BlockchainErrors> {
+        if from > to {
+            return Ok(Vec::new());
+        }
+        check_range(from, to)?;
+
+        // The whole range is in memory
+        if self.get_recent_block(from).is_some() {
+            let to = to.min(self.index);
+            return Ok((from..=to)
+                .filter_map(|height| self.get_recent_block(height).cloned())
+                .collect());
+        }
+
+        self.check_not_pruned(from)?;
+        Ok(self.store.get_blocks_range(from, to))
     }
-}
 
-/*
- * This iterates over a chain of blocks and makes sure that all the blocks and transactions are correct
- * This only makes sense to be run on the node startup to make sure the DB has not been modified
- */
-fn verify_integrity(chain: &[Block]) -> Result<(), BlockchainErrors> {
-    for (i, block) in chain.iter().enumerate() {
-        let block_hash = &block.hash;
-        let block_author = block.key.hash_it();
-        let block_txs = &block.transactions;
-
-        for (tx_i, tx) in block_txs.iter().enumerate() {
-            if let Transaction::COINBASE { hash, .. } = tx {
-                // There can only be one coinbase in each block
-                if tx_i > 0 {
-                    return Err(BlockchainErrors::MultipleCoinbase(hash.to_string()));
-                }
+    /*
+     * Get the header of the block at the given height, headers are kept even when the block is pruned
+     */
+    pub fn get_header_by_height(&self, height: usize) -> Option<BlockHeader> {
+        self.store.get_header_by_height(height)
+    }
+
+    /*
+     * Get the headers from the height `from` up to `to` (both included)
+     */
+    pub fn get_headers_range(
+        &self,
+        from: usize,
+        to: usize,
+    ) -> Result<Vec<BlockHeader>, BlockchainErrors> {
+        if from > to {
+            return Ok(Vec::new());
+        }
+        check_range(from, to)?;
+
+        Ok(self.store.get_headers_range(from, to))
+    }
+
+    /*
+     * Get a confirmed transaction by it's hash together with where it was confirmed
+     */
+    pub fn get_transaction(
+        &self,
+        hash: &str,
+    ) -> Result<Option<(Transaction, TransactionLocation)>, BlockchainErrors> {
+        // The same hash might have been confirmed in several blocks, the newest one is given
+        for location in self.store.get_transaction_locations(hash).into_iter().rev() {
+            self.check_not_pruned(location.height)?;
+
+            let block = match self.store.get_block_by_height(location.height) {
+                Some(block) => block,
+                None => continue,
+            };
+
+            // Make sure the index isn't pointing to a different block
+            if block.hash != location.block_hash {
+                continue;
             }
 
-            if tx_i == 0 {
-                // The first transaction must always be a coinbase rewarding the block creator
-                if let Transaction::COINBASE {
-                    to_address, hash, ..
-                } = tx
-                {
-                    if to_address != &block_author {
-                        return Err(BlockchainErrors::InvalidCoinbaseAddress(hash.clone()));
-                    }
-                } else {
-                    return Err(BlockchainErrors::MultipleCoinbase(block_hash.unite()));
-                }
+            if let Some(transaction) = block
+                .transactions
+                .get(location.position)
+                .filter(|transaction| transaction.get_hash() == hash)
+            {
+                return Ok(Some((transaction.clone(), location)));
             }
         }
 
-        if i > 0 {
-            let previous_block = &chain[i - 1];
-            let previous_hash = block.previous_hash.as_ref().unwrap();
-
-            // The previous hash must be the same as the previous block's hash
-            if previous_hash.unite() != previous_block.hash.unite() {
-                return Err(BlockchainErrors::InvalidPrevioushHash(
-                    previous_hash.hash.to_string(),
-                    previous_block.hash.hash.to_string(),
-                ));
+        Ok(None)
+    }
+
+    /*
+     * Get a Merkle inclusion proof of a confirmed transaction, together with the block where it was confirmed.
+     * There is no proof for transactions in blocks with a legacy hash.
+     */
+    pub fn get_transaction_proof(
+        &self,
+        hash: &str,
+    ) -> Result<Option<(MerkleProof, TransactionLocation)>, BlockchainErrors> {
+        let location = match self.get_transaction(hash)? {
+            Some((_, location)) => location,
+            None => return Ok(None),
+        };
+
+        let block = match self.get_block_by_height(location.height)? {
+            Some(block) => block,
+            None => return Ok(None),
+        };
+
+        Ok(block.merkle_proof(hash).map(|proof| (proof, location)))
+    }
+
+    /*
+     * Get the confirmed transactions of an address from the oldest to the newest, skipping the first `skip` ones.
+     * At most `MAX_RANGE` transactions are returned at once.
+     */
+    pub fn get_address_history(
+        &self,
+        address: &str,
+        skip: usize,
+        limit: usize,
+    ) -> Result<Vec<(Transaction, AddressHistoryEntry)>, BlockchainErrors> {
+        let entries = self
+            .store
+            .get_address_history(address, skip, limit.min(MAX_RANGE));
+
+        if let Some(entry) = entries.first() {
+            self.check_not_pruned(entry.location.height)?;
+        }
+
+        Ok(entries
+            .into_iter()
+            .filter_map(|entry| {
+                let block = self.store.get_block_by_height(entry.location.height)?;
+                let transaction = block.transactions.get(entry.location.position)?.clone();
+
+                // Make sure the entry isn't left from a block that was rolled back
+                if transaction.get_hash() == entry.transaction_hash {
+                    Some((transaction, entry))
+                } else {
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /*
+     * Get a block that hash the same previous hash
+     */
+    pub fn get_block_with_prev_hash(
+        &self,
+        prev_hash: String,
+    ) -> Result<Option<Block>, BlockchainErrors> {
+        match self.store.get_block_with_prev_hash(&prev_hash) {
+            Some(block) => {
+                self.check_not_pruned(block.index.unwrap_or(0))?;
+                Ok(Some(block))
             }
+            None => Ok(None),
+        }
+    }
 
-            //It should also check if the block forger isn't the same as the previous one
-            if previous_block.key == block.key {
-                return Err(BlockchainErrors::InvalidBlockForger(block.hash.unite()));
+    /*
+     * Get a block by it's corresponding hash
+     */
+    pub fn get_block_with_hash(&self, hash: String) -> Result<Option<Block>, BlockchainErrors> {
+        match self.store.get_block_with_hash(&hash) {
+            Some(block) => {
+                self.check_not_pruned(block.index.unwrap_or(0))?;
+                Ok(Some(block))
             }
+            None => Ok(None),
         }
+    }
+}
 
-        let block_signer = PublicAddress::from(&block.key);
+/*
+ * This iterates over a chain of blocks and makes sure that all the blocks and transactions are correct
+ * This only makes sense to be run on the node startup to make sure the DB has not been modified
+ */
+pub(crate) fn verify_integrity(
+    chain: &[Block],
+    chain_id: &ChainId,
+) -> Result<(), BlockchainErrors> {
+    for (i, block) in chain.iter().enumerate() {
+        validate_structure(block, chain_id).map_err(BlockchainErrors::InvalidBlock)?;
 
-        // The signature must be correct according the public key and the block data
-        if !block.verify_sign_with(&block_signer) {
-            return Err(BlockchainErrors::InvalidSignature);
+        if i > 0 {
+            validate_link(block, &chain[i - 1].header()).map_err(BlockchainErrors::InvalidBlock)?;
         }
     }
     Ok(())
 }
+
+/*
+ * Ranges start at the first block, and they can't be longer than `MAX_RANGE`
+ */
+fn check_range(from: usize, to: usize) -> Result<(), BlockchainErrors> {
+    if from == 0 || to - from >= MAX_RANGE {
+        Err(BlockchainErrors::InvalidRange(from, to))
+    } else {
+        Ok(())
+    }
+}
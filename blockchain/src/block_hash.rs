@@ -1,3 +1,7 @@
+use chrono::{
+    DateTime,
+    Utc,
+};
 use crypto::{
     digest::Digest,
     sha3::{
@@ -11,11 +15,40 @@ use serde::{
 };
 
 use crate::{
+    merkle_root,
+    Encode,
+    Encoder,
     Key,
     Transaction,
+    BLOCK_TAG,
 };
 
-static HASH_VERSION: u8 = 1;
+/// Version new blocks and transactions are hashed with
+pub static HASH_VERSION: u8 = 9;
+
+/// Blocks hashed before the Merkle root was introduced, they hash the serialized transactions
+pub static LEGACY_HASH_VERSION: u8 = 1;
+
+/// First version hashed with the canonical encoding, older ones concatenate decimal strings
+pub static CANONICAL_HASH_VERSION: u8 = 3;
+
+/// First version that commits to the fees of the transactions, older ones can't have fees
+pub static FEE_HASH_VERSION: u8 = 4;
+
+/// First version whose signatures are bound to the chain, see `ChainId`
+pub static CHAIN_ID_HASH_VERSION: u8 = 5;
+
+/// First version with delegations, it commits to the commission of the stakes
+pub static DELEGATION_HASH_VERSION: u8 = 6;
+
+/// First version with batch payments
+pub static BATCH_HASH_VERSION: u8 = 7;
+
+/// First version with undelegations
+pub static UNDELEGATION_HASH_VERSION: u8 = 8;
+
+/// First version that encodes the block timestamp as milliseconds since the epoch, older ones use it's text form
+pub static TIMESTAMP_HASH_VERSION: u8 = 9;
 
 #[derive(Hash, PartialEq, Eq, Clone, Debug, Serialize, Deserialize, Default)]
 pub struct BlockHash {
@@ -26,16 +59,124 @@ pub struct BlockHash {
 impl BlockHash {
     pub fn new(
         transactions: &[Transaction],
-        timestamp: String,
+        timestamp: DateTime<Utc>,
+        previous_hash: Option<BlockHash>,
+        key: Key,
+    ) -> Self {
+        Self::with_version(HASH_VERSION, transactions, timestamp, previous_hash, key)
+    }
+
+    /*
+     * Hash a block the way it was done in the given version
+     */
+    pub fn with_version(
+        version: u8,
+        transactions: &[Transaction],
+        timestamp: DateTime<Utc>,
         previous_hash: Option<BlockHash>,
         key: Key,
     ) -> Self {
+        if version == LEGACY_HASH_VERSION {
+            return Self::legacy(transactions, timestamp, previous_hash, key);
+        }
+
+        let transaction_hashes = transactions
+            .iter()
+            .map(|tx| tx.get_hash())
+            .collect::<Vec<String>>();
+
+        Self::from_merkle_root_with_version(
+            version,
+            &merkle_root(&transaction_hashes),
+            timestamp,
+            previous_hash,
+            key,
+        )
+    }
+
+    /*
+     * Hash a block only knowing the Merkle root of it's transactions
+     */
+    pub fn from_merkle_root(
+        merkle_root: &str,
+        timestamp: DateTime<Utc>,
+        previous_hash: Option<BlockHash>,
+        key: Key,
+    ) -> Self {
+        Self::from_merkle_root_with_version(
+            HASH_VERSION,
+            merkle_root,
+            timestamp,
+            previous_hash,
+            key,
+        )
+    }
+
+    /*
+     * Hash a block only knowing the Merkle root of it's transactions, the way it was done in the given version
+     */
+    pub fn from_merkle_root_with_version(
+        version: u8,
+        merkle_root: &str,
+        timestamp: DateTime<Utc>,
+        previous_hash: Option<BlockHash>,
+        key: Key,
+    ) -> Self {
+        if version < CANONICAL_HASH_VERSION {
+            return Self::hash_strings(version, merkle_root, timestamp, previous_hash, key);
+        }
+
+        let mut encoder = Encoder::new();
+        encoder.u8(version).u8(BLOCK_TAG).str(merkle_root);
+        encode_timestamp(&mut encoder, version, &timestamp);
+        encoder
+            .bytes(&key.0)
+            .option(previous_hash.as_ref(), |encoder, previous_hash| {
+                encoder.str(&previous_hash.hash);
+            });
+
         let mut hasher = Sha3::new(Sha3Mode::Keccak256);
+        hasher.input(&encoder.into_bytes());
+
+        let hash = hasher.result_str();
+        Self { hash, version }
+    }
+
+    /*
+     * Hash a block the way it was done before the Merkle root was introduced
+     */
+    pub fn legacy(
+        transactions: &[Transaction],
+        timestamp: DateTime<Utc>,
+        previous_hash: Option<BlockHash>,
+        key: Key,
+    ) -> Self {
         let transactions = serde_json::to_string(&transactions).unwrap();
 
-        hasher.input_str(&HASH_VERSION.to_string());
-        hasher.input_str(&transactions);
-        hasher.input_str(&timestamp);
+        Self::hash_strings(
+            LEGACY_HASH_VERSION,
+            &transactions,
+            timestamp,
+            previous_hash,
+            key,
+        )
+    }
+
+    /*
+     * Hash the fields concatenated as strings, like the versions before the canonical encoding
+     */
+    fn hash_strings(
+        version: u8,
+        transactions: &str,
+        timestamp: DateTime<Utc>,
+        previous_hash: Option<BlockHash>,
+        key: Key,
+    ) -> Self {
+        let mut hasher = Sha3::new(Sha3Mode::Keccak256);
+
+        hasher.input_str(&version.to_string());
+        hasher.input_str(transactions);
+        hasher.input_str(&timestamp.to_string());
         hasher.input_str(&key.to_string());
 
         if let Some(previous_hash) = previous_hash {
@@ -43,13 +184,41 @@ impl BlockHash {
         }
 
         let hash = hasher.result_str();
-        Self {
-            hash,
-            version: HASH_VERSION,
-        }
+        Self { hash, version }
+    }
+
+    /*
+     * Make sure the version is one this node knows how to hash
+     */
+    pub fn is_known_version(&self) -> bool {
+        (LEGACY_HASH_VERSION..=HASH_VERSION).contains(&self.version)
     }
 
     pub fn unite(&self) -> String {
         format!("{}x{}", self.version, self.hash)
     }
 }
+
+/*
+ * Write a block timestamp the way it's done in the given version
+ */
+pub(crate) fn encode_timestamp(encoder: &mut Encoder, version: u8, timestamp: &DateTime<Utc>) {
+    if version >= TIMESTAMP_HASH_VERSION {
+        encoder.i64(timestamp.timestamp_millis());
+    } else {
+        encoder.str(&timestamp.to_string());
+    }
+}
+
+/*
+ * Make sure the hash of the given version commits to the whole timestamp, milliseconds can't have a fraction
+ */
+pub(crate) fn is_timestamp_hashable(version: u8, timestamp: &DateTime<Utc>) -> bool {
+    version < TIMESTAMP_HASH_VERSION || timestamp.timestamp_subsec_nanos().is_multiple_of(1_000_000)
+}
+
+impl Encode for BlockHash {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.u8(self.version).str(&self.hash);
+    }
+}
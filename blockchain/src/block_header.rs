@@ -0,0 +1,107 @@
+use chrono::{
+    DateTime,
+    Utc,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    block_hash::is_timestamp_hashable,
+    BlockHash,
+    BlocksErrors,
+    ChainId,
+    Key,
+    PublicAddress,
+    SignVerifier,
+    LEGACY_HASH_VERSION,
+};
+
+/// The fields of a block without it's transactions, enough to follow the chain and check Merkle proofs
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub hash: BlockHash,
+    pub previous_hash: Option<BlockHash>,
+    pub timestamp: DateTime<Utc>,
+    /// Merkle root of the block transactions
+    pub merkle_root: String,
+    pub key: Key,
+    pub signature: Key,
+    pub index: Option<usize>,
+}
+
+impl BlockHeader {
+    /*
+     * Make sure the hash matches the header fields.
+     * Legacy hashes were made out of the whole transactions, so they can't be checked from the header.
+     */
+    pub fn verify_integrity(&self) -> Result<(), BlocksErrors> {
+        if self.hash.version == LEGACY_HASH_VERSION {
+            return Err(BlocksErrors::LegacyHash);
+        }
+
+        if !self.hash.is_known_version() {
+            return Err(BlocksErrors::WrongHash);
+        }
+
+        // Timestamps are hashed as milliseconds from this version on
+        if !is_timestamp_hashable(self.hash.version, &self.timestamp) {
+            return Err(BlocksErrors::WrongHash);
+        }
+
+        let must_hash = BlockHash::from_merkle_root_with_version(
+            self.hash.version,
+            &self.merkle_root,
+            self.timestamp,
+            self.previous_hash.clone(),
+            self.key.clone(),
+        );
+
+        if must_hash == self.hash {
+            Ok(())
+        } else {
+            Err(BlocksErrors::WrongHash)
+        }
+    }
+
+    /*
+     * Make sure the header was signed by it's forger for the given chain
+     */
+    pub fn verify_sign(&self, chain: &ChainId) -> bool {
+        PublicAddress::from(&self.key).verify_signature(
+            &self.signature,
+            chain.signing_data_with_version(self.hash.version, &self.hash.unite()),
+        )
+    }
+
+    /*
+     * Verify the header on it's own and linked to it's parent, genesis headers don't have a parent
+     */
+    pub fn verify(
+        &self,
+        previous_header: Option<&BlockHeader>,
+        chain: &ChainId,
+    ) -> Result<(), BlocksErrors> {
+        self.verify_integrity()?;
+
+        if !self.verify_sign(chain) {
+            return Err(BlocksErrors::WrongSignature);
+        }
+
+        let is_linked = match previous_header {
+            Some(previous_header) => {
+                self.previous_hash.as_ref() == Some(&previous_header.hash)
+                    && self.index.is_some()
+                    && self.index == previous_header.index.map(|index| index + 1)
+            }
+            None => self.previous_hash.is_none(),
+        };
+
+        if is_linked {
+            Ok(())
+        } else {
+            Err(BlocksErrors::WrongPreviousHash)
+        }
+    }
+}
@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+
+use crate::{
+    validate_block,
+    validation::{
+        validate_size,
+        validate_structure,
+    },
+    Block,
+    BlockUndo,
+    Blockchain,
+    BlockchainErrors,
+    Chainstate,
+    Transaction,
+};
+
+/// Blocks that don't belong to the main chain but are linked to it, they form the competing branches
+#[derive(Clone, Debug, Default)]
+pub struct BlockTree {
+    /// Block hash -> Side block, with it's height as `index`
+    blocks: HashMap<String, Block>,
+}
+
+impl BlockTree {
+    pub fn insert(&mut self, block: Block) {
+        self.blocks.insert(block.hash.hash.clone(), block);
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&Block> {
+        self.blocks.get(hash)
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.blocks.contains_key(hash)
+    }
+
+    pub fn remove(&mut self, hash: &str) -> Option<Block> {
+        self.blocks.remove(hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /*
+     * Get the side blocks from the one that forks from the main chain up to the given block
+     */
+    pub fn branch(&self, hash: &str) -> Vec<Block> {
+        let mut branch = Vec::new();
+        let mut current = self.blocks.get(hash);
+
+        while let Some(block) = current {
+            branch.push(block.clone());
+            current = block
+                .previous_hash
+                .as_ref()
+                .and_then(|previous_hash| self.blocks.get(&previous_hash.hash));
+        }
+
+        branch.reverse();
+        branch
+    }
+
+    /*
+     * Remove a block together with all the side blocks built on top of it
+     */
+    pub fn remove_with_descendants(&mut self, hash: &str) {
+        let mut pending = vec![hash.to_string()];
+
+        while let Some(hash) = pending.pop() {
+            if self.blocks.remove(&hash).is_some() {
+                pending.extend(
+                    self.blocks
+                        .values()
+                        .filter(|block| {
+                            block.previous_hash.as_ref().map(|previous| &previous.hash)
+                                == Some(&hash)
+                        })
+                        .map(|block| block.hash.hash.clone()),
+                );
+            }
+        }
+    }
+
+    /*
+     * Forget the side blocks at or below the given height, they are too old to ever win
+     */
+    pub fn prune_below(&mut self, height: usize) {
+        self.blocks
+            .retain(|_, block| block.index.unwrap_or(0) > height);
+    }
+
+    /*
+     * Forget the oldest side blocks, together with the blocks built on top of them, until there are at most the given amount
+     */
+    pub fn prune_to(&mut self, max_blocks: usize) {
+        while self.blocks.len() > max_blocks {
+            let oldest = self
+                .blocks
+                .values()
+                .min_by_key(|block| block.index.unwrap_or(0))
+                .map(|block| block.hash.hash.clone())
+                .unwrap();
+            self.remove_with_descendants(&oldest);
+        }
+    }
+}
+
+/*
+ * Fork choice rule: the longest chain wins, on a tie the current main chain is kept
+ */
+pub fn is_better_branch(branch_height: usize, main_height: usize) -> bool {
+    branch_height > main_height
+}
+
+/// What happened to a block given to `Blockchain::import_block`
+#[derive(Clone, Debug)]
+pub enum BlockImport {
+    /// The block was already in the main chain or in a side branch
+    AlreadyKnown,
+    /// The block was appended to the main chain
+    Extended,
+    /// The block was saved in a side branch that isn't better than the main chain
+    SideBranch,
+    /// The main chain switched to the branch of the block
+    Reorganized {
+        /// Height of the last block both branches share
+        ancestor_height: usize,
+        /// Blocks of the new branch that were appended to the main chain
+        applied_blocks: Vec<Block>,
+        /// Transactions of the abandoned blocks that aren't in the new branch
+        abandoned_transactions: Vec<Transaction>,
+    },
+}
+
+impl Blockchain {
+    /*
+     * Add a block that can either extend the main chain or a side branch.
+     * If a side branch becomes better than the main chain the chain is reorganized to it.
+     */
+    pub fn import_block(&mut self, block: &Block) -> Result<BlockImport, BlockchainErrors> {
+        let hash = &block.hash.hash;
+
+        if self.forks.contains(hash) || self.store.get_block_with_hash(hash).is_some() {
+            return Ok(BlockImport::AlreadyKnown);
+        }
+
+        /*
+         * Side blocks are fully validated once their branch is applied. Oversized blocks are rejected before hashing them,
+         * side blocks are close to the tip so it's parameters are used. The genesis block has no limits.
+         */
+        if block.previous_hash.is_some() {
+            validate_size(block, &self.consensus_params())
+                .map_err(BlockchainErrors::InvalidBlock)?;
+        }
+        validate_structure(block, &self.chain_id()).map_err(BlockchainErrors::InvalidBlock)?;
+
+        // The block extends the main chain
+        if block.previous_hash == self.last_block_hash {
+            validate_block(
+                block,
+                &self.state,
+                &self.consensus_params(),
+                &self.chain_id(),
+            )
+            .map_err(BlockchainErrors::InvalidBlock)?;
+
+            self.add_block(block)?;
+            self.prune_forks();
+
+            return Ok(BlockImport::Extended);
+        }
+
+        // Only one genesis block is accepted
+        let previous_hash = match &block.previous_hash {
+            Some(previous_hash) => previous_hash,
+            None => return Err(BlockchainErrors::InvalidPreviousHash(block.hash.unite())),
+        };
+
+        let parent_height = match self.forks.get(&previous_hash.hash) {
+            Some(parent) => parent.index.unwrap(),
+            None => match self.store.get_block_with_hash(&previous_hash.hash) {
+                Some(parent) => parent.index.unwrap(),
+                None => {
+                    return Err(BlockchainErrors::UnknownPreviousBlock(
+                        previous_hash.hash.clone(),
+                    ))
+                }
+            },
+        };
+
+        let max_fork_depth = self.config.lock().unwrap().max_fork_depth;
+        if parent_height + max_fork_depth < self.index {
+            return Err(BlockchainErrors::ForkTooDeep(block.hash.unite()));
+        }
+
+        let mut side_block = block.clone();
+        side_block.index = Some(parent_height + 1);
+        self.forks.insert(side_block);
+
+        if is_better_branch(parent_height + 1, self.index) {
+            self.reorganize(hash)
+        } else {
+            tracing::info!(
+                "(Node.{}) Saved block ({}) in a side branch at height {}",
+                self.config.lock().unwrap().id,
+                block.hash.unite(),
+                parent_height + 1
+            );
+            self.prune_forks();
+            Ok(BlockImport::SideBranch)
+        }
+    }
+
+    /*
+     * Switch the main chain to the branch ending in the given side block.
+     * The chainstate is reverted to the common ancestor and the blocks of the branch are applied on top.
+     */
+    fn reorganize(&mut self, tip_hash: &str) -> Result<BlockImport, BlockchainErrors> {
+        let branch = self.forks.branch(tip_hash);
+        let ancestor_height = branch[0].index.unwrap() - 1;
+
+        let ancestor = match ancestor_height {
+            0 => None,
+            height => Some(
+                self.store
+                    .get_block_by_height(height)
+                    .ok_or_else(|| BlockchainErrors::CouldntLoadBlock(height.to_string()))?,
+            ),
+        };
+
+        let abandoned_blocks = self.store.get_blocks_range(ancestor_height + 1, self.index);
+        if abandoned_blocks.len() != self.index - ancestor_height {
+            return Err(BlockchainErrors::CouldntLoadBlock(self.index.to_string()));
+        }
+
+        self.revert_state_to(ancestor_height)?;
+
+        // Make sure the whole branch is valid before touching the stored chain
+        let mut branch_undos = Vec::new();
+
+        for block in &branch {
+            match validate_block(
+                block,
+                &self.state,
+                &self.consensus_params(),
+                &self.chain_id(),
+            ) {
+                Ok(()) => branch_undos.push(self.state.apply_block(block)),
+                Err(err) => {
+                    tracing::warn!(
+                        "(Node.{}) Discarded the invalid side block ({}): {:?}",
+                        self.config.lock().unwrap().id,
+                        block.hash.unite(),
+                        err
+                    );
+                    self.forks.remove_with_descendants(&block.hash.hash);
+                    self.restore_state(branch_undos, &abandoned_blocks);
+                    return Err(BlockchainErrors::InvalidBlock(err));
+                }
+            }
+        }
+
+        // The branch is applied again once it's persisted
+        for undo in branch_undos.into_iter().rev() {
+            self.state.revert_block(undo);
+        }
+
+        if let Err(err) = self.store.rollback_to(ancestor_height) {
+            self.restore_state(Vec::new(), &abandoned_blocks);
+            return Err(err);
+        }
+
+        let applied_blocks = match self.extend_from(ancestor_height, &ancestor, &branch) {
+            Ok(applied_blocks) => applied_blocks,
+            Err(err) => {
+                tracing::error!(
+                    "(Node.{}) Couldn't persist the branch of the block ({}), going back to the previous main chain: {:?}",
+                    self.config.lock().unwrap().id,
+                    tip_hash,
+                    err
+                );
+
+                // Undo the part of the branch that was persisted and put the abandoned blocks back
+                self.revert_state_to(ancestor_height)?;
+                self.store.rollback_to(ancestor_height)?;
+                self.extend_from(ancestor_height, &ancestor, &abandoned_blocks)?;
+                return Err(err);
+            }
+        };
+
+        for block in &branch {
+            self.forks.remove(&block.hash.hash);
+        }
+
+        // The abandoned blocks become a side branch, so the chain can switch back to them
+        let mut abandoned_transactions = Vec::new();
+
+        for block in abandoned_blocks {
+            for tx in &block.transactions {
+                let is_in_new_branch = applied_blocks.iter().any(|applied_block| {
+                    applied_block
+                        .transactions
+                        .iter()
+                        .any(|applied_tx| applied_tx.get_hash() == tx.get_hash())
+                });
+
+                if !is_in_new_branch && !matches!(tx, Transaction::COINBASE { .. }) {
+                    abandoned_transactions.push(tx.clone());
+                }
+            }
+            self.forks.insert(block);
+        }
+
+        self.prune_forks();
+
+        tracing::warn!(
+            "(Node.{}) Reorganized the chain from height {} up to {}, {} transactions were abandoned",
+            self.config.lock().unwrap().id,
+            ancestor_height,
+            self.index,
+            abandoned_transactions.len()
+        );
+
+        Ok(BlockImport::Reorganized {
+            ancestor_height,
+            applied_blocks,
+            abandoned_transactions,
+        })
+    }
+
+    /*
+     * Append the given blocks on top of the ancestor, once the stored chain was rolled back to it
+     */
+    fn extend_from(
+        &mut self,
+        ancestor_height: usize,
+        ancestor: &Option<Block>,
+        blocks: &[Block],
+    ) -> Result<Vec<Block>, BlockchainErrors> {
+        self.index = ancestor_height;
+        self.last_block_hash = ancestor.as_ref().map(|block| block.hash.clone());
+
+        // Fill the recent blocks again, up to the ancestor
+        let recent_blocks = self.config.lock().unwrap().recent_blocks.max(1);
+        let first_recent_block = (ancestor_height + 1)
+            .saturating_sub(recent_blocks)
+            .max(self.store.get_pruned_height() + 1);
+        self.chain = self
+            .store
+            .get_blocks_range(first_recent_block, ancestor_height);
+
+        let mut applied_blocks = Vec::new();
+
+        for block in blocks {
+            self.add_block(block)?;
+            applied_blocks.push(self.peek().unwrap().clone());
+        }
+
+        Ok(applied_blocks)
+    }
+
+    /*
+     * Bring the chainstate back to right after the block at the given height.
+     * The undo records are used when they reach that far, otherwise it's calculated from the closest snapshot.
+     */
+    fn revert_state_to(&mut self, height: usize) -> Result<(), BlockchainErrors> {
+        let depth = self.index - height;
+
+        if self.undo_journal.len() >= depth {
+            for _ in 0..depth {
+                let undo = self.undo_journal.pop_back().unwrap();
+                self.state.revert_block(undo);
+            }
+        } else {
+            self.state = self.chainstate_at(height)?;
+            self.undo_journal.clear();
+        }
+
+        Ok(())
+    }
+
+    /*
+     * Go back to the main chain after a failed reorganization,
+     * reverting the given branch blocks and applying the abandoned main blocks again
+     */
+    fn restore_state(&mut self, branch_undos: Vec<BlockUndo>, main_blocks: &[Block]) {
+        for undo in branch_undos.into_iter().rev() {
+            self.state.revert_block(undo);
+        }
+
+        for block in main_blocks {
+            let undo = self.state.apply_block(block);
+            self.push_undo(undo);
+        }
+    }
+
+    /*
+     * Calculate the chainstate right after the block at the given height, starting from the closest snapshot
+     */
+    fn chainstate_at(&self, height: usize) -> Result<Chainstate, BlockchainErrors> {
+        let mut chainstate =
+            Chainstate::with_consensus(self.store.clone(), self.state.consensus.clone());
+
+        let snapshot_height = match self.store.get_snapshot_at(height) {
+            Some(snapshot) => {
+                let snapshot_height = snapshot.height;
+                chainstate.restore_snapshot(snapshot);
+                snapshot_height
+            }
+            None => 0,
+        };
+
+        if snapshot_height < height && snapshot_height < self.store.get_pruned_height() {
+            return Err(BlockchainErrors::PrunedBlock(snapshot_height + 1));
+        }
+
+        let blocks = self.store.get_blocks_range(snapshot_height + 1, height);
+        if blocks.len() != height - snapshot_height {
+            return Err(BlockchainErrors::CouldntLoadBlock(height.to_string()));
+        }
+
+        for block in blocks {
+            chainstate.apply_block(&block);
+        }
+
+        Ok(chainstate)
+    }
+
+    /*
+     * Forget the side branches that are too deep to ever replace the main chain
+     */
+    fn prune_forks(&mut self) {
+        let max_fork_depth = self.config.lock().unwrap().max_fork_depth;
+        self.forks
+            .prune_below(self.index.saturating_sub(max_fork_depth));
+
+        let max_fork_blocks = self.config.lock().unwrap().max_fork_blocks;
+        self.forks.prune_to(max_fork_blocks);
+    }
+}
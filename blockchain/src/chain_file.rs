@@ -0,0 +1,222 @@
+use std::io::{
+    ErrorKind,
+    Read,
+    Write,
+};
+
+use crate::{
+    validate_block,
+    Block,
+    BlockHash,
+    Blockchain,
+    BlockchainErrors,
+};
+
+/// Bytes every chain file starts with
+pub static CHAIN_FILE_MAGIC: &[u8; 4] = b"CHNF";
+
+/// Version of the chain file format
+pub static CHAIN_FILE_VERSION: u16 = 1;
+
+/// How many blocks are read from the storage at once when exporting
+static EXPORT_BATCH_SIZE: usize = 100;
+
+/// Header of a chain file, it's followed by the length-prefixed blocks
+///
+/// Layout (all integers are big endian):
+///
+/// ```text
+/// magic (4 bytes) | version (u16) | chain name (u32 length + utf8) | genesis hash (u32 length + json)
+/// block (u32 length + json) | block (u32 length + json) | ...
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChainFileHeader {
+    pub version: u16,
+    pub chain_name: String,
+    /// Hash of the first block, `None` if the chain is empty
+    pub genesis_hash: Option<BlockHash>,
+}
+
+impl ChainFileHeader {
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<(), BlockchainErrors> {
+        let genesis_hash = serde_json::to_vec(&self.genesis_hash).unwrap();
+
+        writer.write_all(CHAIN_FILE_MAGIC).map_err(export_error)?;
+        writer
+            .write_all(&self.version.to_be_bytes())
+            .map_err(export_error)?;
+        write_chunk(writer, self.chain_name.as_bytes())?;
+        write_chunk(writer, &genesis_hash)
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> Result<Self, BlockchainErrors> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic).map_err(import_error)?;
+        if &magic != CHAIN_FILE_MAGIC {
+            return Err(BlockchainErrors::CouldntImportChain(
+                "Not a chain file".to_string(),
+            ));
+        }
+
+        let mut version = [0; 2];
+        reader.read_exact(&mut version).map_err(import_error)?;
+        let version = u16::from_be_bytes(version);
+        if version > CHAIN_FILE_VERSION {
+            return Err(BlockchainErrors::CouldntImportChain(format!(
+                "Unsupported chain file version {}",
+                version
+            )));
+        }
+
+        let chain_name =
+            String::from_utf8(read_chunk(reader)?.unwrap_or_default()).map_err(import_error)?;
+        let genesis_hash = serde_json::from_slice(&read_chunk(reader)?.unwrap_or_default())
+            .map_err(import_error)?;
+
+        Ok(Self {
+            version,
+            chain_name,
+            genesis_hash,
+        })
+    }
+}
+
+fn export_error(err: impl ToString) -> BlockchainErrors {
+    BlockchainErrors::CouldntExportChain(err.to_string())
+}
+
+fn import_error(err: impl ToString) -> BlockchainErrors {
+    BlockchainErrors::CouldntImportChain(err.to_string())
+}
+
+fn write_chunk(writer: &mut impl Write, chunk: &[u8]) -> Result<(), BlockchainErrors> {
+    writer
+        .write_all(&(chunk.len() as u32).to_be_bytes())
+        .map_err(export_error)?;
+    writer.write_all(chunk).map_err(export_error)
+}
+
+/*
+ * Read a length-prefixed chunk, returns `None` if the reader was already at it's end
+ */
+fn read_chunk(reader: &mut impl Read) -> Result<Option<Vec<u8>>, BlockchainErrors> {
+    let mut length = [0; 4];
+    match reader.read_exact(&mut length) {
+        Ok(_) => {}
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(import_error(err)),
+    }
+
+    let mut chunk = vec![0; u32::from_be_bytes(length) as usize];
+    reader.read_exact(&mut chunk).map_err(import_error)?;
+
+    Ok(Some(chunk))
+}
+
+impl Blockchain {
+    /*
+     * Write the whole chain saved in the storage into a chain file, returns how many blocks were exported
+     */
+    pub fn export_chain(&self, writer: &mut impl Write) -> Result<usize, BlockchainErrors> {
+        let height = self.store.get_height();
+
+        // Pruned blocks lost their transactions so they couldn't be verified when imported
+        if self.store.get_pruned_height() > 0 {
+            return Err(BlockchainErrors::CouldntExportChain(
+                "Only archive nodes can export the chain".to_string(),
+            ));
+        }
+
+        let header = ChainFileHeader {
+            version: CHAIN_FILE_VERSION,
+            chain_name: self.config.lock().unwrap().chain_name.clone(),
+            genesis_hash: self.store.get_block_by_height(1).map(|block| block.hash),
+        };
+        header.write_to(writer)?;
+
+        let mut exported = 0;
+
+        for from in (1..=height).step_by(EXPORT_BATCH_SIZE) {
+            let to = (from + EXPORT_BATCH_SIZE - 1).min(height);
+
+            for mut block in self.store.get_blocks_range(from, to) {
+                // The height is implied by the position of the block in the file
+                block.index = None;
+                write_chunk(writer, &serde_json::to_vec(&block).unwrap())?;
+                exported += 1;
+            }
+        }
+
+        if exported != height {
+            return Err(BlockchainErrors::CouldntExportChain(format!(
+                "Only {} of {} blocks could be read from the storage",
+                exported, height
+            )));
+        }
+
+        writer.flush().map_err(export_error)?;
+
+        Ok(exported)
+    }
+
+    /*
+     * Load the blocks of a chain file into an empty node, returns the height of the node after the import.
+     * Nodes launched from a chain spec already have it's genesis block, which must be the one of the file.
+     * Every block is verified before being added, the import stops at the first invalid one.
+     */
+    pub fn import_chain(&mut self, reader: &mut impl Read) -> Result<usize, BlockchainErrors> {
+        let header = ChainFileHeader::read_from(reader)?;
+
+        let chain_name = self.config.lock().unwrap().chain_name.clone();
+        if header.chain_name != chain_name {
+            return Err(BlockchainErrors::CouldntImportChain(format!(
+                "The file belongs to the chain `{}` but the node runs `{}`",
+                header.chain_name, chain_name
+            )));
+        }
+
+        let has_genesis = match self.index {
+            0 => false,
+            1 if self.last_block_hash.is_some() && self.last_block_hash == header.genesis_hash => {
+                true
+            }
+            _ => {
+                return Err(BlockchainErrors::CouldntImportChain(
+                    "The node already has blocks".to_string(),
+                ))
+            }
+        };
+
+        let mut is_first_block = true;
+
+        while let Some(chunk) = read_chunk(reader)? {
+            let block: Block = serde_json::from_slice(&chunk).map_err(import_error)?;
+
+            if std::mem::take(&mut is_first_block) {
+                if Some(&block.hash) != header.genesis_hash.as_ref() {
+                    return Err(BlockchainErrors::CouldntImportChain(format!(
+                        "The first block ({}) is not the genesis of the file",
+                        block.hash.unite()
+                    )));
+                }
+
+                // The node already applied the same genesis
+                if has_genesis {
+                    continue;
+                }
+            }
+
+            validate_block(
+                &block,
+                &self.state,
+                &self.consensus_params(),
+                &self.chain_id(),
+            )
+            .map_err(BlockchainErrors::InvalidBlock)?;
+
+            self.add_block(&block)?;
+        }
+
+        Ok(self.index)
+    }
+}
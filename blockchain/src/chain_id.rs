@@ -0,0 +1,57 @@
+use crypto::{
+    digest::Digest,
+    sha3::{
+        Sha3,
+        Sha3Mode,
+    },
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    Encoder,
+    CHAIN_ID_HASH_VERSION,
+};
+
+/// Identifies a chain in the signatures, so transactions and blocks signed for one chain aren't valid on another
+/// that uses the same wallets
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainId {
+    pub chain_name: String,
+    /// Tells apart chains that share the name, like a testnet relaunched from the same spec
+    pub id: u64,
+}
+
+impl ChainId {
+    pub fn new(chain_name: &str, id: u64) -> Self {
+        Self {
+            chain_name: chain_name.to_string(),
+            id,
+        }
+    }
+
+    /*
+     * Get what is signed for the given hash, which is the hash bound to this chain
+     */
+    pub fn signing_data(&self, hash: &str) -> String {
+        let mut encoder = Encoder::new();
+        encoder.str(&self.chain_name).u64(self.id).str(hash);
+
+        let mut hasher = Sha3::new(Sha3Mode::Keccak256);
+        hasher.input(&encoder.into_bytes());
+        hasher.result_str()
+    }
+
+    /*
+     * Get what was signed for the given hash in the given hash version, older versions signed the hash alone
+     */
+    pub fn signing_data_with_version(&self, version: u8, hash: &str) -> String {
+        if version >= CHAIN_ID_HASH_VERSION {
+            self.signing_data(hash)
+        } else {
+            hash.to_string()
+        }
+    }
+}
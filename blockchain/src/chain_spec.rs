@@ -0,0 +1,249 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+};
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+use crypto::{
+    digest::Digest,
+    sha3::{
+        Sha3,
+        Sha3Mode,
+    },
+};
+use k256::ecdsa::VerifyingKey;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    Block,
+    BlockBuilder,
+    BlockchainErrors,
+    ChainId,
+    ConsensusParams,
+    Encoder,
+    Key,
+    Transaction,
+    TransactionBuilder,
+    TransactionType,
+    Wallet,
+};
+
+/// Ammount given to an address in the genesis block
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GenesisAllocation {
+    pub address: String,
+    pub ammount: u64,
+}
+
+/// Stake made in the genesis block, stakers are the first possible forgers of the chain
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GenesisStaker {
+    /// Public key of the staker, in hexadecimal
+    pub public_key: String,
+    /// Ammount given to the staker and staked right away
+    pub ammount: u64,
+}
+
+/// Description of a network, every node started from the same spec gets the same genesis block
+///
+/// ```json
+/// {
+///     "chain_name": "mars",
+///     "chain_id": 1,
+///     "genesis_timestamp": "2021-10-01T00:00:00Z",
+///     "allocations": [{ "address": "<address>", "ammount": 2000000 }],
+///     "stakers": [{ "public_key": "<public key in hex>", "ammount": 2 }],
+///     "consensus": { "block_reward": 10, "upgrades": [{ "height": 100000, "block_reward": 5 }] }
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChainSpec {
+    pub chain_name: String,
+    /// Tells apart chains launched with the same name, transactions signed for one aren't valid on the other
+    #[serde(default)]
+    pub chain_id: u64,
+    /// Timestamp of the genesis block, in RFC 3339
+    pub genesis_timestamp: String,
+    #[serde(default)]
+    pub allocations: Vec<GenesisAllocation>,
+    pub stakers: Vec<GenesisStaker>,
+    #[serde(default)]
+    pub consensus: ConsensusParams,
+}
+
+impl ChainSpec {
+    pub fn from_json(json: &str) -> Result<Self, BlockchainErrors> {
+        let spec: Self = serde_json::from_str(json)
+            .map_err(|err| BlockchainErrors::InvalidChainSpec(err.to_string()))?;
+        spec.verify()?;
+        Ok(spec)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, BlockchainErrors> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|err| BlockchainErrors::InvalidChainSpec(err.to_string()))?;
+        Self::from_json(&json)
+    }
+
+    /*
+     * Make sure a genesis block can be generated from the spec
+     */
+    pub fn verify(&self) -> Result<(), BlockchainErrors> {
+        if self.chain_name.is_empty() {
+            return Err(BlockchainErrors::InvalidChainSpec(
+                "The chain name is empty".to_string(),
+            ));
+        }
+
+        self.timestamp()?;
+        self.total_supply()?;
+
+        // Without stakers there wouldn't be anyone to forge the next block
+        if self.stakers.is_empty() {
+            return Err(BlockchainErrors::InvalidChainSpec(
+                "There must be at least one staker".to_string(),
+            ));
+        }
+
+        for staker in &self.stakers {
+            staker_key(staker)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn chain(&self) -> ChainId {
+        ChainId::new(&self.chain_name, self.chain_id)
+    }
+
+    /*
+     * Wallet that forges the genesis block, it's derived from the chain name so every node gets the same one.
+     * It's private key is public, so it hands out all the supply in the genesis block and keeps nothing.
+     */
+    pub fn genesis_wallet(&self) -> Wallet {
+        let mut encoder = Encoder::new();
+        encoder.str("genesis").str(&self.chain_name);
+
+        let mut hasher = Sha3::new(Sha3Mode::Keccak256);
+        hasher.input(&encoder.into_bytes());
+        let mut private_key = [0; 32];
+        hasher.result(&mut private_key);
+
+        Wallet::from_private(&private_key, 0)
+    }
+
+    /*
+     * Generate the genesis block of the chain
+     *
+     * The genesis wallet gets the whole supply with the coinbase, sends it to the allocations and stakers,
+     * and then the stakers stake it. Stakes aren't signed, as the spec only knows the public key of the stakers.
+     */
+    pub fn genesis_block(&self) -> Result<Block, BlockchainErrors> {
+        self.verify()?;
+
+        let mut genesis_wallet = self.genesis_wallet();
+        let chain = self.chain();
+
+        let mut transactions = vec![TransactionBuilder::new()
+            .to_address(&genesis_wallet.get_public().hash_it())
+            .ammount(self.total_supply()?)
+            .is_type(TransactionType::COINBASE)
+            .with_wallet(&mut genesis_wallet)
+            .build()];
+
+        for allocation in &self.allocations {
+            transactions.push(
+                TransactionBuilder::new()
+                    .to_address(&allocation.address)
+                    .ammount(allocation.ammount)
+                    .is_type(TransactionType::MOVEMENT)
+                    .chain(&chain)
+                    .with_wallet(&mut genesis_wallet)
+                    .build(),
+            );
+        }
+
+        // The same staker might appear more than once
+        let mut stakers_history = HashMap::new();
+
+        for staker in &self.stakers {
+            let author_public_key = staker_key(staker)?;
+            let from_address = author_public_key.hash_it();
+
+            transactions.push(
+                TransactionBuilder::new()
+                    .to_address(&from_address)
+                    .ammount(staker.ammount)
+                    .is_type(TransactionType::MOVEMENT)
+                    .chain(&chain)
+                    .with_wallet(&mut genesis_wallet)
+                    .build(),
+            );
+
+            let history = stakers_history.entry(from_address.clone()).or_insert(0);
+
+            let mut stake = Transaction::STAKE {
+                author_public_key,
+                signature: Key(Vec::new()),
+                from_address,
+                ammount: staker.ammount,
+                hash: String::new(),
+                history: *history,
+                fee: 0,
+                commission: 0,
+            };
+            let stake_hash = stake.hash_it();
+            if let Transaction::STAKE { hash, .. } = &mut stake {
+                *hash = stake_hash;
+            }
+
+            transactions.push(stake);
+            *history += 1;
+        }
+
+        Ok(BlockBuilder::new()
+            .transactions(&transactions)
+            .timestamp(self.timestamp()?)
+            .key(&genesis_wallet.get_public())
+            .hash_it()
+            .sign_with(&genesis_wallet, &chain)
+            .build())
+    }
+
+    fn timestamp(&self) -> Result<DateTime<Utc>, BlockchainErrors> {
+        DateTime::parse_from_rfc3339(&self.genesis_timestamp)
+            .map(|timestamp| timestamp.with_timezone(&Utc))
+            .map_err(|err| {
+                BlockchainErrors::InvalidChainSpec(format!("Invalid genesis timestamp: {}", err))
+            })
+    }
+
+    /*
+     * Everything that is given in the genesis block
+     */
+    fn total_supply(&self) -> Result<u64, BlockchainErrors> {
+        self.allocations
+            .iter()
+            .map(|allocation| allocation.ammount)
+            .chain(self.stakers.iter().map(|staker| staker.ammount))
+            .try_fold(0_u64, |total, ammount| total.checked_add(ammount))
+            .ok_or_else(|| BlockchainErrors::InvalidChainSpec("The supply overflows".to_string()))
+    }
+}
+
+fn staker_key(staker: &GenesisStaker) -> Result<Key, BlockchainErrors> {
+    Key::from_hex(&staker.public_key)
+        .filter(|key| VerifyingKey::from_sec1_bytes(&key.0).is_ok())
+        .ok_or_else(|| {
+            BlockchainErrors::InvalidChainSpec(format!(
+                "Invalid staker public key `{}`",
+                staker.public_key
+            ))
+        })
+}
@@ -1,51 +1,179 @@
 use std::{
     collections::HashMap,
-    sync::{
-        Arc,
-        Mutex,
-    },
+    sync::Arc,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
 };
 
 use crate::{
-    Configuration,
+    Block,
+    BlockHeader,
+    BlockStore,
+    ConsensusParams,
     Transaction,
+    MAX_COMMISSION,
 };
 
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct AddressInfo {
     pub ammount: u64,
     // aka nonce
     pub history: u64,
+    /// Funds locked by stakes, they aren't part of `ammount`
+    #[serde(default)]
+    pub staked: u64,
+    /// Funds other addresses delegated to this one as a validator
+    #[serde(default)]
+    pub delegated: u64,
+    /// Part of the rewards kept as a validator before splitting them with the delegators, in basis points
+    #[serde(default)]
+    pub commission: u32,
+}
+
+/// Unstaked or undelegated funds waiting for the unbonding period to end
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Unbonding {
+    pub address: String,
+    pub ammount: u64,
+    /// Height of the block after which the funds are given back to the address
+    pub release_height: usize,
 }
 
 #[derive(Clone, Debug)]
 pub struct Chainstate {
-    pub config: Arc<Mutex<Configuration>>,
+    pub store: Arc<dyn BlockStore>,
     pub addresses: HashMap<String, AddressInfo>,
     pub last_staking_addresses: Vec<Transaction>,
+    /// Unstaked and undelegated funds that are still locked, ordered by the time they were withdrawn
+    pub unbondings: Vec<Unbonding>,
+    /// Validator address -> Delegator address -> Delegated ammount
+    pub delegations: HashMap<String, HashMap<String, u64>>,
+    pub last_forgers: Vec<String>,
+    pub missed_forgers: HashMap<String, usize>,
+    pub last_forger_was_blocked: bool,
+    /// Consensus parameters of the chain, with their upgrades
+    pub consensus: ConsensusParams,
+    /// Height of the last block applied to the chainstate
+    pub height: usize,
+    /// Header of the last block applied to the chainstate
+    pub last_block: Option<BlockHeader>,
+}
+
+/// A copy of the chainstate right after the block at `height` was applied
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct ChainstateSnapshot {
+    pub height: usize,
+    pub addresses: HashMap<String, AddressInfo>,
+    pub last_staking_addresses: Vec<Transaction>,
+    #[serde(default)]
+    pub unbondings: Vec<Unbonding>,
+    #[serde(default)]
+    pub delegations: HashMap<String, HashMap<String, u64>>,
+    pub last_forgers: Vec<String>,
+    pub missed_forgers: HashMap<String, usize>,
+    pub last_forger_was_blocked: bool,
+}
+
+/// A single change `effect_transaction` made to the chainstate
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum UndoEntry {
+    /// The info the address had before, `None` if it didn't exist yet
+    Address(String, Option<AddressInfo>),
+    /// A stake was appended to `last_staking_addresses`
+    StakePushed,
+    /// The oldest stake was dropped from `last_staking_addresses`
+    StakeRemoved(Transaction),
+    /// The stakes of an address that unstaked everything were dropped from `last_staking_addresses`, with their positions
+    StakesWithdrawn(Vec<(usize, Transaction)>),
+    /// An unbonding was appended to `unbondings`
+    UnbondingPushed,
+    /// An unbonding was released from `unbondings`, with it's position
+    UnbondingReleased(usize, Unbonding),
+    /// The ammount a delegator had delegated to a validator before, `None` if it hadn't
+    Delegation(String, String, Option<u64>),
+}
+
+/// Undo journal of a block, enough to revert the chainstate to right before the block was applied
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct BlockUndo {
+    /// Changes in the order they were made
+    pub entries: Vec<UndoEntry>,
     pub last_forgers: Vec<String>,
     pub missed_forgers: HashMap<String, usize>,
     pub last_forger_was_blocked: bool,
+    pub height: usize,
+    pub last_block: Option<BlockHeader>,
 }
 
 impl Chainstate {
-    pub fn new(config: Arc<Mutex<Configuration>>) -> Self {
+    pub fn new(store: Arc<dyn BlockStore>) -> Self {
+        Self::with_consensus(store, ConsensusParams::default())
+    }
+
+    pub fn with_consensus(store: Arc<dyn BlockStore>, consensus: ConsensusParams) -> Self {
         Self {
-            config,
+            store,
             addresses: HashMap::new(),
             last_staking_addresses: Vec::new(),
+            unbondings: Vec::new(),
+            delegations: HashMap::new(),
             last_forgers: Vec::new(),
             missed_forgers: HashMap::new(),
             last_forger_was_blocked: false,
+            consensus,
+            height: 0,
+            last_block: None,
         }
     }
 
+    /*
+     * Copy of the chainstate with only the given addresses, enough to apply transactions that don't touch any other.
+     * The delegations are kept, rewards are split with the delegators of the forger.
+     */
+    pub(crate) fn with_only_addresses<'a>(
+        &self,
+        addresses: impl Iterator<Item = &'a String>,
+    ) -> Self {
+        let addresses = addresses
+            .filter_map(|address| {
+                self.addresses
+                    .get(address)
+                    .map(|address_info| (address.clone(), address_info.clone()))
+            })
+            .collect();
+
+        Self {
+            store: self.store.clone(),
+            addresses,
+            last_staking_addresses: Vec::new(),
+            unbondings: Vec::new(),
+            delegations: self.delegations.clone(),
+            last_forgers: Vec::new(),
+            missed_forgers: HashMap::new(),
+            last_forger_was_blocked: false,
+            consensus: self.consensus.clone(),
+            height: self.height,
+            last_block: self.last_block.clone(),
+        }
+    }
+
+    /*
+     * Consensus parameters of the next block, the one transactions are applied for
+     */
+    pub fn consensus_params(&self) -> ConsensusParams {
+        self.consensus.at_height(self.height + 1)
+    }
+
     pub fn is_punished(&self, address: &str) -> bool {
         self.missed_forgers.contains_key(address)
     }
 
     pub fn add_recent_forger(&mut self, address: &str) {
-        if self.last_forgers.len() > 1 {
+        let recent_forgers = self.consensus_params().recent_forgers.max(1);
+        while self.last_forgers.len() >= recent_forgers {
             self.last_forgers.remove(0);
         }
 
@@ -64,50 +192,254 @@ impl Chainstate {
     pub fn get_address_ammount(&self, address: String) -> u64 {
         self.addresses
             .get(&address)
-            .unwrap_or(&AddressInfo {
-                ammount: 0,
-                history: 0,
-            })
+            .unwrap_or(&AddressInfo::default())
             .ammount
     }
 
     /*
-     * Calculate the chainstate from the begining of the blockchain
+     * Funds the address has locked by stakes
+     */
+    pub fn get_address_staked(&self, address: &str) -> u64 {
+        self.addresses
+            .get(address)
+            .map(|address_info| address_info.staked)
+            .unwrap_or(0)
+    }
+
+    /*
+     * Weight of a validator in the forger election, it's own stake and the funds delegated to it
+     */
+    pub fn get_election_weight(&self, address: &str) -> u64 {
+        self.addresses
+            .get(address)
+            .map(|address_info| address_info.staked.saturating_add(address_info.delegated))
+            .unwrap_or(0)
+    }
+
+    /*
+     * Calculate the chainstate starting from the last saved snapshot, so only the blocks after it are replayed.
+     * Returns the height of the snapshot it started from.
+     */
+    pub fn load_from_chain(&mut self) -> usize {
+        let height = self.store.get_height();
+
+        let snapshot_height = match self.store.get_last_snapshot() {
+            Some(snapshot) if snapshot.height <= height => {
+                let snapshot_height = snapshot.height;
+                self.restore_snapshot(snapshot);
+                snapshot_height
+            }
+            _ => 0,
+        };
+
+        for block in self.store.get_blocks_range(snapshot_height + 1, height) {
+            self.apply_block(&block);
+        }
+
+        snapshot_height
+    }
+
+    /*
+     * Take a snapshot of the current chainstate
+     */
+    pub fn snapshot(&self, height: usize) -> ChainstateSnapshot {
+        ChainstateSnapshot {
+            height,
+            addresses: self.addresses.clone(),
+            last_staking_addresses: self.last_staking_addresses.clone(),
+            unbondings: self.unbondings.clone(),
+            delegations: self.delegations.clone(),
+            last_forgers: self.last_forgers.clone(),
+            missed_forgers: self.missed_forgers.clone(),
+            last_forger_was_blocked: self.last_forger_was_blocked,
+        }
+    }
+
+    /*
+     * Replace the current chainstate with the given snapshot
+     */
+    pub fn restore_snapshot(&mut self, snapshot: ChainstateSnapshot) {
+        self.addresses = snapshot.addresses;
+        self.last_staking_addresses = snapshot.last_staking_addresses;
+        self.unbondings = snapshot.unbondings;
+        self.delegations = snapshot.delegations;
+        self.last_forgers = snapshot.last_forgers;
+        self.missed_forgers = snapshot.missed_forgers;
+        self.last_forger_was_blocked = snapshot.last_forger_was_blocked;
+        self.height = snapshot.height;
+        self.last_block = self.store.get_header_by_height(snapshot.height);
+    }
+
+    /*
+     * Start an undo journal, it keeps the current forger bookkeeping
+     */
+    pub fn begin_undo(&self) -> BlockUndo {
+        BlockUndo {
+            entries: Vec::new(),
+            last_forgers: self.last_forgers.clone(),
+            missed_forgers: self.missed_forgers.clone(),
+            last_forger_was_blocked: self.last_forger_was_blocked,
+            height: self.height,
+            last_block: self.last_block.clone(),
+        }
+    }
+
+    /*
+     * Apply all the transactions of a block and release the unbondings that end on it, returns what's needed to revert it
+     */
+    pub fn apply_block(&mut self, block: &Block) -> BlockUndo {
+        let mut undo = self.begin_undo();
+
+        for tx in block.transactions.iter() {
+            self.effect_transaction_journaled(tx, &mut undo);
+        }
+
+        // Every node must know the recent forgers to calculate the same election
+        self.add_recent_forger(&block.key.hash_it());
+
+        self.height += 1;
+        self.last_block = Some(block.header());
+
+        self.release_unbondings(&mut undo);
+
+        undo
+    }
+
+    /*
+     * Give back the unstaked funds whose unbonding period ended with the current height,
+     * they can be spent from the next block on
+     */
+    fn release_unbondings(&mut self, undo: &mut BlockUndo) {
+        let mut i = 0;
+
+        while i < self.unbondings.len() {
+            if self.unbondings[i].release_height > self.height {
+                i += 1;
+                continue;
+            }
+
+            let unbonding = self.unbondings.remove(i);
+            self.journal_address(&unbonding.address, &mut Some(&mut *undo));
+
+            let address_info = self.addresses.entry(unbonding.address.clone()).or_default();
+            address_info.ammount = address_info.ammount.saturating_add(unbonding.ammount);
+
+            undo.entries
+                .push(UndoEntry::UnbondingReleased(i, unbonding));
+        }
+    }
+
+    /*
+     * Undo the changes of a block (or of any journaled transactions), newest first
      */
-    pub async fn load_from_chain(&mut self) {
-        let chain = self.config.lock().unwrap().get_blocks().await.unwrap();
+    pub fn revert_block(&mut self, undo: BlockUndo) {
+        for entry in undo.entries.into_iter().rev() {
+            match entry {
+                UndoEntry::Address(address, Some(address_info)) => {
+                    self.addresses.insert(address, address_info);
+                }
+                UndoEntry::Address(address, None) => {
+                    self.addresses.remove(&address);
+                }
+                UndoEntry::StakePushed => {
+                    self.last_staking_addresses.pop();
+                }
+                UndoEntry::StakeRemoved(tx) => {
+                    self.last_staking_addresses.insert(0, tx);
+                }
+                UndoEntry::StakesWithdrawn(stakes) => {
+                    for (position, tx) in stakes {
+                        self.last_staking_addresses.insert(position, tx);
+                    }
+                }
+                UndoEntry::UnbondingPushed => {
+                    self.unbondings.pop();
+                }
+                UndoEntry::UnbondingReleased(position, unbonding) => {
+                    self.unbondings.insert(position, unbonding);
+                }
+                UndoEntry::Delegation(validator, delegator, Some(ammount)) => {
+                    self.delegations
+                        .entry(validator)
+                        .or_default()
+                        .insert(delegator, ammount);
+                }
+                UndoEntry::Delegation(validator, delegator, None) => {
+                    if let Some(delegators) = self.delegations.get_mut(&validator) {
+                        delegators.remove(&delegator);
 
-        for block in chain.iter() {
-            let transactions = &block.transactions;
-            for tx in transactions.iter() {
-                self.effect_transaction(tx);
+                        if delegators.is_empty() {
+                            self.delegations.remove(&validator);
+                        }
+                    }
+                }
             }
         }
+
+        self.last_forgers = undo.last_forgers;
+        self.missed_forgers = undo.missed_forgers;
+        self.last_forger_was_blocked = undo.last_forger_was_blocked;
+        self.height = undo.height;
+        self.last_block = undo.last_block;
     }
 
     /*
-     * Make sure a transaction can be spent
+     * Save the current info of an address in the journal before it's modified
+     */
+    fn journal_address(&self, address: &str, journal: &mut Option<&mut BlockUndo>) {
+        if let Some(undo) = journal {
+            undo.entries.push(UndoEntry::Address(
+                address.to_string(),
+                self.addresses.get(address).cloned(),
+            ));
+        }
+    }
+
+    /*
+     * Make sure a transaction can be spent, the sender pays the fee on top of the ammount.
+     * Unstakes can't withdraw more than the staked funds, nor undelegations more than the delegated ones.
      */
     pub fn verify_transaction_ammount(&self, tx: &Transaction) -> bool {
         match tx {
-            Transaction::MOVEMENT {
+            Transaction::MOVEMENT { from_address, .. }
+            | Transaction::STAKE { from_address, .. }
+            | Transaction::DELEGATE { from_address, .. }
+            | Transaction::BATCH { from_address, .. } => {
+                if let Some(address_info) = self.addresses.get(&from_address.clone()) {
+                    tx.get_cost()
+                        .is_some_and(|cost| address_info.ammount >= cost)
+                } else {
+                    false
+                }
+            }
+            Transaction::UNSTAKE {
                 from_address,
                 ammount,
+                fee,
                 ..
             } => {
                 if let Some(address_info) = self.addresses.get(&from_address.clone()) {
-                    address_info.ammount >= *ammount
+                    address_info.staked >= *ammount && address_info.ammount >= *fee
                 } else {
                     false
                 }
             }
-            Transaction::STAKE {
+            Transaction::UNDELEGATE {
+                validator,
                 from_address,
                 ammount,
+                fee,
                 ..
             } => {
+                let delegated = self
+                    .delegations
+                    .get(&validator.hash_it())
+                    .and_then(|delegators| delegators.get(from_address))
+                    .copied()
+                    .unwrap_or(0);
+
                 if let Some(address_info) = self.addresses.get(&from_address.clone()) {
-                    address_info.ammount >= *ammount
+                    delegated >= *ammount && address_info.ammount >= *fee
                 } else {
                     false
                 }
@@ -137,6 +469,26 @@ impl Chainstate {
                 from_address,
                 history,
                 ..
+            }
+            | Transaction::UNSTAKE {
+                from_address,
+                history,
+                ..
+            }
+            | Transaction::DELEGATE {
+                from_address,
+                history,
+                ..
+            }
+            | Transaction::UNDELEGATE {
+                from_address,
+                history,
+                ..
+            }
+            | Transaction::BATCH {
+                from_address,
+                history,
+                ..
             } => {
                 if let Some(address_info) = self.addresses.get(&from_address.clone()) {
                     address_info.history == *history
@@ -149,9 +501,21 @@ impl Chainstate {
     }
 
     /*
-     * Apply the proper changes to the chainstate when a transaction is ocurred
+     * Apply the proper changes to the chainstate when a transaction is ocurred.
+     * Fees are taken from the sender, the forger gets them with the coinbase of the block.
      */
     pub fn effect_transaction(&mut self, tx: &Transaction) {
+        self.apply_transaction(tx, None);
+    }
+
+    /*
+     * Same as `effect_transaction`, but the changes are recorded in the given undo journal
+     */
+    pub fn effect_transaction_journaled(&mut self, tx: &Transaction, undo: &mut BlockUndo) {
+        self.apply_transaction(tx, Some(undo));
+    }
+
+    fn apply_transaction(&mut self, tx: &Transaction, mut journal: Option<&mut BlockUndo>) {
         match tx {
             Transaction::MOVEMENT {
                 from_address,
@@ -160,13 +524,17 @@ impl Chainstate {
                 history,
                 ..
             } => {
+                self.journal_address(from_address, &mut journal);
+
                 let origin_is_valid = {
                     // Address does exist
                     if let Some(address_info) = self.addresses.get_mut(&from_address.clone()) {
-                        // Has enough ammount and the history is correct
-                        if &address_info.ammount >= ammount && &address_info.history == history {
-                            // Remove the transaction ammount from the origin
-                            address_info.ammount -= ammount;
+                        // Has enough ammount for it and the fee, and the history is correct
+                        if let Some(cost) = tx.get_cost().filter(|cost| {
+                            &address_info.ammount >= cost && &address_info.history == history
+                        }) {
+                            // Remove the transaction ammount and the fee from the origin
+                            address_info.ammount -= cost;
                             address_info.history += 1;
                             true
                         } else {
@@ -178,6 +546,8 @@ impl Chainstate {
                 };
 
                 if origin_is_valid {
+                    self.journal_address(to_address, &mut journal);
+
                     if let Some(address_info) = self.addresses.get_mut(&to_address.clone()) {
                         address_info.ammount += ammount;
                     } else {
@@ -185,7 +555,7 @@ impl Chainstate {
                             to_address.clone(),
                             AddressInfo {
                                 ammount: *ammount,
-                                history: 0,
+                                ..Default::default()
                             },
                         );
                     }
@@ -196,38 +566,325 @@ impl Chainstate {
                 ammount,
                 ..
             } => {
-                if let Some(address_info) = self.addresses.get_mut(&to_address.clone()) {
-                    address_info.ammount += ammount;
-                } else {
-                    self.addresses.insert(
-                        to_address.clone(),
-                        AddressInfo {
-                            ammount: *ammount,
-                            history: 0,
-                        },
-                    );
+                // The forger shares the reward with it's delegators
+                for (address, share) in self.reward_shares(to_address, *ammount) {
+                    self.journal_address(&address, &mut journal);
+
+                    if let Some(address_info) = self.addresses.get_mut(&address) {
+                        address_info.ammount += share;
+                    } else {
+                        self.addresses.insert(
+                            address,
+                            AddressInfo {
+                                ammount: share,
+                                ..Default::default()
+                            },
+                        );
+                    }
                 }
             }
             Transaction::STAKE {
-                ammount,
                 from_address,
+                ammount,
                 history,
+                commission,
                 ..
             } => {
+                self.journal_address(from_address, &mut journal);
+
                 if let Some(address_info) = self.addresses.get_mut(&from_address.clone()) {
-                    // Has enough ammount and the history is correct
-                    if &address_info.ammount >= ammount && &address_info.history == history {
-                        address_info.ammount -= ammount;
+                    // Has enough ammount for it and the fee, and the history is correct
+                    if let Some(cost) = tx.get_cost().filter(|cost| {
+                        &address_info.ammount >= cost && &address_info.history == history
+                    }) {
+                        // The ammount is locked until it's unstaked, the fee is spent
+                        address_info.ammount -= cost;
+                        address_info.staked = address_info.staked.saturating_add(*ammount);
                         address_info.history += 1;
 
+                        // The last stake sets the commission of the validator
+                        address_info.commission = *commission;
+
                         self.last_staking_addresses.push(tx.clone());
 
-                        if self.last_staking_addresses.len() >= 100 {
-                            self.last_staking_addresses.remove(0);
+                        if let Some(undo) = journal.as_deref_mut() {
+                            undo.entries.push(UndoEntry::StakePushed);
+                        }
+
+                        if self.last_staking_addresses.len()
+                            >= self.consensus_params().staking_window
+                        {
+                            let removed_tx = self.last_staking_addresses.remove(0);
+
+                            if let Some(undo) = journal {
+                                undo.entries.push(UndoEntry::StakeRemoved(removed_tx));
+                            }
+                        }
+                    }
+                }
+            }
+            Transaction::UNSTAKE {
+                from_address,
+                ammount,
+                history,
+                fee,
+                ..
+            } => {
+                self.journal_address(from_address, &mut journal);
+
+                let release_height = self.height + 1 + self.consensus_params().unbonding_period;
+
+                if let Some(address_info) = self.addresses.get_mut(&from_address.clone()) {
+                    // Has enough staked for it, enough ammount for the fee, and the history is correct
+                    if &address_info.staked >= ammount
+                        && &address_info.ammount >= fee
+                        && &address_info.history == history
+                    {
+                        address_info.staked -= ammount;
+                        address_info.ammount -= fee;
+                        address_info.history += 1;
+
+                        let withdrew_everything = address_info.staked == 0;
+
+                        self.unbondings.push(Unbonding {
+                            address: from_address.clone(),
+                            ammount: *ammount,
+                            release_height,
+                        });
+
+                        if let Some(undo) = journal.as_deref_mut() {
+                            undo.entries.push(UndoEntry::UnbondingPushed);
+                        }
+
+                        // Without stake the address can't be elected anymore
+                        if withdrew_everything {
+                            let stakes = self.withdraw_stakes(from_address);
+
+                            if let Some(undo) = journal {
+                                if !stakes.is_empty() {
+                                    undo.entries.push(UndoEntry::StakesWithdrawn(stakes));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            Transaction::DELEGATE {
+                validator,
+                from_address,
+                ammount,
+                history,
+                ..
+            } => {
+                self.journal_address(from_address, &mut journal);
+
+                let origin_is_valid = {
+                    if let Some(address_info) = self.addresses.get_mut(&from_address.clone()) {
+                        // Has enough ammount for it and the fee, and the history is correct
+                        if let Some(cost) = tx.get_cost().filter(|cost| {
+                            &address_info.ammount >= cost && &address_info.history == history
+                        }) {
+                            address_info.ammount -= cost;
+                            address_info.history += 1;
+                            true
+                        } else {
+                            false
+                        }
+                    } else {
+                        false
+                    }
+                };
+
+                if origin_is_valid {
+                    let validator_address = validator.hash_it();
+                    self.journal_address(&validator_address, &mut journal);
+
+                    let validator_info =
+                        self.addresses.entry(validator_address.clone()).or_default();
+                    validator_info.delegated = validator_info.delegated.saturating_add(*ammount);
+
+                    let delegators = self
+                        .delegations
+                        .entry(validator_address.clone())
+                        .or_default();
+                    let previous = delegators.get(from_address).copied();
+                    delegators.insert(
+                        from_address.clone(),
+                        previous.unwrap_or(0).saturating_add(*ammount),
+                    );
+
+                    if let Some(undo) = journal {
+                        undo.entries.push(UndoEntry::Delegation(
+                            validator_address,
+                            from_address.clone(),
+                            previous,
+                        ));
+                    }
+                }
+            }
+            Transaction::UNDELEGATE {
+                validator,
+                from_address,
+                ammount,
+                history,
+                fee,
+                ..
+            } => {
+                self.journal_address(from_address, &mut journal);
+
+                let validator_address = validator.hash_it();
+                let release_height = self.height + 1 + self.consensus_params().unbonding_period;
+
+                let previous = self
+                    .delegations
+                    .get(&validator_address)
+                    .and_then(|delegators| delegators.get(from_address))
+                    .copied();
+
+                if let Some(address_info) = self.addresses.get_mut(&from_address.clone()) {
+                    // Has enough delegated for it, enough ammount for the fee, and the history is correct
+                    if previous.unwrap_or(0) >= *ammount
+                        && &address_info.ammount >= fee
+                        && &address_info.history == history
+                    {
+                        address_info.ammount -= fee;
+                        address_info.history += 1;
+
+                        self.journal_address(&validator_address, &mut journal);
+
+                        let validator_info =
+                            self.addresses.entry(validator_address.clone()).or_default();
+                        validator_info.delegated =
+                            validator_info.delegated.saturating_sub(*ammount);
+
+                        let delegators = self
+                            .delegations
+                            .entry(validator_address.clone())
+                            .or_default();
+                        let remaining = previous.unwrap_or(0) - ammount;
+                        if remaining > 0 {
+                            delegators.insert(from_address.clone(), remaining);
+                        } else {
+                            delegators.remove(from_address);
+
+                            if delegators.is_empty() {
+                                self.delegations.remove(&validator_address);
+                            }
+                        }
+
+                        // The funds go through the same unbonding period as the unstaked ones
+                        self.unbondings.push(Unbonding {
+                            address: from_address.clone(),
+                            ammount: *ammount,
+                            release_height,
+                        });
+
+                        if let Some(undo) = journal {
+                            undo.entries.push(UndoEntry::Delegation(
+                                validator_address,
+                                from_address.clone(),
+                                previous,
+                            ));
+                            undo.entries.push(UndoEntry::UnbondingPushed);
+                        }
+                    }
+                }
+            }
+            Transaction::BATCH {
+                from_address,
+                outputs,
+                history,
+                ..
+            } => {
+                self.journal_address(from_address, &mut journal);
+
+                let origin_is_valid = {
+                    if let Some(address_info) = self.addresses.get_mut(&from_address.clone()) {
+                        // Has enough ammount for all the outputs and the fee, and the history is correct
+                        if let Some(cost) = tx.get_cost().filter(|cost| {
+                            &address_info.ammount >= cost && &address_info.history == history
+                        }) {
+                            address_info.ammount -= cost;
+                            address_info.history += 1;
+                            true
+                        } else {
+                            false
                         }
+                    } else {
+                        false
+                    }
+                };
+
+                if origin_is_valid {
+                    for output in outputs {
+                        self.journal_address(&output.to_address, &mut journal);
+
+                        let address_info =
+                            self.addresses.entry(output.to_address.clone()).or_default();
+                        address_info.ammount += output.ammount;
                     }
                 }
             }
         };
     }
+
+    /*
+     * Split a reward of a validator with it's delegators, from the height the weighted election is activated on.
+     * Only the block reward is split, the fees of the block go to the validator. The validator keeps it's commission,
+     * the rest is shared by the funds each one has bonded, and the validator keeps what's left after rounding down.
+     */
+    fn reward_shares(&self, validator: &str, ammount: u64) -> Vec<(String, u64)> {
+        let params = self.consensus_params();
+
+        let delegators = match self.delegations.get(validator) {
+            Some(delegators) if !delegators.is_empty() && params.weighted_election => delegators,
+            _ => return vec![(validator.to_string(), ammount)],
+        };
+
+        let reward = ammount.min(params.block_reward) as u128;
+        let validator_info = self.addresses.get(validator).cloned().unwrap_or_default();
+        let commission =
+            reward * validator_info.commission.min(MAX_COMMISSION) as u128 / MAX_COMMISSION as u128;
+        let shared = reward - commission;
+        let total_bonded = validator_info.staked as u128 + validator_info.delegated as u128;
+
+        let mut delegators = delegators.iter().collect::<Vec<(&String, &u64)>>();
+        delegators.sort();
+
+        let mut validator_share = ammount;
+        let mut shares = Vec::new();
+
+        for (delegator, delegated) in delegators {
+            let share = (shared * *delegated as u128 / total_bonded.max(1)) as u64;
+            if share > 0 {
+                validator_share -= share;
+                shares.push((delegator.clone(), share));
+            }
+        }
+
+        shares.insert(0, (validator.to_string(), validator_share));
+        shares
+    }
+
+    /*
+     * Drop the stakes of an address from `last_staking_addresses`, returns them with the positions they had
+     */
+    fn withdraw_stakes(&mut self, address: &str) -> Vec<(usize, Transaction)> {
+        let mut stakes = Vec::new();
+        let mut position = 0;
+
+        self.last_staking_addresses.retain(|tx| {
+            let is_withdrawn =
+                matches!(tx, Transaction::STAKE { from_address, .. } if from_address == address);
+
+            if is_withdrawn {
+                stakes.push((position, tx.clone()));
+            }
+            position += 1;
+
+            !is_withdrawn
+        });
+
+        stakes
+    }
 }
@@ -1,53 +1,74 @@
 use crate::{
-    Block,
     BlockchainErrors,
+    ChainId,
+    ChainSpec,
+    ConsensusParams,
+    StorageBackend,
     Wallet,
 };
 
-use futures_util::{
-    stream::StreamExt,
-    TryStreamExt,
-};
-use mongodb::{
-    bson::doc,
-    options::{
-        ClientOptions,
-        ServerAddress,
-    },
-    Client,
-};
+/// How much of the chain a node keeps in it's storage
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum PruningMode {
+    /// Every block is kept
+    #[default]
+    Archive,
+    /// Only the last `keep_blocks` blocks keep their transactions, older blocks are reduced to their headers.
+    /// Blocks are pruned each time a chainstate snapshot is saved, and never past it.
+    Pruned { keep_blocks: usize },
+}
 
 #[derive(Clone, Debug)]
 pub struct Configuration {
     pub id: u16,
-    pub mongo_client: Client,
+    pub storage: StorageBackend,
     pub rpc_port: u16,
     pub rpc_ws_port: u16,
     pub hostname: String,
     pub wallet: Wallet,
     pub transaction_threads: u16,
     pub chain_name: String,
+    /// Together with the chain name it identifies the chain in the signatures
+    pub chain_id: u64,
+    /// Every how many blocks a snapshot of the chainstate is saved
+    pub snapshot_interval: usize,
+    pub pruning: PruningMode,
+    /// How many of the last blocks are kept in memory, at least the last block is always kept
+    pub recent_blocks: usize,
+    /// How many blocks behind the main chain's tip a side branch can start
+    pub max_fork_depth: usize,
+    /// How many side blocks are kept at most, the oldest branches are forgotten first
+    pub max_fork_blocks: usize,
+    /// Pending transactions needed in the mempool to forge a new block
+    pub min_block_transactions: usize,
+    /// Transactions that are propagated to the peers together
+    pub transactions_chunk_size: usize,
+    pub consensus: ConsensusParams,
+    /// Spec the chain was launched from, it's genesis block is created when the chain is empty
+    pub chain_spec: Option<ChainSpec>,
 }
 
 impl Configuration {
     pub fn new() -> Self {
-        let mut client_options = ClientOptions::default();
-        client_options.hosts = vec![ServerAddress::Tcp {
-            host: "localhost".to_string(),
-            port: Some(27017),
-        }];
-
-        let mongo_client = Client::with_options(client_options).unwrap();
-
         Self {
             id: 0,
-            mongo_client,
+            storage: StorageBackend::default(),
             rpc_port: 2000,
             rpc_ws_port: 7000,
             hostname: "0.0.0.0".to_string(),
             wallet: Wallet::default(),
             transaction_threads: 2,
             chain_name: "mars".to_string(),
+            chain_id: 0,
+            snapshot_interval: 100,
+            pruning: PruningMode::default(),
+            recent_blocks: 1,
+            max_fork_depth: 100,
+            max_fork_blocks: 1000,
+            min_block_transactions: 100,
+            transactions_chunk_size: 4,
+            consensus: ConsensusParams::default(),
+            chain_spec: None,
         }
     }
 
@@ -60,107 +81,58 @@ impl Configuration {
         transaction_threads: u16,
         chain_name: &str,
     ) -> Self {
-        let mut client_options = ClientOptions::default();
-        client_options.hosts = vec![ServerAddress::Tcp {
-            host: "localhost".to_string(),
-            port: Some(27017),
-        }];
-
-        let mongo_client = Client::with_options(client_options).unwrap();
-
         Self {
             id,
-            mongo_client,
+            storage: StorageBackend::default(),
             rpc_port,
             rpc_ws_port,
             hostname: hostname.to_string(),
             wallet,
             transaction_threads,
             chain_name: chain_name.to_string(),
+            chain_id: 0,
+            snapshot_interval: 100,
+            pruning: PruningMode::default(),
+            recent_blocks: 1,
+            max_fork_depth: 100,
+            max_fork_blocks: 1000,
+            min_block_transactions: 100,
+            transactions_chunk_size: 4,
+            consensus: ConsensusParams::default(),
+            chain_spec: None,
         }
     }
 
     /*
-     * Get all the blocks on the blockchain
+     * Run the chain described by the spec, it's name, id and consensus rules replace the configured ones
      */
-    pub async fn get_blocks(&self) -> Result<Vec<Block>, BlockchainErrors> {
-        let mut chain = Vec::new();
-
-        let id = self.id;
-        let db = self.mongo_client.database(&format!("db_{}", id));
-
-        // Blocks tree
-        let blocks = db.collection::<Block>("blocks");
-        let mut cursor = blocks.find(None, None).await.unwrap();
-
-        while let Some(Ok(block)) = cursor.next().await {
-            if block.verify_integrity().is_ok() {
-                chain.push(block)
-            } else {
-                return Err(BlockchainErrors::InvalidHash);
-            }
-        }
-        chain = order_chain(&chain);
-        Ok(chain)
+    pub fn with_chain_spec(&mut self, chain_spec: ChainSpec) -> &mut Self {
+        self.chain_name = chain_spec.chain_name.clone();
+        self.chain_id = chain_spec.chain_id;
+        self.consensus = chain_spec.consensus.clone();
+        self.chain_spec = Some(chain_spec);
+        self
     }
 
     /*
-     * Add a block to the database
+     * Make sure the settings can work together
      */
-    pub fn add_block(&mut self, block: &Block) {
-        let block = block.clone();
-
-        let id = self.id;
-        let mongo_client = self.mongo_client.clone();
-
-        tokio::spawn(async move {
-            let db = mongo_client.database(&format!("db_{}", id));
-
-            let coll = db.collection::<Block>("blocks");
-
-            coll.insert_one(block, None).await.ok();
-        });
-    }
-
-    pub async fn get_block_with_prev_hash(&self, prev_hash: String) -> Option<Block> {
-        let db = self.mongo_client.database(&format!("db_{}", self.id));
-
-        let coll = db.collection::<Block>("blocks");
-
-        let mut cursor = coll
-            .find(
-                doc! {
-                    "prev_hash.hash": prev_hash
-                },
-                None,
-            )
-            .await
-            .unwrap();
-
-        match cursor.try_next().await {
-            Ok(block) => block,
-            _ => None,
+    pub fn verify(&self) -> Result<(), BlockchainErrors> {
+        // Reorganizations need the transactions of the blocks they revert
+        if let PruningMode::Pruned { keep_blocks } = self.pruning {
+            if keep_blocks < self.max_fork_depth {
+                return Err(BlockchainErrors::InvalidConfiguration(format!(
+                    "Pruned nodes must keep at least the last {} blocks (max_fork_depth), but they keep {}",
+                    self.max_fork_depth, keep_blocks
+                )));
+            }
         }
-    }
-
-    pub async fn get_block_with_hash(&self, hash: String) -> Option<Block> {
-        let db = self.mongo_client.database(&format!("db_{}", self.id));
 
-        let coll = db.collection::<Block>("blocks");
-        let mut cursor = coll
-            .find(
-                doc! {
-                    "hash.hash": hash
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        Ok(())
+    }
 
-        match cursor.try_next().await {
-            Ok(block) => block,
-            _ => None,
-        }
+    pub fn chain(&self) -> ChainId {
+        ChainId::new(&self.chain_name, self.chain_id)
     }
 }
 
@@ -169,13 +141,3 @@ impl Default for Configuration {
         Self::new()
     }
 }
-
-fn order_chain(chain: &[Block]) -> Vec<Block> {
-    let mut ordered_list = chain.to_owned();
-
-    for block in chain {
-        ordered_list[block.index.unwrap() - 1] = block.clone();
-    }
-
-    ordered_list
-}
@@ -0,0 +1,101 @@
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Consensus rules of a chain
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConsensusParams {
+    /// Ammount rewarded to the forger of every block
+    pub block_reward: u64,
+    /// Milliseconds the elected forger has to create a block before it's punished
+    pub block_time_max: i64,
+    /// Milliseconds a block timestamp can be ahead of the clock of the node receiving it
+    pub max_future_drift: i64,
+    /// Transactions taken from the mempool into a new block
+    pub max_block_transactions: usize,
+    /// Bytes a block can take in it's canonical encoding
+    pub max_block_size: usize,
+    /// Stakes taken into account in the forger election, the oldest is dropped once there are this many
+    pub staking_window: usize,
+    /// Blocks unstaked funds stay locked before they can be spent
+    pub unbonding_period: usize,
+    /// Last forgers that can't be elected again
+    pub recent_forgers: usize,
+    /// Forgers are elected by their staked and delegated funds, instead of by the hashes of their stakes
+    pub weighted_election: bool,
+    /// Changes of the parameters at later heights
+    pub upgrades: Vec<ConsensusUpgrade>,
+}
+
+/// Parameters that change from a block height on, the ones that aren't set keep their value
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConsensusUpgrade {
+    /// First block the new values apply to
+    pub height: usize,
+    pub block_reward: Option<u64>,
+    pub block_time_max: Option<i64>,
+    pub max_future_drift: Option<i64>,
+    pub max_block_transactions: Option<usize>,
+    pub max_block_size: Option<usize>,
+    pub staking_window: Option<usize>,
+    pub unbonding_period: Option<usize>,
+    pub recent_forgers: Option<usize>,
+    pub weighted_election: Option<bool>,
+}
+
+impl Default for ConsensusParams {
+    fn default() -> Self {
+        Self {
+            block_reward: 10,
+            block_time_max: 8000,
+            max_future_drift: 15000,
+            max_block_transactions: 700,
+            max_block_size: 1_000_000,
+            staking_window: 100,
+            unbonding_period: 100,
+            recent_forgers: 2,
+            weighted_election: false,
+            upgrades: Vec::new(),
+        }
+    }
+}
+
+impl ConsensusParams {
+    /*
+     * Get the parameters in effect for the block at the given height, with every upgrade up to it applied
+     */
+    pub fn at_height(&self, height: usize) -> ConsensusParams {
+        let mut upgrades = self
+            .upgrades
+            .iter()
+            .filter(|upgrade| upgrade.height <= height)
+            .collect::<Vec<&ConsensusUpgrade>>();
+        upgrades.sort_by_key(|upgrade| upgrade.height);
+
+        let mut params = ConsensusParams {
+            upgrades: Vec::new(),
+            ..self.clone()
+        };
+
+        for upgrade in upgrades {
+            params.block_reward = upgrade.block_reward.unwrap_or(params.block_reward);
+            params.block_time_max = upgrade.block_time_max.unwrap_or(params.block_time_max);
+            params.max_future_drift = upgrade.max_future_drift.unwrap_or(params.max_future_drift);
+            params.max_block_transactions = upgrade
+                .max_block_transactions
+                .unwrap_or(params.max_block_transactions);
+            params.max_block_size = upgrade.max_block_size.unwrap_or(params.max_block_size);
+            params.staking_window = upgrade.staking_window.unwrap_or(params.staking_window);
+            params.unbonding_period = upgrade.unbonding_period.unwrap_or(params.unbonding_period);
+            params.recent_forgers = upgrade.recent_forgers.unwrap_or(params.recent_forgers);
+            params.weighted_election = upgrade
+                .weighted_election
+                .unwrap_or(params.weighted_election);
+        }
+
+        params
+    }
+}
@@ -0,0 +1,143 @@
+use crate::{
+    Chainstate,
+    Key,
+    Transaction,
+};
+
+/*
+ * Forgers of the next block in order of priority. The first one is the elected forger, the next ones take it's place
+ * one by one each time `block_time_max` passes without a block, see `validate_block`.
+ *
+ * The candidates are the stakers of the staking window and the hash of the last block is the source of randomness.
+ * Recent forgers go after everyone else, the least recent first, so they are only elected when nobody else can be.
+ */
+pub fn rank_forgers(chainstate: &Chainstate) -> Vec<Key> {
+    let seed = match &chainstate.last_block {
+        Some(last_block) => &last_block.hash.hash,
+        None => return Vec::new(),
+    };
+
+    let stakes = chainstate
+        .last_staking_addresses
+        .iter()
+        .filter_map(|tx| match tx {
+            Transaction::STAKE {
+                author_public_key,
+                from_address,
+                hash,
+                ..
+            } if !chainstate.has_recent_forger(from_address) => {
+                Some((from_address, author_public_key, hash))
+            }
+            _ => None,
+        })
+        .collect::<Vec<(&String, &Key, &String)>>();
+
+    let mut ranking = if chainstate.consensus_params().weighted_election {
+        rank_by_weight(chainstate, stakes, seed)
+    } else {
+        rank_by_hash(stakes, seed)
+    };
+
+    for forger in &chainstate.last_forgers {
+        let key = chainstate
+            .last_staking_addresses
+            .iter()
+            .find_map(|tx| match tx {
+                Transaction::STAKE {
+                    author_public_key,
+                    from_address,
+                    ..
+                } if from_address == forger => Some(author_public_key),
+                _ => None,
+            });
+
+        if let Some(key) = key {
+            if !ranking.contains(key) {
+                ranking.push(key.clone());
+            }
+        }
+    }
+
+    ranking
+}
+
+/*
+ * The staker whose stake hash contains the longest start of the seed wins, the first stake of the window on ties.
+ * When no stake hash contains even it's first digit, the stake hash closest to the seed wins instead.
+ * The next ones are elected the same way without the stakes of the ones already elected.
+ */
+fn rank_by_hash(mut stakes: Vec<(&String, &Key, &String)>, seed: &str) -> Vec<Key> {
+    let mut ranking = Vec::new();
+
+    while !stakes.is_empty() {
+        let &(winner, author_public_key, _) = (1..=seed.len())
+            .rev()
+            .find_map(|len| {
+                stakes
+                    .iter()
+                    .find(|(_, _, hash)| hash.contains(&seed[0..len]))
+            })
+            .unwrap_or_else(|| {
+                stakes
+                    .iter()
+                    .min_by_key(|(_, _, hash)| hash_number(hash).abs_diff(hash_number(seed)))
+                    .unwrap()
+            });
+
+        ranking.push(author_public_key.clone());
+        stakes.retain(|(from_address, _, _)| *from_address != winner);
+    }
+
+    ranking
+}
+
+/*
+ * The chance of each staker is it's election weight, it's own stake and the funds delegated to it.
+ * The next ones are drawn with the same seed without the ones already drawn.
+ */
+fn rank_by_weight(
+    chainstate: &Chainstate,
+    stakes: Vec<(&String, &Key, &String)>,
+    seed: &str,
+) -> Vec<Key> {
+    // Each staker is a candidate once, no matter how many stakes it has in the window
+    let mut candidates: Vec<(&String, &Key, u128)> = Vec::new();
+
+    for (from_address, author_public_key, _) in stakes {
+        if !candidates
+            .iter()
+            .any(|(address, _, _)| *address == from_address)
+        {
+            // Stakes that were made before their funds were tracked still give a chance
+            let weight = chainstate.get_election_weight(from_address).max(1);
+            candidates.push((from_address, author_public_key, weight as u128));
+        }
+    }
+
+    let seed = hash_number(seed);
+    let mut ranking = Vec::new();
+
+    while !candidates.is_empty() {
+        let total_weight = candidates.iter().map(|(_, _, weight)| weight).sum::<u128>();
+        let mut winner = seed % total_weight;
+
+        let mut position = 0;
+        while winner >= candidates[position].2 {
+            winner -= candidates[position].2;
+            position += 1;
+        }
+
+        let (_, author_public_key, _) = candidates.remove(position);
+        ranking.push(author_public_key.clone());
+    }
+
+    ranking
+}
+
+/*
+ * Number made of the first 32 hexadecimal digits of a hash
+ */
+fn hash_number(hash: &str) -> u128 {
+    u128::from_str_radix(&hash[0..hash.len().min(32)], 16).unwrap_or(0)
+}
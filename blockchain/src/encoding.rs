@@ -0,0 +1,105 @@
+/*
+ * Canonical binary encoding, used for everything that is hashed or signed.
+ *
+ * - Integers are written with a fixed width in big endian (u8, u32, u64 and i64).
+ * - Strings (UTF-8) and byte strings are written as their length (u32) followed by their bytes.
+ * - Optional values are written as a u8 0 when missing, or a u8 1 followed by the value.
+ * - Lists are written as their number of items (u32) followed by the items.
+ *
+ * Every hashed value starts with the hash version and a tag of what's being hashed,
+ * so values of different kinds or versions never share the same encoding.
+ */
+
+/// Tag of each kind of encoded value
+pub static MOVEMENT_TAG: u8 = 0;
+pub static COINBASE_TAG: u8 = 1;
+pub static STAKE_TAG: u8 = 2;
+pub static UNSTAKE_TAG: u8 = 3;
+pub static DELEGATE_TAG: u8 = 4;
+pub static BATCH_TAG: u8 = 5;
+pub static UNDELEGATE_TAG: u8 = 6;
+pub static BLOCK_TAG: u8 = 16;
+
+/// Writes values with the canonical encoding
+#[derive(Clone, Debug, Default)]
+pub struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn u8(&mut self, value: u8) -> &mut Self {
+        self.bytes.push(value);
+        self
+    }
+
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn u64(&mut self, value: u64) -> &mut Self {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn i64(&mut self, value: i64) -> &mut Self {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn bytes(&mut self, value: &[u8]) -> &mut Self {
+        self.u32(value.len() as u32);
+        self.bytes.extend_from_slice(value);
+        self
+    }
+
+    pub fn str(&mut self, value: &str) -> &mut Self {
+        self.bytes(value.as_bytes())
+    }
+
+    pub fn option<T>(
+        &mut self,
+        value: Option<&T>,
+        encode: impl FnOnce(&mut Self, &T),
+    ) -> &mut Self {
+        match value {
+            Some(value) => {
+                self.u8(1);
+                encode(self, value);
+            }
+            None => {
+                self.u8(0);
+            }
+        }
+        self
+    }
+
+    pub fn list<T: Encode>(&mut self, values: &[T]) -> &mut Self {
+        self.u32(values.len() as u32);
+        for value in values {
+            value.encode(self);
+        }
+        self
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Values that have a canonical encoding
+pub trait Encode {
+    /// Write the value into the encoder
+    fn encode(&self, encoder: &mut Encoder);
+
+    /// Get the canonical encoding of the value
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        self.encode(&mut encoder);
+        encoder.into_bytes()
+    }
+}
@@ -23,6 +23,28 @@ impl Key {
         hasher.input_str(&str_key);
         hasher.result_str()
     }
+
+    /*
+     * Hexadecimal representation of the key, how keys are written in files such as the chain spec
+     */
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /*
+     * Parse a key from it's hexadecimal representation
+     */
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+            return None;
+        }
+
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect::<Option<Vec<u8>>>()
+            .map(Key)
+    }
 }
 
 impl std::fmt::Display for Key {
@@ -2,35 +2,133 @@ mod block;
 mod block_builder;
 mod block_chain;
 mod block_hash;
+mod block_header;
+mod block_tree;
+mod chain_file;
+mod chain_id;
+mod chain_spec;
 mod chainstate;
 mod configuration;
+mod consensus_params;
+mod election;
+mod encoding;
 mod key;
+mod merkle;
 mod metrics;
 mod public_address;
+mod storage;
 mod transaction;
 mod transaction_builder;
+mod validation;
 mod wallet;
 
-pub use block::Block;
+pub use block::{
+    Block,
+    BlocksErrors,
+};
 pub use block_builder::BlockBuilder;
 pub use block_chain::{
     Blockchain,
     BlockchainErrors,
+    MAX_RANGE,
+};
+pub use block_hash::{
+    BlockHash,
+    BATCH_HASH_VERSION,
+    CANONICAL_HASH_VERSION,
+    CHAIN_ID_HASH_VERSION,
+    DELEGATION_HASH_VERSION,
+    FEE_HASH_VERSION,
+    HASH_VERSION,
+    LEGACY_HASH_VERSION,
+    TIMESTAMP_HASH_VERSION,
+    UNDELEGATION_HASH_VERSION,
+};
+pub use block_header::BlockHeader;
+pub use block_tree::{
+    is_better_branch,
+    BlockImport,
+    BlockTree,
+};
+pub use chain_file::{
+    ChainFileHeader,
+    CHAIN_FILE_MAGIC,
+    CHAIN_FILE_VERSION,
+};
+pub use chain_id::ChainId;
+pub use chain_spec::{
+    ChainSpec,
+    GenesisAllocation,
+    GenesisStaker,
+};
+pub use chainstate::{
+    AddressInfo,
+    BlockUndo,
+    Chainstate,
+    ChainstateSnapshot,
+    Unbonding,
+    UndoEntry,
+};
+pub use configuration::{
+    Configuration,
+    PruningMode,
+};
+pub use consensus_params::{
+    ConsensusParams,
+    ConsensusUpgrade,
+};
+pub use election::rank_forgers;
+pub use encoding::{
+    Encode,
+    Encoder,
+    BATCH_TAG,
+    BLOCK_TAG,
+    COINBASE_TAG,
+    DELEGATE_TAG,
+    MOVEMENT_TAG,
+    STAKE_TAG,
+    UNDELEGATE_TAG,
+    UNSTAKE_TAG,
 };
-pub use block_hash::BlockHash;
-pub use chainstate::Chainstate;
-pub use configuration::Configuration;
 pub use key::Key;
+pub use merkle::{
+    merkle_root,
+    MerkleProof,
+    MerkleSibling,
+};
 pub use metrics::{
     Metrics,
     MetricsClient,
 };
 pub use public_address::PublicAddress;
-pub use transaction::Transaction;
+pub use storage::{
+    migrate,
+    AddressHistoryEntry,
+    BlockStore,
+    DiskStore,
+    MemoryStore,
+    Migration,
+    MongoStore,
+    StorageBackend,
+    TransactionDirection,
+    TransactionLocation,
+    MIGRATIONS,
+    SCHEMA_VERSION,
+};
+pub use transaction::{
+    PaymentOutput,
+    Transaction,
+    MAX_COMMISSION,
+};
 pub use transaction_builder::{
     TransactionBuilder,
     TransactionType,
 };
+pub use validation::{
+    validate_block,
+    validate_block_at,
+    BlockValidationError,
+};
 pub use wallet::Wallet;
 
 pub trait SignVerifier {
@@ -39,13 +137,14 @@ pub trait SignVerifier {
     /// # Example
     ///
     /// ```
+    /// # use blockchain::{SignVerifier, Wallet};
     /// let wallet = Wallet::new();
     /// // Some data
     /// let data = "Hello World".to_string();
     /// // The signature that certifies that the wallet signed `Hello World`
     /// let signature = wallet.sign_data(data.clone());
     /// // The verification of the signature
-    /// let is_signature_ok = wallet.verify_signature(signature, data);
+    /// let is_signature_ok = wallet.verify_signature(&signature, data);
     /// ```
     ///
     fn verify_signature(&self, signature: &Key, data: String) -> bool;
@@ -0,0 +1,140 @@
+use crypto::{
+    digest::Digest,
+    sha3::{
+        Sha3,
+        Sha3Mode,
+    },
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Prefixes that keep leaves and inner nodes from being mistaken for each other
+static LEAF_PREFIX: u8 = 0;
+static NODE_PREFIX: u8 = 1;
+
+/// One step of a Merkle inclusion proof
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleSibling {
+    pub hash: String,
+    /// The sibling goes on the left when both are hashed together
+    pub is_left: bool,
+}
+
+/// Proof that a transaction is part of the transactions a Merkle root was calculated from
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub transaction_hash: String,
+    /// Position of the transaction inside the block
+    pub index: usize,
+    /// Siblings from the leaf up to the root
+    pub siblings: Vec<MerkleSibling>,
+}
+
+fn hash_leaf(transaction_hash: &str) -> String {
+    let mut hasher = Sha3::new(Sha3Mode::Keccak256);
+    hasher.input(&[LEAF_PREFIX]);
+    hasher.input_str(transaction_hash);
+    hasher.result_str()
+}
+
+fn hash_node(left: &str, right: &str) -> String {
+    let mut hasher = Sha3::new(Sha3Mode::Keccak256);
+    hasher.input(&[NODE_PREFIX]);
+    hasher.input_str(left);
+    hasher.input_str(right);
+    hasher.result_str()
+}
+
+/*
+ * Hash every pair of nodes of a level, a node without a pair is moved up as it is
+ */
+fn next_level(level: &[String]) -> Vec<String> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => hash_node(left, right),
+            [single] => single.clone(),
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/*
+ * Calculate the Merkle root of the given transaction hashes
+ */
+pub fn merkle_root(transaction_hashes: &[String]) -> String {
+    if transaction_hashes.is_empty() {
+        return Sha3::new(Sha3Mode::Keccak256).result_str();
+    }
+
+    let mut level = transaction_hashes
+        .iter()
+        .map(|hash| hash_leaf(hash))
+        .collect::<Vec<String>>();
+
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+
+    level.remove(0)
+}
+
+impl MerkleProof {
+    /*
+     * Create the proof for the transaction at the given position
+     */
+    pub fn generate(transaction_hashes: &[String], index: usize) -> Option<Self> {
+        let transaction_hash = transaction_hashes.get(index)?.clone();
+
+        let mut level = transaction_hashes
+            .iter()
+            .map(|hash| hash_leaf(hash))
+            .collect::<Vec<String>>();
+        let mut position = index;
+        let mut siblings = Vec::new();
+
+        while level.len() > 1 {
+            let sibling_position = position ^ 1;
+
+            if let Some(hash) = level.get(sibling_position) {
+                siblings.push(MerkleSibling {
+                    hash: hash.clone(),
+                    is_left: sibling_position < position,
+                });
+            }
+
+            level = next_level(&level);
+            position /= 2;
+        }
+
+        Some(Self {
+            transaction_hash,
+            index,
+            siblings,
+        })
+    }
+
+    /*
+     * Calculate the Merkle root this proof leads to
+     */
+    pub fn root(&self) -> String {
+        self.siblings
+            .iter()
+            .fold(hash_leaf(&self.transaction_hash), |hash, sibling| {
+                if sibling.is_left {
+                    hash_node(&sibling.hash, &hash)
+                } else {
+                    hash_node(&hash, &sibling.hash)
+                }
+            })
+    }
+
+    /*
+     * Make sure the transaction is included under the given Merkle root
+     */
+    pub fn verify(&self, merkle_root: &str) -> bool {
+        self.root() == merkle_root
+    }
+}
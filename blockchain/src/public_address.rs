@@ -16,11 +16,13 @@ pub struct PublicAddress {
 }
 
 impl PublicAddress {
-    pub fn from_signature(signature: &[u8], data: &[u8]) -> Self {
-        let signature: k256::ecdsa::recoverable::Signature =
-            Signature::from_bytes(signature).unwrap();
-        let public_key = signature.recover_verify_key(data).unwrap();
-        Self { public_key }
+    /*
+     * Recover the public key that signed the data, it fails if the signature is malformed
+     */
+    pub fn from_signature(signature: &[u8], data: &[u8]) -> Result<Self, k256::ecdsa::Error> {
+        let signature: k256::ecdsa::recoverable::Signature = Signature::from_bytes(signature)?;
+        let public_key = signature.recover_verify_key(data)?;
+        Ok(Self { public_key })
     }
 
     pub fn get_public(&self) -> Key {
@@ -31,7 +33,10 @@ impl PublicAddress {
 impl SignVerifier for PublicAddress {
     fn verify_signature(&self, signature: &Key, data: String) -> bool {
         let signature: k256::ecdsa::recoverable::Signature =
-            Signature::from_bytes(&signature.0).unwrap();
+            match Signature::from_bytes(&signature.0) {
+                Ok(signature) => signature,
+                Err(_) => return false,
+            };
         let result: Result<(), k256::ecdsa::Error> =
             self.public_key.verify(data.as_bytes(), &signature);
         result.is_ok()
@@ -0,0 +1,271 @@
+mod disk;
+mod memory;
+mod migrations;
+mod mongo;
+
+use std::{
+    fmt::Debug,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+pub use disk::DiskStore;
+pub use memory::MemoryStore;
+pub use migrations::{
+    migrate,
+    Migration,
+    MIGRATIONS,
+    SCHEMA_VERSION,
+};
+pub use mongo::MongoStore;
+
+use crate::{
+    Block,
+    BlockHash,
+    BlockHeader,
+    BlockchainErrors,
+    ChainstateSnapshot,
+    Transaction,
+};
+
+/// Where a confirmed transaction can be found
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransactionLocation {
+    pub block_hash: BlockHash,
+    pub height: usize,
+    /// Position of the transaction inside the block
+    pub position: usize,
+}
+
+impl TransactionLocation {
+    /*
+     * Get the location of every transaction in the given block
+     */
+    pub fn from_block(block: &Block) -> Vec<(String, TransactionLocation)> {
+        block
+            .transactions
+            .iter()
+            .enumerate()
+            .map(|(position, tx)| {
+                (
+                    tx.get_hash(),
+                    TransactionLocation {
+                        block_hash: block.hash.clone(),
+                        height: block.index.unwrap(),
+                        position,
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+/// Whether a transaction sent or received funds for an address
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TransactionDirection {
+    Incoming,
+    Outgoing,
+}
+
+/// A transaction in the history of an address
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AddressHistoryEntry {
+    pub transaction_hash: String,
+    pub location: TransactionLocation,
+    pub direction: TransactionDirection,
+}
+
+impl AddressHistoryEntry {
+    /*
+     * Get the history entries of every address involved in the given block
+     */
+    pub fn from_block(block: &Block) -> Vec<(String, AddressHistoryEntry)> {
+        let mut entries = Vec::new();
+
+        for (hash, location) in TransactionLocation::from_block(block) {
+            let tx = &block.transactions[location.position];
+
+            let addresses = match tx {
+                Transaction::MOVEMENT {
+                    from_address,
+                    to_address,
+                    ..
+                } => vec![
+                    (from_address, TransactionDirection::Outgoing),
+                    (to_address, TransactionDirection::Incoming),
+                ],
+                Transaction::COINBASE { to_address, .. } => {
+                    vec![(to_address, TransactionDirection::Incoming)]
+                }
+                Transaction::BATCH {
+                    from_address,
+                    outputs,
+                    ..
+                } => {
+                    let mut addresses = vec![(from_address, TransactionDirection::Outgoing)];
+
+                    // An address paid twice in the same batch has a single entry
+                    for output in outputs {
+                        let entry = (&output.to_address, TransactionDirection::Incoming);
+                        if !addresses.contains(&entry) {
+                            addresses.push(entry);
+                        }
+                    }
+
+                    addresses
+                }
+                Transaction::STAKE { from_address, .. }
+                | Transaction::UNSTAKE { from_address, .. }
+                | Transaction::DELEGATE { from_address, .. }
+                | Transaction::UNDELEGATE { from_address, .. } => {
+                    vec![(from_address, TransactionDirection::Outgoing)]
+                }
+            };
+
+            for (address, direction) in addresses {
+                entries.push((
+                    address.clone(),
+                    AddressHistoryEntry {
+                        transaction_hash: hash.clone(),
+                        location: location.clone(),
+                        direction,
+                    },
+                ));
+            }
+        }
+
+        entries
+    }
+}
+
+/// A place where the blocks of a chain are persisted
+pub trait BlockStore: Send + Sync + Debug {
+    /// Get all the blocks on the blockchain, ordered by their height
+    fn get_blocks(&self) -> Result<Vec<Block>, BlockchainErrors> {
+        let blocks = self.get_blocks_range(1, self.get_height());
+        let pruned_height = self.get_pruned_height();
+
+        // Make sure the loaded blocks weren't modified, pruned blocks can't be verified as they lost their transactions
+        for block in &blocks {
+            if block.index.unwrap_or(0) > pruned_height && block.verify_integrity().is_err() {
+                return Err(BlockchainErrors::InvalidHash);
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    /// Get the height of the last saved block, the first block has a height of 1
+    fn get_height(&self) -> usize;
+
+    /// Get the block at the given height
+    fn get_block_by_height(&self, height: usize) -> Option<Block>;
+
+    /// Get the blocks from the height `from` up to `to` (both included), ordered by their height
+    fn get_blocks_range(&self, from: usize, to: usize) -> Vec<Block>;
+
+    /// Save a block and it's header and index it's transactions and addresses, it only returns once the block is durably persisted
+    fn add_block(&self, block: &Block) -> Result<(), BlockchainErrors>;
+
+    /// Save the header of an already saved block, replacing the previous one
+    fn save_header(&self, header: &BlockHeader) -> Result<(), BlockchainErrors>;
+
+    /// Get the header of the block at the given height
+    fn get_header_by_height(&self, height: usize) -> Option<BlockHeader>;
+
+    /// Get the headers from the height `from` up to `to` (both included), ordered by their height
+    fn get_headers_range(&self, from: usize, to: usize) -> Vec<BlockHeader>;
+
+    /// Index the transactions and addresses of an already saved block, replacing it's previous entries
+    fn index_block(&self, block: &Block) -> Result<(), BlockchainErrors>;
+
+    /// Get every place the transaction with the given hash was confirmed, ordered by their height.
+    /// Transactions with the same content, such as equal coinbases, have the same hash in different blocks.
+    fn get_transaction_locations(&self, hash: &str) -> Vec<TransactionLocation>;
+
+    /// Get the last place the transaction with the given hash was confirmed
+    fn get_transaction_location(&self, hash: &str) -> Option<TransactionLocation> {
+        self.get_transaction_locations(hash).pop()
+    }
+
+    /// Get the transactions of an address from the oldest to the newest, skipping the first `skip` ones
+    fn get_address_history(
+        &self,
+        address: &str,
+        skip: usize,
+        limit: usize,
+    ) -> Vec<AddressHistoryEntry>;
+
+    /// Get a block by it's corresponding hash
+    fn get_block_with_hash(&self, hash: &str) -> Option<Block>;
+
+    /// Get a block that has the given previous hash
+    fn get_block_with_prev_hash(&self, prev_hash: &str) -> Option<Block>;
+
+    /// Save a snapshot of the chainstate
+    fn save_snapshot(&self, snapshot: &ChainstateSnapshot) -> Result<(), BlockchainErrors>;
+
+    /// Get the snapshot with the highest height
+    fn get_last_snapshot(&self) -> Option<ChainstateSnapshot>;
+
+    /// Get the snapshot with the highest height that isn't above `max_height`
+    fn get_snapshot_at(&self, max_height: usize) -> Option<ChainstateSnapshot>;
+
+    /// Remove the blocks above the given height together with their headers, indexes and snapshots
+    fn rollback_to(&self, height: usize) -> Result<(), BlockchainErrors>;
+
+    /// Get the height up to which blocks only keep their headers, 0 if nothing has been pruned
+    fn get_pruned_height(&self) -> usize;
+
+    /// Drop the transactions of the blocks up to the given height, their headers and indexes are kept
+    fn prune_blocks(&self, up_to: usize) -> Result<(), BlockchainErrors>;
+
+    /// Get the version of the layout the data is saved with, 0 if it was never set
+    fn get_schema_version(&self) -> u32;
+
+    /// Set the version of the layout the data is saved with
+    fn set_schema_version(&self, version: u32) -> Result<(), BlockchainErrors>;
+
+    /// Drop the transactions index of the schema versions before 4, for the backends that kept it somewhere else
+    fn drop_legacy_transactions_index(&self) -> Result<(), BlockchainErrors> {
+        Ok(())
+    }
+}
+
+/// The kind of storage a node saves it's blocks into
+#[derive(Clone, Debug)]
+pub enum StorageBackend {
+    /// A MongoDB server, each node uses it's own `db_{id}` database
+    Mongo { host: String, port: u16 },
+    /// Blocks are only kept in memory and are lost once the node stops
+    Memory,
+    /// An embedded database saved in `{path}/db_{id}`
+    Disk { path: PathBuf },
+}
+
+impl StorageBackend {
+    /// Open the storage of the node with the given ID
+    pub fn open(&self, id: u16) -> Result<Arc<dyn BlockStore>, BlockchainErrors> {
+        Ok(match self {
+            StorageBackend::Mongo { host, port } => Arc::new(MongoStore::new(host, *port, id)),
+            StorageBackend::Memory => Arc::new(MemoryStore::default()),
+            StorageBackend::Disk { path } => {
+                Arc::new(DiskStore::new(&path.join(format!("db_{}", id)))?)
+            }
+        })
+    }
+}
+
+impl Default for StorageBackend {
+    fn default() -> Self {
+        StorageBackend::Mongo {
+            host: "localhost".to_string(),
+            port: 27017,
+        }
+    }
+}
@@ -0,0 +1,429 @@
+use std::path::Path;
+
+use sled::{
+    transaction::ConflictableTransactionResult,
+    Transactional,
+};
+
+use super::{
+    AddressHistoryEntry,
+    BlockStore,
+    TransactionDirection,
+    TransactionLocation,
+};
+use crate::{
+    Block,
+    BlockHeader,
+    BlockchainErrors,
+    ChainstateSnapshot,
+};
+
+/// Embedded on-disk storage, no external database server is needed
+#[derive(Debug, Clone)]
+pub struct DiskStore {
+    db: sled::Db,
+    /// Block height -> Block
+    blocks: sled::Tree,
+    /// Block height -> Block header
+    headers: sled::Tree,
+    /// Block hash -> Block height
+    hashes: sled::Tree,
+    /// Transaction hash + Transaction location -> Transaction location
+    transactions: sled::Tree,
+    /// Address + Transaction location -> Address history entry
+    addresses: sled::Tree,
+    /// Snapshot height -> Chainstate snapshot
+    snapshots: sled::Tree,
+    /// Information about the storage itself, such as the pruned height
+    meta: sled::Tree,
+}
+
+static PRUNED_HEIGHT_KEY: &[u8] = b"pruned_height";
+static SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+
+/*
+ * Heights are saved as big endian so the trees are sorted by them
+ */
+fn height_key(height: usize) -> [u8; 8] {
+    (height as u64).to_be_bytes()
+}
+
+fn key_height(key: &[u8]) -> usize {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(key);
+    u64::from_be_bytes(bytes) as usize
+}
+
+/*
+ * All the locations of a transaction hash share the same prefix, and they are sorted by their height
+ */
+fn transaction_prefix(hash: &str) -> Vec<u8> {
+    format!("{}/", hash).into_bytes()
+}
+
+fn transaction_key(hash: &str, location: &TransactionLocation) -> Vec<u8> {
+    let mut key = transaction_prefix(hash);
+    key.extend_from_slice(&height_key(location.height));
+    key.extend_from_slice(&(location.position as u32).to_be_bytes());
+    key
+}
+
+/*
+ * All the entries of an address share the same prefix, and they are sorted by their location
+ */
+fn address_prefix(address: &str) -> Vec<u8> {
+    format!("{}/", address).into_bytes()
+}
+
+fn address_key(address: &str, entry: &AddressHistoryEntry) -> Vec<u8> {
+    let mut key = address_prefix(address);
+    key.extend_from_slice(&height_key(entry.location.height));
+    key.extend_from_slice(&(entry.location.position as u32).to_be_bytes());
+    key.push(match entry.direction {
+        TransactionDirection::Outgoing => 0,
+        TransactionDirection::Incoming => 1,
+    });
+    key
+}
+
+type IndexEntries = (Vec<(Vec<u8>, Vec<u8>)>, Vec<(Vec<u8>, Vec<u8>)>);
+
+/*
+ * Serialize the transactions and addresses index entries of a block
+ */
+fn index_entries(block: &Block) -> IndexEntries {
+    let transaction_entries = TransactionLocation::from_block(block)
+        .into_iter()
+        .map(|(hash, location)| {
+            (
+                transaction_key(&hash, &location),
+                serde_json::to_vec(&location).unwrap(),
+            )
+        })
+        .collect();
+    let address_entries = AddressHistoryEntry::from_block(block)
+        .into_iter()
+        .map(|(address, entry)| {
+            (
+                address_key(&address, &entry),
+                serde_json::to_vec(&entry).unwrap(),
+            )
+        })
+        .collect();
+
+    (transaction_entries, address_entries)
+}
+
+impl DiskStore {
+    pub fn new(path: &Path) -> Result<Self, BlockchainErrors> {
+        let db = sled::open(path)
+            .map_err(|err| BlockchainErrors::CouldntOpenStorage(err.to_string()))?;
+        let open_tree = |name: &str| {
+            db.open_tree(name)
+                .map_err(|err| BlockchainErrors::CouldntOpenStorage(err.to_string()))
+        };
+
+        Ok(Self {
+            blocks: open_tree("blocks")?,
+            headers: open_tree("headers")?,
+            hashes: open_tree("hashes")?,
+            transactions: open_tree("transaction_locations")?,
+            addresses: open_tree("addresses")?,
+            snapshots: open_tree("snapshots")?,
+            meta: open_tree("meta")?,
+            db,
+        })
+    }
+
+    /*
+     * Wait until all the changes are written to disk
+     */
+    fn flush(&self) -> Result<(), String> {
+        self.db.flush().map(|_| ()).map_err(|err| err.to_string())
+    }
+}
+
+impl BlockStore for DiskStore {
+    fn get_height(&self) -> usize {
+        match self.blocks.last() {
+            Ok(Some((key, _))) => key_height(&key),
+            _ => 0,
+        }
+    }
+
+    fn get_block_by_height(&self, height: usize) -> Option<Block> {
+        let value = self.blocks.get(height_key(height)).ok()??;
+        serde_json::from_slice(&value).ok()
+    }
+
+    fn get_blocks_range(&self, from: usize, to: usize) -> Vec<Block> {
+        if from > to {
+            return Vec::new();
+        }
+
+        self.blocks
+            .range(height_key(from)..=height_key(to))
+            .values()
+            .filter_map(|value| value.ok())
+            .filter_map(|value| serde_json::from_slice::<Block>(&value).ok())
+            .collect()
+    }
+
+    fn add_block(&self, block: &Block) -> Result<(), BlockchainErrors> {
+        let height = height_key(block.index.unwrap());
+        let value = serde_json::to_vec(block).unwrap();
+        let header = serde_json::to_vec(&block.header()).unwrap();
+        let (transaction_entries, address_entries) = index_entries(block);
+
+        // The block, it's header and it's indexes are saved all at once
+        (
+            &self.blocks,
+            &self.headers,
+            &self.hashes,
+            &self.transactions,
+            &self.addresses,
+        )
+            .transaction(
+                |(blocks, headers, hashes, transactions, addresses)| -> ConflictableTransactionResult<(), ()> {
+                    blocks.insert(&height, value.as_slice())?;
+                    headers.insert(&height, header.as_slice())?;
+                    hashes.insert(block.hash.hash.as_bytes(), &height)?;
+                    for (key, location) in &transaction_entries {
+                        transactions.insert(key.as_slice(), location.as_slice())?;
+                    }
+                    for (key, entry) in &address_entries {
+                        addresses.insert(key.as_slice(), entry.as_slice())?;
+                    }
+                    Ok(())
+                },
+            )
+            .map_err(|err| BlockchainErrors::CouldntPersistBlock(format!("{:?}", err)))?;
+
+        self.flush().map_err(BlockchainErrors::CouldntPersistBlock)
+    }
+
+    fn save_header(&self, header: &BlockHeader) -> Result<(), BlockchainErrors> {
+        let value = serde_json::to_vec(header).unwrap();
+
+        self.headers
+            .insert(height_key(header.index.unwrap()), value)
+            .map_err(|err| BlockchainErrors::CouldntPersistBlock(err.to_string()))?;
+
+        self.flush().map_err(BlockchainErrors::CouldntPersistBlock)
+    }
+
+    fn get_header_by_height(&self, height: usize) -> Option<BlockHeader> {
+        let value = self.headers.get(height_key(height)).ok()??;
+        serde_json::from_slice(&value).ok()
+    }
+
+    fn get_headers_range(&self, from: usize, to: usize) -> Vec<BlockHeader> {
+        if from > to {
+            return Vec::new();
+        }
+
+        self.headers
+            .range(height_key(from)..=height_key(to))
+            .values()
+            .filter_map(|value| value.ok())
+            .filter_map(|value| serde_json::from_slice::<BlockHeader>(&value).ok())
+            .collect()
+    }
+
+    fn index_block(&self, block: &Block) -> Result<(), BlockchainErrors> {
+        let height = height_key(block.index.unwrap());
+        let (transaction_entries, address_entries) = index_entries(block);
+
+        // Entries are keyed by what they index, so indexing a block twice overwrites them
+        (&self.hashes, &self.transactions, &self.addresses)
+            .transaction(
+                |(hashes, transactions, addresses)| -> ConflictableTransactionResult<(), ()> {
+                    hashes.insert(block.hash.hash.as_bytes(), &height)?;
+                    for (key, location) in &transaction_entries {
+                        transactions.insert(key.as_slice(), location.as_slice())?;
+                    }
+                    for (key, entry) in &address_entries {
+                        addresses.insert(key.as_slice(), entry.as_slice())?;
+                    }
+                    Ok(())
+                },
+            )
+            .map_err(|err| BlockchainErrors::CouldntPersistBlock(format!("{:?}", err)))?;
+
+        self.flush().map_err(BlockchainErrors::CouldntPersistBlock)
+    }
+
+    fn get_transaction_locations(&self, hash: &str) -> Vec<TransactionLocation> {
+        self.transactions
+            .scan_prefix(transaction_prefix(hash))
+            .values()
+            .filter_map(|value| value.ok())
+            .filter_map(|value| serde_json::from_slice(&value).ok())
+            .collect()
+    }
+
+    fn get_address_history(
+        &self,
+        address: &str,
+        skip: usize,
+        limit: usize,
+    ) -> Vec<AddressHistoryEntry> {
+        self.addresses
+            .scan_prefix(address_prefix(address))
+            .values()
+            .skip(skip)
+            .take(limit)
+            .filter_map(|value| value.ok())
+            .filter_map(|value| serde_json::from_slice(&value).ok())
+            .collect()
+    }
+
+    fn get_block_with_hash(&self, hash: &str) -> Option<Block> {
+        let height = self.hashes.get(hash.as_bytes()).ok()??;
+        self.get_block_by_height(key_height(&height))
+    }
+
+    fn get_block_with_prev_hash(&self, prev_hash: &str) -> Option<Block> {
+        let height = self.hashes.get(prev_hash.as_bytes()).ok()??;
+        self.get_block_by_height(key_height(&height) + 1)
+    }
+
+    fn save_snapshot(&self, snapshot: &ChainstateSnapshot) -> Result<(), BlockchainErrors> {
+        let value = serde_json::to_vec(snapshot).unwrap();
+
+        // Snapshots are keyed by their height so the last one is also the highest
+        self.snapshots
+            .insert(height_key(snapshot.height), value)
+            .map_err(|err| BlockchainErrors::CouldntPersistSnapshot(err.to_string()))?;
+
+        self.flush()
+            .map_err(BlockchainErrors::CouldntPersistSnapshot)
+    }
+
+    fn get_last_snapshot(&self) -> Option<ChainstateSnapshot> {
+        let (_, value) = self.snapshots.last().ok()??;
+        serde_json::from_slice(&value).ok()
+    }
+
+    fn get_snapshot_at(&self, max_height: usize) -> Option<ChainstateSnapshot> {
+        let (_, value) = self
+            .snapshots
+            .range(..=height_key(max_height))
+            .next_back()?
+            .ok()?;
+        serde_json::from_slice(&value).ok()
+    }
+
+    fn rollback_to(&self, height: usize) -> Result<(), BlockchainErrors> {
+        let removed_blocks = self.get_blocks_range(height + 1, self.get_height());
+        let removed_snapshots = self
+            .snapshots
+            .range(height_key(height + 1)..)
+            .keys()
+            .filter_map(|key| key.ok())
+            .collect::<Vec<sled::IVec>>();
+        let removed_entries = removed_blocks
+            .iter()
+            .map(index_entries)
+            .collect::<Vec<IndexEntries>>();
+
+        // The blocks, their headers and indexes and the snapshots that depend on them are removed all at once
+        (
+            &self.blocks,
+            &self.headers,
+            &self.hashes,
+            &self.transactions,
+            &self.addresses,
+            &self.snapshots,
+        )
+            .transaction(
+                |(blocks, headers, hashes, transactions, addresses, snapshots)| -> ConflictableTransactionResult<(), ()> {
+                    for key in &removed_snapshots {
+                        snapshots.remove(key)?;
+                    }
+                    for block in &removed_blocks {
+                        blocks.remove(&height_key(block.index.unwrap()))?;
+                        headers.remove(&height_key(block.index.unwrap()))?;
+                        hashes.remove(block.hash.hash.as_bytes())?;
+                    }
+                    for (transaction_entries, address_entries) in &removed_entries {
+                        for (key, _) in transaction_entries {
+                            transactions.remove(key.as_slice())?;
+                        }
+                        for (key, _) in address_entries {
+                            addresses.remove(key.as_slice())?;
+                        }
+                    }
+                    Ok(())
+                },
+            )
+            .map_err(|err| BlockchainErrors::CouldntRollbackBlocks(format!("{:?}", err)))?;
+
+        self.flush()
+            .map_err(BlockchainErrors::CouldntRollbackBlocks)
+    }
+
+    fn get_pruned_height(&self) -> usize {
+        match self.meta.get(PRUNED_HEIGHT_KEY) {
+            Ok(Some(value)) => key_height(&value),
+            _ => 0,
+        }
+    }
+
+    fn prune_blocks(&self, up_to: usize) -> Result<(), BlockchainErrors> {
+        let pruned_height = self.get_pruned_height();
+
+        if up_to <= pruned_height {
+            return Ok(());
+        }
+
+        let headers = self
+            .get_blocks_range(pruned_height + 1, up_to)
+            .into_iter()
+            .map(|mut block| {
+                block.transactions.clear();
+                (
+                    height_key(block.index.unwrap()),
+                    serde_json::to_vec(&block).unwrap(),
+                )
+            })
+            .collect::<Vec<([u8; 8], Vec<u8>)>>();
+
+        // The pruned height is only moved once all the blocks lost their transactions
+        (&self.blocks, &self.meta)
+            .transaction(|(blocks, meta)| -> ConflictableTransactionResult<(), ()> {
+                for (height, header) in &headers {
+                    blocks.insert(height, header.as_slice())?;
+                }
+                meta.insert(PRUNED_HEIGHT_KEY, &height_key(up_to))?;
+                Ok(())
+            })
+            .map_err(|err| BlockchainErrors::CouldntPruneBlocks(format!("{:?}", err)))?;
+
+        self.flush().map_err(BlockchainErrors::CouldntPruneBlocks)
+    }
+
+    fn get_schema_version(&self) -> u32 {
+        match self.meta.get(SCHEMA_VERSION_KEY) {
+            Ok(Some(value)) => key_height(&value) as u32,
+            _ => 0,
+        }
+    }
+
+    fn set_schema_version(&self, version: u32) -> Result<(), BlockchainErrors> {
+        self.meta
+            .insert(SCHEMA_VERSION_KEY, &height_key(version as usize))
+            .map_err(|err| BlockchainErrors::CouldntMigrateStorage(err.to_string()))?;
+
+        self.flush()
+            .map_err(BlockchainErrors::CouldntMigrateStorage)
+    }
+
+    fn drop_legacy_transactions_index(&self) -> Result<(), BlockchainErrors> {
+        // Before the schema version 4 transactions were keyed by their hash alone in the `transactions` tree
+        self.db
+            .drop_tree("transactions")
+            .map(|_| ())
+            .map_err(|err| BlockchainErrors::CouldntMigrateStorage(err.to_string()))
+    }
+}
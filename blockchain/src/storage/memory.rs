@@ -0,0 +1,245 @@
+use std::{
+    collections::{
+        BTreeMap,
+        HashMap,
+    },
+    sync::Mutex,
+};
+
+use super::{
+    AddressHistoryEntry,
+    BlockStore,
+    TransactionLocation,
+};
+use crate::{
+    Block,
+    BlockHeader,
+    BlockchainErrors,
+    ChainstateSnapshot,
+};
+
+/// Keeps all the blocks in memory, useful for tests and short-lived simulations
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    data: Mutex<MemoryData>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryData {
+    /// Block height -> Block
+    blocks: BTreeMap<usize, Block>,
+    /// Block height -> Block header
+    headers: BTreeMap<usize, BlockHeader>,
+    /// Block hash -> Block height
+    hashes: HashMap<String, usize>,
+    /// Transaction hash -> Transaction locations, sorted by their height
+    transactions: HashMap<String, Vec<TransactionLocation>>,
+    /// Address -> Transactions of the address
+    addresses: HashMap<String, Vec<AddressHistoryEntry>>,
+    /// Snapshot height -> Chainstate snapshot
+    snapshots: BTreeMap<usize, ChainstateSnapshot>,
+    pruned_height: usize,
+    schema_version: u32,
+}
+
+impl BlockStore for MemoryStore {
+    fn get_height(&self) -> usize {
+        let data = self.data.lock().unwrap();
+        data.blocks.keys().next_back().copied().unwrap_or(0)
+    }
+
+    fn get_block_by_height(&self, height: usize) -> Option<Block> {
+        self.data.lock().unwrap().blocks.get(&height).cloned()
+    }
+
+    fn get_blocks_range(&self, from: usize, to: usize) -> Vec<Block> {
+        if from > to {
+            return Vec::new();
+        }
+
+        let data = self.data.lock().unwrap();
+        data.blocks
+            .range(from..=to)
+            .map(|(_, block)| block.clone())
+            .collect()
+    }
+
+    fn add_block(&self, block: &Block) -> Result<(), BlockchainErrors> {
+        self.data
+            .lock()
+            .unwrap()
+            .blocks
+            .insert(block.index.unwrap(), block.clone());
+
+        self.save_header(&block.header())?;
+        self.index_block(block)
+    }
+
+    fn save_header(&self, header: &BlockHeader) -> Result<(), BlockchainErrors> {
+        self.data
+            .lock()
+            .unwrap()
+            .headers
+            .insert(header.index.unwrap(), header.clone());
+        Ok(())
+    }
+
+    fn get_header_by_height(&self, height: usize) -> Option<BlockHeader> {
+        self.data.lock().unwrap().headers.get(&height).cloned()
+    }
+
+    fn get_headers_range(&self, from: usize, to: usize) -> Vec<BlockHeader> {
+        if from > to {
+            return Vec::new();
+        }
+
+        let data = self.data.lock().unwrap();
+        data.headers
+            .range(from..=to)
+            .map(|(_, header)| header.clone())
+            .collect()
+    }
+
+    fn index_block(&self, block: &Block) -> Result<(), BlockchainErrors> {
+        let mut data = self.data.lock().unwrap();
+
+        let height = block.index.unwrap();
+        data.hashes.insert(block.hash.hash.clone(), height);
+        for (hash, location) in TransactionLocation::from_block(block) {
+            let locations = data.transactions.entry(hash).or_default();
+
+            // Indexing a block twice doesn't duplicate it's locations
+            if !locations.contains(&location) {
+                let index = locations.partition_point(|saved| {
+                    (saved.height, saved.position) <= (location.height, location.position)
+                });
+                locations.insert(index, location);
+            }
+        }
+        for (address, entry) in AddressHistoryEntry::from_block(block) {
+            let entries = data.addresses.entry(address).or_default();
+
+            // Keep the entries sorted by their location, even if blocks are added out of order
+            let index = entries.partition_point(|saved| {
+                (saved.location.height, saved.location.position)
+                    <= (entry.location.height, entry.location.position)
+            });
+
+            // Indexing a block twice doesn't duplicate it's entries
+            let already_indexed = entries[..index]
+                .iter()
+                .rev()
+                .take_while(|saved| saved.location == entry.location)
+                .any(|saved| saved == &entry);
+
+            if !already_indexed {
+                entries.insert(index, entry);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_transaction_locations(&self, hash: &str) -> Vec<TransactionLocation> {
+        match self.data.lock().unwrap().transactions.get(hash) {
+            Some(locations) => locations.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    fn get_address_history(
+        &self,
+        address: &str,
+        skip: usize,
+        limit: usize,
+    ) -> Vec<AddressHistoryEntry> {
+        match self.data.lock().unwrap().addresses.get(address) {
+            Some(entries) => entries.iter().skip(skip).take(limit).cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn get_block_with_hash(&self, hash: &str) -> Option<Block> {
+        let height = *self.data.lock().unwrap().hashes.get(hash)?;
+        self.get_block_by_height(height)
+    }
+
+    fn get_block_with_prev_hash(&self, prev_hash: &str) -> Option<Block> {
+        let height = *self.data.lock().unwrap().hashes.get(prev_hash)?;
+        self.get_block_by_height(height + 1)
+    }
+
+    fn save_snapshot(&self, snapshot: &ChainstateSnapshot) -> Result<(), BlockchainErrors> {
+        self.data
+            .lock()
+            .unwrap()
+            .snapshots
+            .insert(snapshot.height, snapshot.clone());
+        Ok(())
+    }
+
+    fn get_last_snapshot(&self) -> Option<ChainstateSnapshot> {
+        let data = self.data.lock().unwrap();
+        data.snapshots.values().next_back().cloned()
+    }
+
+    fn get_snapshot_at(&self, max_height: usize) -> Option<ChainstateSnapshot> {
+        let data = self.data.lock().unwrap();
+        data.snapshots
+            .range(..=max_height)
+            .next_back()
+            .map(|(_, snapshot)| snapshot.clone())
+    }
+
+    fn rollback_to(&self, height: usize) -> Result<(), BlockchainErrors> {
+        let mut data = self.data.lock().unwrap();
+
+        data.snapshots.split_off(&(height + 1));
+        data.headers.split_off(&(height + 1));
+        let removed_blocks = data.blocks.split_off(&(height + 1));
+
+        for block in removed_blocks.values() {
+            data.hashes.remove(&block.hash.hash);
+        }
+        for locations in data.transactions.values_mut() {
+            locations.retain(|location| location.height <= height);
+        }
+        data.transactions
+            .retain(|_, locations| !locations.is_empty());
+        for entries in data.addresses.values_mut() {
+            entries.retain(|entry| entry.location.height <= height);
+        }
+        data.addresses.retain(|_, entries| !entries.is_empty());
+
+        Ok(())
+    }
+
+    fn get_pruned_height(&self) -> usize {
+        self.data.lock().unwrap().pruned_height
+    }
+
+    fn prune_blocks(&self, up_to: usize) -> Result<(), BlockchainErrors> {
+        let mut data = self.data.lock().unwrap();
+
+        if up_to <= data.pruned_height {
+            return Ok(());
+        }
+
+        let from = data.pruned_height + 1;
+        for (_, block) in data.blocks.range_mut(from..=up_to) {
+            block.transactions.clear();
+        }
+        data.pruned_height = up_to;
+
+        Ok(())
+    }
+
+    fn get_schema_version(&self) -> u32 {
+        self.data.lock().unwrap().schema_version
+    }
+
+    fn set_schema_version(&self, version: u32) -> Result<(), BlockchainErrors> {
+        self.data.lock().unwrap().schema_version = version;
+        Ok(())
+    }
+}
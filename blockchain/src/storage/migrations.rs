@@ -0,0 +1,118 @@
+use super::BlockStore;
+use crate::BlockchainErrors;
+
+/// Version of the layout new data is saved with
+pub static SCHEMA_VERSION: u32 = 4;
+
+/// Version of the databases that were created before schema versions were saved
+static UNVERSIONED_SCHEMA: u32 = 1;
+
+/// How many blocks are loaded at once while migrating
+static MIGRATION_BATCH_SIZE: usize = 100;
+
+/// Upgrades a database from `version - 1` to `version`
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub run: fn(&dyn BlockStore) -> Result<(), BlockchainErrors>,
+}
+
+/// All the migrations, ordered by their version
+pub static MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 2,
+        description: "Index the transactions and addresses of the saved blocks",
+        run: index_saved_blocks,
+    },
+    Migration {
+        version: 3,
+        description: "Save the headers of the saved blocks",
+        run: save_block_headers,
+    },
+    Migration {
+        version: 4,
+        description: "Index the transactions by their location, so equal transactions of different blocks don't collide",
+        run: index_transaction_locations,
+    },
+];
+
+/*
+ * Upgrade the storage to the current schema version, running the pending migrations one by one.
+ * Returns the version the storage had before.
+ */
+pub fn migrate(store: &dyn BlockStore) -> Result<u32, BlockchainErrors> {
+    let version = match store.get_schema_version() {
+        // Empty databases are already created with the current layout
+        0 if store.get_height() == 0 => {
+            store.set_schema_version(SCHEMA_VERSION)?;
+            return Ok(SCHEMA_VERSION);
+        }
+        0 => UNVERSIONED_SCHEMA,
+        version => version,
+    };
+
+    if version > SCHEMA_VERSION {
+        return Err(BlockchainErrors::UnsupportedSchemaVersion(version));
+    }
+
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|migration| migration.version > version)
+    {
+        tracing::info!(
+            "Migrating the storage to the schema version {}: {}",
+            migration.version,
+            migration.description
+        );
+
+        (migration.run)(store)?;
+
+        // The version is saved after each migration, so an interrupted upgrade continues where it stopped
+        store.set_schema_version(migration.version)?;
+    }
+
+    Ok(version)
+}
+
+/*
+ * Version 2: the transactions and addresses indexes were added.
+ */
+fn index_saved_blocks(store: &dyn BlockStore) -> Result<(), BlockchainErrors> {
+    let height = store.get_height();
+
+    for from in (1..=height).step_by(MIGRATION_BATCH_SIZE) {
+        let to = (from + MIGRATION_BATCH_SIZE - 1).min(height);
+
+        for block in store.get_blocks_range(from, to) {
+            store.index_block(&block)?;
+        }
+    }
+
+    Ok(())
+}
+
+/*
+ * Version 3: the block headers are saved on their own.
+ * Pruned blocks lost their transactions, so their Merkle root can't be calculated and they are left without a header.
+ */
+fn save_block_headers(store: &dyn BlockStore) -> Result<(), BlockchainErrors> {
+    let height = store.get_height();
+
+    for from in (store.get_pruned_height() + 1..=height).step_by(MIGRATION_BATCH_SIZE) {
+        let to = (from + MIGRATION_BATCH_SIZE - 1).min(height);
+
+        for block in store.get_blocks_range(from, to) {
+            store.save_header(&block.header())?;
+        }
+    }
+
+    Ok(())
+}
+
+/*
+ * Version 4: the transactions index keeps every location of a hash, the old index is dropped before indexing again
+ */
+fn index_transaction_locations(store: &dyn BlockStore) -> Result<(), BlockchainErrors> {
+    store.drop_legacy_transactions_index()?;
+    index_saved_blocks(store)
+}
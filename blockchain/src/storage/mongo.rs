@@ -0,0 +1,601 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        mpsc::{
+            channel,
+            Sender,
+        },
+        Arc,
+        Mutex,
+    },
+    thread,
+};
+
+use futures_util::stream::StreamExt;
+use mongodb::{
+    bson::{
+        doc,
+        Document,
+    },
+    options::{
+        ClientOptions,
+        FindOneOptions,
+        FindOptions,
+        IndexOptions,
+        InsertManyOptions,
+        InsertOneOptions,
+        ReplaceOptions,
+        ServerAddress,
+        UpdateOptions,
+        WriteConcern,
+    },
+    Client,
+    Collection,
+    Database,
+    IndexModel,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::{
+    AddressHistoryEntry,
+    BlockStore,
+    TransactionLocation,
+};
+use crate::{
+    Block,
+    BlockHeader,
+    BlockchainErrors,
+    ChainstateSnapshot,
+};
+
+type Task = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Document of the transactions index
+#[derive(Serialize, Deserialize)]
+struct TransactionEntry {
+    hash: String,
+    #[serde(flatten)]
+    location: TransactionLocation,
+}
+
+/// Document of the addresses history index
+#[derive(Serialize, Deserialize)]
+struct AddressEntry {
+    address: String,
+    #[serde(flatten)]
+    entry: AddressHistoryEntry,
+}
+
+/// Saves the blocks in a MongoDB server
+#[derive(Debug, Clone)]
+pub struct MongoStore {
+    db: Database,
+    tasks: Arc<Mutex<Sender<Task>>>,
+}
+
+impl MongoStore {
+    pub fn new(host: &str, port: u16, id: u16) -> Self {
+        let tasks = Arc::new(Mutex::new(create_tasks_handler()));
+
+        let mut client_options = ClientOptions::default();
+        client_options.hosts = vec![ServerAddress::Tcp {
+            host: host.to_string(),
+            port: Some(port),
+        }];
+
+        let db = run_task(&tasks, async move {
+            let db = Client::with_options(client_options)
+                .unwrap()
+                .database(&format!("db_{}", id));
+
+            // Blocks are looked up by their height and hash, there is only one block per height and hash
+            let blocks = db.collection::<Block>("blocks");
+            let indexes = vec![
+                IndexModel::builder()
+                    .keys(doc! { "index": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "hash.hash": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            ];
+            blocks.create_indexes(indexes, None).await.ok();
+
+            let headers = db.collection::<BlockHeader>("headers");
+            headers
+                .create_index(
+                    IndexModel::builder()
+                        .keys(doc! { "index": 1 })
+                        .options(IndexOptions::builder().unique(true).build())
+                        .build(),
+                    None,
+                )
+                .await
+                .ok();
+
+            // A transaction hash can only be once in each location
+            let transactions = db.collection::<TransactionEntry>("transactions");
+            transactions
+                .create_index(
+                    IndexModel::builder()
+                        .keys(doc! { "hash": 1, "height": 1, "position": 1 })
+                        .options(IndexOptions::builder().unique(true).build())
+                        .build(),
+                    None,
+                )
+                .await
+                .ok();
+
+            let addresses = db.collection::<AddressEntry>("addresses");
+            addresses
+                .create_index(
+                    IndexModel::builder()
+                        .keys(doc! {
+                            "address": 1,
+                            "location.height": 1,
+                            "location.position": 1,
+                            "direction": 1,
+                        })
+                        .options(IndexOptions::builder().unique(true).build())
+                        .build(),
+                    None,
+                )
+                .await
+                .ok();
+
+            db
+        });
+
+        Self { db, tasks }
+    }
+
+    /*
+     * Run a database operation in the MongoDB runtime and wait for it's result
+     */
+    fn run<T: Send + 'static>(&self, task: impl Future<Output = T> + Send + 'static) -> T {
+        run_task(&self.tasks, task)
+    }
+
+    fn find_block(&self, filter: Document) -> Option<Block> {
+        let blocks = self.db.collection::<Block>("blocks");
+
+        self.run(async move { blocks.find_one(filter, None).await.unwrap_or_default() })
+    }
+}
+
+/*
+ * Only acknowledge insertions once they have been written to the journal
+ */
+fn journaled_insert() -> InsertOneOptions {
+    InsertOneOptions::builder()
+        .write_concern(WriteConcern::builder().journal(true).build())
+        .build()
+}
+
+fn journaled_insert_many() -> InsertManyOptions {
+    InsertManyOptions::builder()
+        .write_concern(WriteConcern::builder().journal(true).build())
+        .build()
+}
+
+fn journaled_update(upsert: bool) -> UpdateOptions {
+    UpdateOptions::builder()
+        .upsert(upsert)
+        .write_concern(WriteConcern::builder().journal(true).build())
+        .build()
+}
+
+fn journaled_upsert() -> ReplaceOptions {
+    ReplaceOptions::builder()
+        .upsert(true)
+        .write_concern(WriteConcern::builder().journal(true).build())
+        .build()
+}
+
+/*
+ * Save the transactions and addresses index entries of a block, dropping the previous entries at it's height
+ * so they aren't duplicated
+ */
+async fn replace_index_entries(
+    transactions: &Collection<TransactionEntry>,
+    addresses: &Collection<AddressEntry>,
+    block: &Block,
+) -> mongodb::error::Result<()> {
+    let height = block.index.unwrap() as i64;
+
+    transactions
+        .delete_many(doc! { "height": height }, None)
+        .await?;
+    addresses
+        .delete_many(doc! { "location.height": height }, None)
+        .await?;
+
+    let transaction_entries = TransactionLocation::from_block(block)
+        .into_iter()
+        .map(|(hash, location)| TransactionEntry { hash, location })
+        .collect::<Vec<TransactionEntry>>();
+    let address_entries = AddressHistoryEntry::from_block(block)
+        .into_iter()
+        .map(|(address, entry)| AddressEntry { address, entry })
+        .collect::<Vec<AddressEntry>>();
+
+    if !transaction_entries.is_empty() {
+        transactions
+            .insert_many(transaction_entries, journaled_insert_many())
+            .await?;
+    }
+    if !address_entries.is_empty() {
+        addresses
+            .insert_many(address_entries, journaled_insert_many())
+            .await?;
+    }
+
+    Ok(())
+}
+
+impl BlockStore for MongoStore {
+    fn get_height(&self) -> usize {
+        let blocks = self.db.collection::<Block>("blocks");
+
+        self.run(async move {
+            let options = FindOneOptions::builder().sort(doc! { "index": -1 }).build();
+
+            match blocks.find_one(None, options).await {
+                Ok(Some(block)) => block.index.unwrap_or(0),
+                _ => 0,
+            }
+        })
+    }
+
+    fn get_block_by_height(&self, height: usize) -> Option<Block> {
+        self.find_block(doc! { "index": height as i64 })
+    }
+
+    fn get_blocks_range(&self, from: usize, to: usize) -> Vec<Block> {
+        let blocks = self.db.collection::<Block>("blocks");
+
+        self.run(async move {
+            let options = FindOptions::builder().sort(doc! { "index": 1 }).build();
+            let filter = doc! {
+                "index": {
+                    "$gte": from as i64,
+                    "$lte": to as i64,
+                }
+            };
+
+            let mut chain = Vec::new();
+            if let Ok(mut cursor) = blocks.find(filter, options).await {
+                while let Some(Ok(block)) = cursor.next().await {
+                    chain.push(block)
+                }
+            }
+            chain
+        })
+    }
+
+    fn add_block(&self, block: &Block) -> Result<(), BlockchainErrors> {
+        let block = block.clone();
+        let blocks = self.db.collection::<Block>("blocks");
+        let headers = self.db.collection::<BlockHeader>("headers");
+        let transactions = self.db.collection::<TransactionEntry>("transactions");
+        let addresses = self.db.collection::<AddressEntry>("addresses");
+
+        self.run(async move {
+            /*
+             * MongoDB only has multi-document transactions on replica sets, so the block is written last.
+             * If saving it fails, the header and index entries are above the saved height, readers ignore them
+             * and they are replaced when a block is saved at that height again.
+             */
+            replace_index_entries(&transactions, &addresses, &block).await?;
+
+            let header = block.header();
+            headers
+                .replace_one(
+                    doc! { "index": header.index.unwrap() as i64 },
+                    header,
+                    journaled_upsert(),
+                )
+                .await?;
+
+            blocks.insert_one(block, journaled_insert()).await
+        })
+        .map(|_| ())
+        .map_err(|err| BlockchainErrors::CouldntPersistBlock(err.to_string()))
+    }
+
+    fn save_header(&self, header: &BlockHeader) -> Result<(), BlockchainErrors> {
+        let header = header.clone();
+        let headers = self.db.collection::<BlockHeader>("headers");
+
+        self.run(async move {
+            headers
+                .replace_one(
+                    doc! { "index": header.index.unwrap() as i64 },
+                    header,
+                    journaled_upsert(),
+                )
+                .await
+        })
+        .map(|_| ())
+        .map_err(|err| BlockchainErrors::CouldntPersistBlock(err.to_string()))
+    }
+
+    fn get_header_by_height(&self, height: usize) -> Option<BlockHeader> {
+        // Headers above the saved height belong to a block that couldn't be saved
+        if height > self.get_height() {
+            return None;
+        }
+
+        let headers = self.db.collection::<BlockHeader>("headers");
+
+        self.run(async move {
+            headers
+                .find_one(doc! { "index": height as i64 }, None)
+                .await
+                .unwrap_or_default()
+        })
+    }
+
+    fn get_headers_range(&self, from: usize, to: usize) -> Vec<BlockHeader> {
+        let to = to.min(self.get_height());
+        let headers = self.db.collection::<BlockHeader>("headers");
+
+        self.run(async move {
+            let options = FindOptions::builder().sort(doc! { "index": 1 }).build();
+            let filter = doc! {
+                "index": {
+                    "$gte": from as i64,
+                    "$lte": to as i64,
+                }
+            };
+
+            let mut range = Vec::new();
+            if let Ok(mut cursor) = headers.find(filter, options).await {
+                while let Some(Ok(header)) = cursor.next().await {
+                    range.push(header)
+                }
+            }
+            range
+        })
+    }
+
+    fn index_block(&self, block: &Block) -> Result<(), BlockchainErrors> {
+        let block = block.clone();
+        let transactions = self.db.collection::<TransactionEntry>("transactions");
+        let addresses = self.db.collection::<AddressEntry>("addresses");
+
+        self.run(async move { replace_index_entries(&transactions, &addresses, &block).await })
+            .map_err(|err| BlockchainErrors::CouldntPersistBlock(err.to_string()))
+    }
+
+    fn get_transaction_locations(&self, hash: &str) -> Vec<TransactionLocation> {
+        let height = self.get_height() as i64;
+        let transactions = self.db.collection::<TransactionEntry>("transactions");
+        let hash = hash.to_string();
+
+        self.run(async move {
+            let options = FindOptions::builder()
+                .sort(doc! { "height": 1, "position": 1 })
+                .build();
+            let filter = doc! { "hash": hash, "height": { "$lte": height } };
+
+            let mut locations = Vec::new();
+            if let Ok(mut cursor) = transactions.find(filter, options).await {
+                while let Some(Ok(entry)) = cursor.next().await {
+                    locations.push(entry.location)
+                }
+            }
+            locations
+        })
+    }
+
+    fn get_address_history(
+        &self,
+        address: &str,
+        skip: usize,
+        limit: usize,
+    ) -> Vec<AddressHistoryEntry> {
+        let height = self.get_height() as i64;
+        let addresses = self.db.collection::<AddressEntry>("addresses");
+        let address = address.to_string();
+
+        self.run(async move {
+            let options = FindOptions::builder()
+                .sort(doc! { "location.height": 1, "location.position": 1 })
+                .skip(skip as u64)
+                .limit(i64::try_from(limit).unwrap_or(i64::MAX))
+                .build();
+            let filter = doc! { "address": address, "location.height": { "$lte": height } };
+
+            let mut history = Vec::new();
+            if let Ok(mut cursor) = addresses.find(filter, options).await {
+                while let Some(Ok(address_entry)) = cursor.next().await {
+                    history.push(address_entry.entry)
+                }
+            }
+            history
+        })
+    }
+
+    fn get_block_with_hash(&self, hash: &str) -> Option<Block> {
+        self.find_block(doc! { "hash.hash": hash })
+    }
+
+    fn get_block_with_prev_hash(&self, prev_hash: &str) -> Option<Block> {
+        self.find_block(doc! { "previous_hash.hash": prev_hash })
+    }
+
+    fn save_snapshot(&self, snapshot: &ChainstateSnapshot) -> Result<(), BlockchainErrors> {
+        let snapshot = snapshot.clone();
+        let snapshots = self.db.collection::<ChainstateSnapshot>("snapshots");
+
+        self.run(async move { snapshots.insert_one(snapshot, journaled_insert()).await })
+            .map(|_| ())
+            .map_err(|err| BlockchainErrors::CouldntPersistSnapshot(err.to_string()))
+    }
+
+    fn get_last_snapshot(&self) -> Option<ChainstateSnapshot> {
+        let snapshots = self.db.collection::<ChainstateSnapshot>("snapshots");
+
+        self.run(async move {
+            let options = FindOneOptions::builder()
+                .sort(doc! { "height": -1 })
+                .build();
+
+            snapshots.find_one(None, options).await.unwrap_or_default()
+        })
+    }
+
+    fn get_snapshot_at(&self, max_height: usize) -> Option<ChainstateSnapshot> {
+        let snapshots = self.db.collection::<ChainstateSnapshot>("snapshots");
+
+        self.run(async move {
+            let options = FindOneOptions::builder()
+                .sort(doc! { "height": -1 })
+                .build();
+
+            snapshots
+                .find_one(doc! { "height": { "$lte": max_height as i64 } }, options)
+                .await
+                .unwrap_or_default()
+        })
+    }
+
+    fn rollback_to(&self, height: usize) -> Result<(), BlockchainErrors> {
+        let blocks = self.db.collection::<Block>("blocks");
+        let headers = self.db.collection::<BlockHeader>("headers");
+        let transactions = self.db.collection::<TransactionEntry>("transactions");
+        let addresses = self.db.collection::<AddressEntry>("addresses");
+        let snapshots = self.db.collection::<ChainstateSnapshot>("snapshots");
+
+        self.run(async move {
+            let height = height as i64;
+
+            /*
+             * The snapshots go first so the node never starts from a snapshot of the removed blocks,
+             * index entries left behind point to blocks that don't exist and they are ignored
+             */
+            snapshots
+                .delete_many(doc! { "height": { "$gt": height } }, None)
+                .await?;
+            blocks
+                .delete_many(doc! { "index": { "$gt": height } }, None)
+                .await?;
+            headers
+                .delete_many(doc! { "index": { "$gt": height } }, None)
+                .await?;
+            transactions
+                .delete_many(doc! { "height": { "$gt": height } }, None)
+                .await?;
+            addresses
+                .delete_many(doc! { "location.height": { "$gt": height } }, None)
+                .await
+        })
+        .map(|_| ())
+        .map_err(|err| BlockchainErrors::CouldntRollbackBlocks(err.to_string()))
+    }
+
+    fn get_pruned_height(&self) -> usize {
+        let meta = self.db.collection::<Document>("meta");
+
+        self.run(async move {
+            match meta.find_one(doc! { "_id": "pruned_height" }, None).await {
+                Ok(Some(entry)) => entry.get_i64("height").unwrap_or(0) as usize,
+                _ => 0,
+            }
+        })
+    }
+
+    fn prune_blocks(&self, up_to: usize) -> Result<(), BlockchainErrors> {
+        let blocks = self.db.collection::<Block>("blocks");
+        let meta = self.db.collection::<Document>("meta");
+
+        self.run(async move {
+            blocks
+                .update_many(
+                    doc! { "index": { "$lte": up_to as i64 } },
+                    doc! { "$set": { "transactions": [] } },
+                    journaled_update(false),
+                )
+                .await?;
+
+            // The pruned height is only moved once all the blocks lost their transactions
+            meta.update_one(
+                doc! { "_id": "pruned_height" },
+                doc! { "$max": { "height": up_to as i64 } },
+                journaled_update(true),
+            )
+            .await
+        })
+        .map(|_| ())
+        .map_err(|err| BlockchainErrors::CouldntPruneBlocks(err.to_string()))
+    }
+
+    fn get_schema_version(&self) -> u32 {
+        let meta = self.db.collection::<Document>("meta");
+
+        self.run(async move {
+            match meta.find_one(doc! { "_id": "schema_version" }, None).await {
+                Ok(Some(entry)) => entry.get_i64("version").unwrap_or(0) as u32,
+                _ => 0,
+            }
+        })
+    }
+
+    fn set_schema_version(&self, version: u32) -> Result<(), BlockchainErrors> {
+        let meta = self.db.collection::<Document>("meta");
+
+        self.run(async move {
+            meta.update_one(
+                doc! { "_id": "schema_version" },
+                doc! { "$set": { "version": version as i64 } },
+                journaled_update(true),
+            )
+            .await
+        })
+        .map(|_| ())
+        .map_err(|err| BlockchainErrors::CouldntMigrateStorage(err.to_string()))
+    }
+}
+
+/*
+ * Create a thread with it's own runtime where all the MongoDB operations are run,
+ * this way the store can be used from both sync and async contexts
+ */
+fn create_tasks_handler() -> Sender<Task> {
+    let (tx, rx) = channel::<Task>();
+
+    thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            while let Ok(task) = rx.recv() {
+                tokio::spawn(task);
+            }
+        })
+    });
+
+    tx
+}
+
+fn run_task<T: Send + 'static>(
+    tasks: &Mutex<Sender<Task>>,
+    task: impl Future<Output = T> + Send + 'static,
+) -> T {
+    let (tx, rx) = channel();
+
+    tasks
+        .lock()
+        .unwrap()
+        .send(Box::pin(async move {
+            tx.send(task.await).ok();
+        }))
+        .unwrap();
+
+    rx.recv().unwrap()
+}
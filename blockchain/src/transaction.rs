@@ -1,7 +1,21 @@
 use crate::{
+    ChainId,
+    Encode,
+    Encoder,
     Key,
     PublicAddress,
     SignVerifier,
+    BATCH_TAG,
+    CANONICAL_HASH_VERSION,
+    COINBASE_TAG,
+    DELEGATE_TAG,
+    DELEGATION_HASH_VERSION,
+    FEE_HASH_VERSION,
+    HASH_VERSION,
+    MOVEMENT_TAG,
+    STAKE_TAG,
+    UNDELEGATE_TAG,
+    UNSTAKE_TAG,
 };
 use crypto::{
     digest::Digest,
@@ -10,12 +24,23 @@ use crypto::{
         Sha3Mode,
     },
 };
+use k256::ecdsa::VerifyingKey;
 
 use serde::{
     Deserialize,
     Serialize,
 };
 
+/// Commission rates are given in basis points, this one gives the whole reward to the validator
+pub static MAX_COMMISSION: u32 = 10_000;
+
+/// A recipient of a batch payment
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PaymentOutput {
+    pub to_address: String,
+    pub ammount: u64,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum Transaction {
     MOVEMENT {
@@ -25,6 +50,9 @@ pub enum Transaction {
         ammount: u64,
         hash: String,
         history: u64,
+        /// Paid to the forger of the block on top of the ammount
+        #[serde(default, skip_serializing_if = "is_zero")]
+        fee: u64,
     },
     COINBASE {
         to_address: String,
@@ -38,6 +66,58 @@ pub enum Transaction {
         ammount: u64,
         hash: String,
         history: u64,
+        /// Paid to the forger of the block on top of the ammount
+        #[serde(default, skip_serializing_if = "is_zero")]
+        fee: u64,
+        /// Part of the rewards the validator keeps before splitting them with it's delegators, in basis points
+        #[serde(default, skip_serializing_if = "is_zero")]
+        commission: u32,
+    },
+    /// Withdraws staked funds, they are locked for the unbonding period before they can be spent
+    UNSTAKE {
+        signature: Key,
+        from_address: String,
+        ammount: u64,
+        hash: String,
+        history: u64,
+        /// Paid to the forger of the block from the spendable funds, not the staked ones
+        #[serde(default, skip_serializing_if = "is_zero")]
+        fee: u64,
+    },
+    /// Bonds funds to a validator, they count towards it's election weight and earn a share of it's rewards
+    DELEGATE {
+        validator: Key,
+        signature: Key,
+        from_address: String,
+        ammount: u64,
+        hash: String,
+        history: u64,
+        /// Paid to the forger of the block on top of the ammount
+        #[serde(default, skip_serializing_if = "is_zero")]
+        fee: u64,
+    },
+    /// Takes back delegated funds, they are locked for the unbonding period before they can be spent
+    UNDELEGATE {
+        validator: Key,
+        signature: Key,
+        from_address: String,
+        ammount: u64,
+        hash: String,
+        history: u64,
+        /// Paid to the forger of the block from the spendable funds, not the delegated ones
+        #[serde(default, skip_serializing_if = "is_zero")]
+        fee: u64,
+    },
+    /// Pays many addresses at once, with a single signature and history
+    BATCH {
+        signature: Key,
+        from_address: String,
+        outputs: Vec<PaymentOutput>,
+        hash: String,
+        history: u64,
+        /// Paid to the forger of the block on top of the ammounts
+        #[serde(default, skip_serializing_if = "is_zero")]
+        fee: u64,
     },
 }
 
@@ -47,6 +127,10 @@ impl Transaction {
             Transaction::MOVEMENT { hash, .. } => hash,
             Transaction::COINBASE { hash, .. } => hash,
             Transaction::STAKE { hash, .. } => hash,
+            Transaction::UNSTAKE { hash, .. } => hash,
+            Transaction::DELEGATE { hash, .. } => hash,
+            Transaction::UNDELEGATE { hash, .. } => hash,
+            Transaction::BATCH { hash, .. } => hash,
         }
         .to_string()
     }
@@ -56,10 +140,116 @@ impl Transaction {
             Transaction::MOVEMENT { history, .. } => history,
             Transaction::COINBASE { .. } => &0_u64,
             Transaction::STAKE { history, .. } => history,
+            Transaction::UNSTAKE { history, .. } => history,
+            Transaction::DELEGATE { history, .. } => history,
+            Transaction::UNDELEGATE { history, .. } => history,
+            Transaction::BATCH { history, .. } => history,
+        }
+    }
+
+    pub fn get_fee(&self) -> u64 {
+        *match self {
+            Transaction::MOVEMENT { fee, .. } => fee,
+            Transaction::COINBASE { .. } => &0_u64,
+            Transaction::STAKE { fee, .. } => fee,
+            Transaction::UNSTAKE { fee, .. } => fee,
+            Transaction::DELEGATE { fee, .. } => fee,
+            Transaction::UNDELEGATE { fee, .. } => fee,
+            Transaction::BATCH { fee, .. } => fee,
+        }
+    }
+
+    /*
+     * What the sender spends from it's funds, the ammount and the fee, coinbases don't have a sender.
+     * Unstakes and undelegations take the ammount from the bonded funds, so they only spend the fee.
+     */
+    pub fn get_cost(&self) -> Option<u64> {
+        match self {
+            Transaction::MOVEMENT { ammount, fee, .. }
+            | Transaction::STAKE { ammount, fee, .. }
+            | Transaction::DELEGATE { ammount, fee, .. } => ammount.checked_add(*fee),
+            Transaction::UNSTAKE { fee, .. } | Transaction::UNDELEGATE { fee, .. } => Some(*fee),
+            Transaction::BATCH { outputs, fee, .. } => outputs
+                .iter()
+                .try_fold(*fee, |cost, output| cost.checked_add(output.ammount)),
+            Transaction::COINBASE { .. } => Some(0),
         }
     }
 
     pub fn hash_it(&self) -> String {
+        self.hash_with_version(HASH_VERSION)
+    }
+
+    /*
+     * Hash the transaction the way it was done in the given block hash version
+     */
+    pub fn hash_with_version(&self, version: u8) -> String {
+        let mut hasher = Sha3::new(Sha3Mode::Keccak256);
+
+        match self.legacy_hash_fields() {
+            Some(fields) if version < CANONICAL_HASH_VERSION => {
+                for field in fields {
+                    hasher.input_str(&field);
+                }
+            }
+            _ => {
+                let mut encoder = Encoder::new();
+                encoder.u8(version);
+                self.encode_payload(&mut encoder, version);
+
+                hasher.input(&encoder.into_bytes());
+            }
+        }
+
+        hasher.result_str()
+    }
+
+    /*
+     * Fields concatenated by the hashes before the canonical encoding,
+     * the transactions introduced after it are always hashed with the canonical encoding
+     */
+    fn legacy_hash_fields(&self) -> Option<Vec<String>> {
+        match self {
+            Transaction::MOVEMENT {
+                from_address,
+                to_address,
+                ammount,
+                history,
+                ..
+            } => Some(vec![
+                from_address.clone(),
+                to_address.clone(),
+                ammount.to_string(),
+                history.to_string(),
+            ]),
+            Transaction::COINBASE {
+                to_address,
+                ammount,
+                ..
+            } => Some(vec![to_address.clone(), ammount.to_string()]),
+            Transaction::STAKE {
+                author_public_key,
+                from_address,
+                ammount,
+                history,
+                ..
+            } => Some(vec![
+                author_public_key.to_string(),
+                from_address.clone(),
+                ammount.to_string(),
+                history.to_string(),
+            ]),
+            Transaction::UNSTAKE { .. }
+            | Transaction::DELEGATE { .. }
+            | Transaction::UNDELEGATE { .. }
+            | Transaction::BATCH { .. } => None,
+        }
+    }
+
+    /*
+     * Write the fields covered by the hash of the given version, which are all of them but the hash and the signature
+     */
+    fn encode_payload(&self, encoder: &mut Encoder, version: u8) {
         match self {
             Transaction::MOVEMENT {
                 from_address,
@@ -68,22 +258,19 @@ impl Transaction {
                 history,
                 ..
             } => {
-                let mut hasher = Sha3::new(Sha3Mode::Keccak256);
-                hasher.input_str(from_address);
-                hasher.input_str(to_address);
-                hasher.input_str(&ammount.to_string());
-                hasher.input_str(&history.to_string());
-                hasher.result_str()
+                encoder
+                    .u8(MOVEMENT_TAG)
+                    .str(from_address)
+                    .str(to_address)
+                    .u64(*ammount)
+                    .u64(*history);
             }
             Transaction::COINBASE {
                 to_address,
                 ammount,
                 ..
             } => {
-                let mut hasher = Sha3::new(Sha3Mode::Keccak256);
-                hasher.input_str(to_address);
-                hasher.input_str(&ammount.to_string());
-                hasher.result_str()
+                encoder.u8(COINBASE_TAG).str(to_address).u64(*ammount);
             }
             Transaction::STAKE {
                 author_public_key,
@@ -92,31 +279,145 @@ impl Transaction {
                 history,
                 ..
             } => {
-                let mut hasher = Sha3::new(Sha3Mode::Keccak256);
-                hasher.input_str(&author_public_key.to_string());
-                hasher.input_str(from_address);
-                hasher.input_str(&ammount.to_string());
-                hasher.input_str(&history.to_string());
-                hasher.result_str()
+                encoder
+                    .u8(STAKE_TAG)
+                    .bytes(&author_public_key.0)
+                    .str(from_address)
+                    .u64(*ammount)
+                    .u64(*history);
+            }
+            Transaction::UNSTAKE {
+                from_address,
+                ammount,
+                history,
+                ..
+            } => {
+                encoder
+                    .u8(UNSTAKE_TAG)
+                    .str(from_address)
+                    .u64(*ammount)
+                    .u64(*history);
+            }
+            Transaction::DELEGATE {
+                validator,
+                from_address,
+                ammount,
+                history,
+                ..
+            } => {
+                encoder
+                    .u8(DELEGATE_TAG)
+                    .bytes(&validator.0)
+                    .str(from_address)
+                    .u64(*ammount)
+                    .u64(*history);
+            }
+            Transaction::UNDELEGATE {
+                validator,
+                from_address,
+                ammount,
+                history,
+                ..
+            } => {
+                encoder
+                    .u8(UNDELEGATE_TAG)
+                    .bytes(&validator.0)
+                    .str(from_address)
+                    .u64(*ammount)
+                    .u64(*history);
+            }
+            Transaction::BATCH {
+                from_address,
+                outputs,
+                history,
+                ..
+            } => {
+                encoder
+                    .u8(BATCH_TAG)
+                    .str(from_address)
+                    .list(outputs)
+                    .u64(*history);
             }
         }
+
+        if version >= FEE_HASH_VERSION {
+            if let Transaction::MOVEMENT { fee, .. }
+            | Transaction::STAKE { fee, .. }
+            | Transaction::UNSTAKE { fee, .. }
+            | Transaction::DELEGATE { fee, .. }
+            | Transaction::UNDELEGATE { fee, .. }
+            | Transaction::BATCH { fee, .. } = self
+            {
+                encoder.u64(*fee);
+            }
+        }
+
+        if version >= DELEGATION_HASH_VERSION {
+            if let Transaction::STAKE { commission, .. } = self {
+                encoder.u32(*commission);
+            }
+        }
+    }
+
+    /*
+     * Verify the hash and the signature of a new transaction, it must have been signed for the given chain
+     */
+    pub fn verify(&self, chain: &ChainId) -> bool {
+        self.verify_with_version(HASH_VERSION, chain)
     }
 
-    pub fn verify(&self) -> bool {
+    /*
+     * Verify the hash and the signature of a transaction confirmed in a block of the given hash version
+     */
+    pub fn verify_with_version(&self, version: u8, chain: &ChainId) -> bool {
         match self {
             Transaction::MOVEMENT {
                 signature,
                 from_address,
                 hash,
                 ..
+            }
+            | Transaction::UNSTAKE {
+                signature,
+                from_address,
+                hash,
+                ..
+            }
+            | Transaction::DELEGATE {
+                signature,
+                from_address,
+                hash,
+                ..
+            }
+            | Transaction::UNDELEGATE {
+                signature,
+                from_address,
+                hash,
+                ..
+            }
+            | Transaction::BATCH {
+                signature,
+                from_address,
+                hash,
+                ..
             } => {
+                // A batch must pay someone
+                if matches!(self, Transaction::BATCH { outputs, .. } if outputs.is_empty()) {
+                    return false;
+                }
+
                 // Make sure the hash is not altered
-                if &self.hash_it() != hash {
+                if &self.hash_with_version(version) != hash {
                     return false;
                 }
 
                 // Verify the signature
-                let public_address = PublicAddress::from_signature(&signature.0, hash.as_bytes());
+                let signed_data = chain.signing_data_with_version(version, hash);
+                let public_address =
+                    match PublicAddress::from_signature(&signature.0, signed_data.as_bytes()) {
+                        Ok(public_address) => public_address,
+                        Err(_) => return false,
+                    };
 
                 let address = public_address.get_public().hash_it();
 
@@ -128,7 +429,7 @@ impl Transaction {
             }
             Transaction::COINBASE { hash, .. } => {
                 // Make sure the hash is not altered
-                if &self.hash_it() != hash {
+                if &self.hash_with_version(version) != hash {
                     return false;
                 }
 
@@ -139,8 +440,13 @@ impl Transaction {
                 signature,
                 from_address,
                 hash,
+                commission,
                 ..
             } => {
+                if *commission > MAX_COMMISSION {
+                    return false;
+                }
+
                 let public_key_hashed = author_public_key.hash_it();
 
                 // Ensure the hashed public key is the same as the from_address
@@ -149,15 +455,83 @@ impl Transaction {
                 }
 
                 // Make sure the hash is not altered
-                if &self.hash_it() != hash {
+                if &self.hash_with_version(version) != hash {
                     return false;
                 }
 
-                // Verify the signature
+                // Verify the signature, with a key that can be parsed
+                if VerifyingKey::from_sec1_bytes(&author_public_key.0).is_err() {
+                    return false;
+                }
                 let public_address = PublicAddress::from(author_public_key);
 
-                public_address.verify_signature(signature, hash.to_string())
+                public_address
+                    .verify_signature(signature, chain.signing_data_with_version(version, hash))
+            }
+        }
+    }
+
+    /*
+     * Verify a transaction of a genesis block, the stakes that come from the chain spec aren't signed
+     */
+    pub fn verify_genesis_with_version(&self, version: u8, chain: &ChainId) -> bool {
+        match self {
+            Transaction::STAKE {
+                author_public_key,
+                signature,
+                from_address,
+                hash,
+                ..
+            } if signature.0.is_empty() => {
+                &author_public_key.hash_it() == from_address
+                    && &self.hash_with_version(version) == hash
             }
+            _ => self.verify_with_version(version, chain),
         }
     }
 }
+
+/*
+ * Transactions without fees or commissions serialize like before they existed, legacy block hashes depend on it
+ */
+fn is_zero<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+impl Encode for Transaction {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.encode_payload(encoder, HASH_VERSION);
+
+        match self {
+            Transaction::MOVEMENT {
+                signature, hash, ..
+            }
+            | Transaction::STAKE {
+                signature, hash, ..
+            }
+            | Transaction::UNSTAKE {
+                signature, hash, ..
+            }
+            | Transaction::DELEGATE {
+                signature, hash, ..
+            }
+            | Transaction::UNDELEGATE {
+                signature, hash, ..
+            }
+            | Transaction::BATCH {
+                signature, hash, ..
+            } => {
+                encoder.str(hash).bytes(&signature.0);
+            }
+            Transaction::COINBASE { hash, .. } => {
+                encoder.str(hash);
+            }
+        }
+    }
+}
+
+impl Encode for PaymentOutput {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.str(&self.to_address).u64(self.ammount);
+    }
+}
@@ -1,13 +1,7 @@
-use crypto::{
-    digest::Digest,
-    sha3::{
-        Sha3,
-        Sha3Mode,
-    },
-};
-
 use crate::{
+    ChainId,
     Key,
+    PaymentOutput,
     Transaction,
     Wallet,
 };
@@ -15,6 +9,10 @@ use crate::{
 pub enum TransactionType {
     MOVEMENT,
     STAKE,
+    UNSTAKE,
+    DELEGATE,
+    UNDELEGATE,
+    BATCH,
     COINBASE,
 }
 
@@ -24,8 +22,13 @@ pub struct TransactionBuilder {
     pub to_address: Option<String>,
     pub ammount: Option<u64>,
     pub history: Option<u64>,
+    pub fee: Option<u64>,
+    pub commission: Option<u32>,
+    pub validator: Option<Key>,
+    pub outputs: Vec<PaymentOutput>,
     pub type_tx: Option<TransactionType>,
     pub wallet: Option<Wallet>,
+    pub chain: Option<ChainId>,
 }
 
 impl Default for TransactionBuilder {
@@ -42,8 +45,13 @@ impl TransactionBuilder {
             to_address: None,
             ammount: None,
             history: None,
+            fee: None,
+            commission: None,
+            validator: None,
+            outputs: Vec::new(),
             type_tx: None,
             wallet: None,
+            chain: None,
         }
     }
 
@@ -57,18 +65,58 @@ impl TransactionBuilder {
         self
     }
 
+    pub fn fee(&mut self, fee: u64) -> &mut Self {
+        self.fee = Some(fee);
+        self
+    }
+
+    /*
+     * Commission of a validator in basis points, only for stakes
+     */
+    pub fn commission(&mut self, commission: u32) -> &mut Self {
+        self.commission = Some(commission);
+        self
+    }
+
+    /*
+     * Validator the funds are delegated to, only for delegations and undelegations
+     */
+    pub fn validator(&mut self, validator: &Key) -> &mut Self {
+        self.validator = Some(validator.clone());
+        self
+    }
+
+    /*
+     * Add a recipient to a batch payment
+     */
+    pub fn output(&mut self, to_address: &str, ammount: u64) -> &mut Self {
+        self.outputs.push(PaymentOutput {
+            to_address: to_address.to_string(),
+            ammount,
+        });
+        self
+    }
+
     pub fn is_type(&mut self, type_tx: TransactionType) -> &mut Self {
         self.type_tx = Some(type_tx);
         self
     }
 
+    /*
+     * Chain the transaction is signed for, only coinbases don't need it
+     */
+    pub fn chain(&mut self, chain: &ChainId) -> &mut Self {
+        self.chain = Some(chain.clone());
+        self
+    }
+
     pub fn with_wallet(&mut self, wallet: &mut Wallet) -> &mut Self {
         self.wallet = Some(wallet.clone());
         self.history = Some(wallet.history);
         self.author_public_key = Some(wallet.get_public());
         self.from_address = Some(wallet.get_public().hash_it());
         match self.type_tx.as_ref().unwrap() {
-            TransactionType::COINBASE { .. } => {}
+            TransactionType::COINBASE => {}
             _ => {
                 wallet.history += 1;
             }
@@ -79,63 +127,99 @@ impl TransactionBuilder {
     pub fn build(&self) -> Transaction {
         let type_tx = self.type_tx.as_ref().unwrap();
 
-        match type_tx {
-            TransactionType::COINBASE => {
-                let mut hasher = Sha3::new(Sha3Mode::Keccak256);
-
-                hasher.input_str(self.to_address.as_ref().unwrap());
-                hasher.input_str(&self.ammount.unwrap().to_string());
+        let mut transaction = match type_tx {
+            TransactionType::COINBASE => Transaction::COINBASE {
+                to_address: self.to_address.as_ref().unwrap().clone(),
+                ammount: *self.ammount.as_ref().unwrap(),
+                hash: String::new(),
+            },
+            TransactionType::MOVEMENT => Transaction::MOVEMENT {
+                signature: Key(Vec::new()),
+                from_address: self.from_address.as_ref().unwrap().clone(),
+                to_address: self.to_address.as_ref().unwrap().clone(),
+                ammount: *self.ammount.as_ref().unwrap(),
+                hash: String::new(),
+                history: self.history.unwrap(),
+                fee: self.fee.unwrap_or(0),
+            },
+            TransactionType::STAKE => Transaction::STAKE {
+                author_public_key: self.author_public_key.as_ref().unwrap().clone(),
+                signature: Key(Vec::new()),
+                from_address: self.from_address.as_ref().unwrap().clone(),
+                ammount: *self.ammount.as_ref().unwrap(),
+                hash: String::new(),
+                history: self.history.unwrap(),
+                fee: self.fee.unwrap_or(0),
+                commission: self.commission.unwrap_or(0),
+            },
+            TransactionType::UNSTAKE => Transaction::UNSTAKE {
+                signature: Key(Vec::new()),
+                from_address: self.from_address.as_ref().unwrap().clone(),
+                ammount: *self.ammount.as_ref().unwrap(),
+                hash: String::new(),
+                history: self.history.unwrap(),
+                fee: self.fee.unwrap_or(0),
+            },
+            TransactionType::DELEGATE => Transaction::DELEGATE {
+                validator: self.validator.as_ref().unwrap().clone(),
+                signature: Key(Vec::new()),
+                from_address: self.from_address.as_ref().unwrap().clone(),
+                ammount: *self.ammount.as_ref().unwrap(),
+                hash: String::new(),
+                history: self.history.unwrap(),
+                fee: self.fee.unwrap_or(0),
+            },
+            TransactionType::UNDELEGATE => Transaction::UNDELEGATE {
+                validator: self.validator.as_ref().unwrap().clone(),
+                signature: Key(Vec::new()),
+                from_address: self.from_address.as_ref().unwrap().clone(),
+                ammount: *self.ammount.as_ref().unwrap(),
+                hash: String::new(),
+                history: self.history.unwrap(),
+                fee: self.fee.unwrap_or(0),
+            },
+            TransactionType::BATCH => Transaction::BATCH {
+                signature: Key(Vec::new()),
+                from_address: self.from_address.as_ref().unwrap().clone(),
+                outputs: self.outputs.clone(),
+                hash: String::new(),
+                history: self.history.unwrap(),
+                fee: self.fee.unwrap_or(0),
+            },
+        };
 
-                let hash = hasher.result_str();
+        // The hash only covers the fields above, then the author signs it for the chain
+        let transaction_hash = transaction.hash_it();
 
-                Transaction::COINBASE {
-                    to_address: self.to_address.as_ref().unwrap().clone(),
-                    ammount: *self.ammount.as_ref().unwrap(),
-                    hash,
-                }
+        match &mut transaction {
+            Transaction::COINBASE { hash, .. } => {
+                *hash = transaction_hash;
             }
-            TransactionType::MOVEMENT => {
-                let wallet = self.wallet.as_ref().unwrap();
-                let mut hasher = Sha3::new(Sha3Mode::Keccak256);
-
-                hasher.input_str(self.from_address.as_ref().unwrap());
-                hasher.input_str(self.to_address.as_ref().unwrap());
-                hasher.input_str(&self.ammount.unwrap().to_string());
-                hasher.input_str(&self.history.unwrap().to_string());
-
-                let hash = hasher.result_str();
-                let signature = wallet.sign_data(hash.clone());
-
-                Transaction::MOVEMENT {
-                    signature,
-                    from_address: self.from_address.as_ref().unwrap().clone(),
-                    to_address: self.to_address.as_ref().unwrap().clone(),
-                    ammount: *self.ammount.as_ref().unwrap(),
-                    hash,
-                    history: self.history.unwrap(),
-                }
+            Transaction::MOVEMENT {
+                signature, hash, ..
+            }
+            | Transaction::STAKE {
+                signature, hash, ..
+            }
+            | Transaction::UNSTAKE {
+                signature, hash, ..
             }
-            TransactionType::STAKE => {
+            | Transaction::DELEGATE {
+                signature, hash, ..
+            }
+            | Transaction::UNDELEGATE {
+                signature, hash, ..
+            }
+            | Transaction::BATCH {
+                signature, hash, ..
+            } => {
                 let wallet = self.wallet.as_ref().unwrap();
-                let mut hasher = Sha3::new(Sha3Mode::Keccak256);
-
-                hasher.input_str(&self.author_public_key.as_ref().unwrap().to_string());
-                hasher.input_str(self.from_address.as_ref().unwrap());
-                hasher.input_str(&self.ammount.unwrap().to_string());
-                hasher.input_str(&self.history.unwrap().to_string());
-
-                let hash = hasher.result_str();
-                let signature = wallet.sign_data(hash.clone());
-
-                Transaction::STAKE {
-                    author_public_key: self.author_public_key.as_ref().unwrap().clone(),
-                    signature,
-                    from_address: self.from_address.as_ref().unwrap().clone(),
-                    ammount: *self.ammount.as_ref().unwrap(),
-                    hash,
-                    history: self.history.unwrap(),
-                }
+                let chain = self.chain.as_ref().unwrap();
+                *signature = wallet.sign_data(chain.signing_data(&transaction_hash));
+                *hash = transaction_hash;
             }
         }
+
+        transaction
     }
 }
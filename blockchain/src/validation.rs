@@ -0,0 +1,427 @@
+use chrono::{
+    DateTime,
+    Duration,
+    Utc,
+};
+use k256::ecdsa::{
+    recoverable,
+    signature::Signature,
+    VerifyingKey,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    rank_forgers,
+    Block,
+    BlockHeader,
+    ChainId,
+    Chainstate,
+    ConsensusParams,
+    Encode,
+    Key,
+    PublicAddress,
+    Transaction,
+};
+
+/// Reason why a block was rejected
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BlockValidationError {
+    /// The hash doesn't match the content of the block, or it's version is unknown
+    InvalidHash,
+    /// The block wasn't signed by it's forger for this chain
+    InvalidSignature,
+    /// The block is hashed with an older version than the previous block, which signatures might not be bound to the chain
+    HashVersionDowngrade { previous: u8, found: u8 },
+    /// The block doesn't follow the last block of the chain
+    InvalidPreviousHash {
+        expected: Option<String>,
+        found: Option<String>,
+    },
+    /// The block has transactions but the first one isn't a coinbase
+    MissingCoinbase,
+    /// Hash of a coinbase that isn't the first transaction
+    MultipleCoinbase(String),
+    /// Hash of a coinbase that doesn't reward the forger
+    InvalidCoinbaseAddress(String),
+    /// The coinbase gives more than the block reward and the fees
+    InvalidCoinbaseAmmount { max: u64, found: u64 },
+    /// Address of a forger that has no stake
+    IneligibleForger(String),
+    /// The forger isn't the elected one, and the slots of the forgers ranked before it haven't passed yet
+    NotElectedForger { elected: String, found: String },
+    /// Address of a forger that also forged the previous block
+    RepeatedForger(String),
+    /// The timestamp isn't later than the previous block's
+    TimestampNotAfterPrevious {
+        previous: DateTime<Utc>,
+        found: DateTime<Utc>,
+    },
+    /// The timestamp is further in the future than the allowed drift
+    TimestampTooFarInFuture {
+        max: DateTime<Utc>,
+        found: DateTime<Utc>,
+    },
+    /// The block has more transactions than allowed, the coinbase isn't counted
+    TooManyTransactions { max: usize, found: usize },
+    /// The block takes more bytes than allowed in it's canonical encoding
+    BlockTooLarge { max: usize, found: usize },
+    /// Hash of a transaction that isn't correctly signed for this chain or can't be applied on the chainstate
+    InvalidTransaction(String),
+}
+
+/*
+ * Make sure the block can be appended on top of the chainstate's last block
+ *
+ * Checks the hash, signature, link to the previous block, coinbase position and ammount, forger eligibility,
+ * timestamp, size and the validity of every transaction. The genesis block is only checked structurally,
+ * as it creates the initial supply and stakes.
+ *
+ * The timestamp is compared against the clock of this node, see `validate_block_at` to use another time.
+ */
+pub fn validate_block(
+    block: &Block,
+    chainstate: &Chainstate,
+    params: &ConsensusParams,
+    chain: &ChainId,
+) -> Result<(), BlockValidationError> {
+    validate_block_at(block, chainstate, params, chain, Utc::now())
+}
+
+/*
+ * Same as `validate_block`, with `now` as the current time
+ */
+pub fn validate_block_at(
+    block: &Block,
+    chainstate: &Chainstate,
+    params: &ConsensusParams,
+    chain: &ChainId,
+    now: DateTime<Utc>,
+) -> Result<(), BlockValidationError> {
+    let previous_block = chainstate.last_block.as_ref();
+
+    // The size is checked first so oversized blocks are rejected before hashing them
+    if previous_block.is_some() {
+        validate_size(block, params)?;
+    }
+
+    validate_structure(block, chain)?;
+
+    match previous_block {
+        Some(previous_block) => validate_link(block, previous_block)?,
+        None => {
+            if let Some(previous_hash) = &block.previous_hash {
+                return Err(BlockValidationError::InvalidPreviousHash {
+                    expected: None,
+                    found: Some(previous_hash.unite()),
+                });
+            }
+        }
+    }
+
+    if let Some(previous_block) = previous_block {
+        validate_future_drift(block, params, now)?;
+        validate_coinbase_ammount(block, params)?;
+        validate_forger(block, previous_block, chainstate, params, now)?;
+    }
+
+    validate_transactions(block, chainstate, chain)
+}
+
+/*
+ * Rules a block must follow by itself: the hash, signature and coinbase position
+ */
+pub(crate) fn validate_structure(
+    block: &Block,
+    chain: &ChainId,
+) -> Result<(), BlockValidationError> {
+    if block.verify_integrity().is_err() {
+        return Err(BlockValidationError::InvalidHash);
+    }
+
+    let forger = block.key.hash_it();
+
+    // The first transaction must always be a coinbase rewarding the block creator, and the only one
+    for (i, tx) in block.transactions.iter().enumerate() {
+        match tx {
+            Transaction::COINBASE {
+                to_address, hash, ..
+            } => {
+                if i > 0 {
+                    return Err(BlockValidationError::MultipleCoinbase(hash.clone()));
+                }
+                if to_address != &forger {
+                    return Err(BlockValidationError::InvalidCoinbaseAddress(hash.clone()));
+                }
+            }
+            _ if i == 0 => return Err(BlockValidationError::MissingCoinbase),
+            _ => {}
+        }
+    }
+
+    // Malformed keys or signatures can't be verified
+    let is_well_formed =
+        is_key_well_formed(&block.key) && is_signature_well_formed(&block.signature);
+
+    if !is_well_formed || !block.verify_sign_with(&PublicAddress::from(&block.key), chain) {
+        return Err(BlockValidationError::InvalidSignature);
+    }
+
+    Ok(())
+}
+
+/*
+ * Limits on the transactions of a block, the coinbase isn't counted, and on it's size in bytes
+ */
+pub(crate) fn validate_size(
+    block: &Block,
+    params: &ConsensusParams,
+) -> Result<(), BlockValidationError> {
+    let transactions = block.transactions.len().saturating_sub(1);
+    if transactions > params.max_block_transactions {
+        return Err(BlockValidationError::TooManyTransactions {
+            max: params.max_block_transactions,
+            found: transactions,
+        });
+    }
+
+    let size = block.to_bytes().len();
+    if size > params.max_block_size {
+        return Err(BlockValidationError::BlockTooLarge {
+            max: params.max_block_size,
+            found: size,
+        });
+    }
+
+    Ok(())
+}
+
+/*
+ * Rules between a block and the previous one
+ */
+pub(crate) fn validate_link(
+    block: &Block,
+    previous_block: &BlockHeader,
+) -> Result<(), BlockValidationError> {
+    if block.previous_hash.as_ref() != Some(&previous_block.hash) {
+        return Err(BlockValidationError::InvalidPreviousHash {
+            expected: Some(previous_block.hash.unite()),
+            found: block.previous_hash.as_ref().map(|hash| hash.unite()),
+        });
+    }
+
+    // Otherwise blocks could keep being signed without the chain
+    if block.hash.version < previous_block.hash.version {
+        return Err(BlockValidationError::HashVersionDowngrade {
+            previous: previous_block.hash.version,
+            found: block.hash.version,
+        });
+    }
+
+    // The same forger can't create two blocks in a row
+    if previous_block.key == block.key {
+        return Err(BlockValidationError::RepeatedForger(block.key.hash_it()));
+    }
+
+    // Otherwise a forger could backdate it's block to hide that it was late
+    if block.timestamp <= previous_block.timestamp {
+        return Err(BlockValidationError::TimestampNotAfterPrevious {
+            previous: previous_block.timestamp,
+            found: block.timestamp,
+        });
+    }
+
+    Ok(())
+}
+
+/*
+ * Clocks of the nodes aren't exactly the same, so blocks can be a bit ahead of ours
+ */
+fn validate_future_drift(
+    block: &Block,
+    params: &ConsensusParams,
+    now: DateTime<Utc>,
+) -> Result<(), BlockValidationError> {
+    let max = now + Duration::milliseconds(params.max_future_drift);
+
+    if block.timestamp > max {
+        return Err(BlockValidationError::TimestampTooFarInFuture {
+            max,
+            found: block.timestamp,
+        });
+    }
+
+    Ok(())
+}
+
+/*
+ * The forger can reward itself with the block reward and the fees of the block transactions
+ */
+fn validate_coinbase_ammount(
+    block: &Block,
+    params: &ConsensusParams,
+) -> Result<(), BlockValidationError> {
+    if let Some(Transaction::COINBASE { ammount, .. }) = block.transactions.first() {
+        let max = block
+            .transactions
+            .iter()
+            .map(|tx| tx.get_fee())
+            .fold(params.block_reward, u64::saturating_add);
+
+        if *ammount > max {
+            return Err(BlockValidationError::InvalidCoinbaseAmmount {
+                max,
+                found: *ammount,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/*
+ * Only the elected forger can forge, when it misses it's slot the next one of the ranking can once `block_time_max`
+ * passed since the previous block, and so on. Until someone stakes anyone can forge so the chain can start.
+ *
+ * The time passed is measured by our clock when the block is ahead of it, otherwise a forger could take a later
+ * slot early by setting it's timestamp within the allowed drift.
+ */
+fn validate_forger(
+    block: &Block,
+    previous_block: &BlockHeader,
+    chainstate: &Chainstate,
+    params: &ConsensusParams,
+    now: DateTime<Utc>,
+) -> Result<(), BlockValidationError> {
+    if chainstate.last_staking_addresses.is_empty() {
+        return Ok(());
+    }
+
+    let ranking = rank_forgers(chainstate);
+
+    let rank = match ranking.iter().position(|key| key == &block.key) {
+        Some(rank) => rank as i64,
+        None => return Err(BlockValidationError::IneligibleForger(block.key.hash_it())),
+    };
+
+    let elapsed = block
+        .timestamp
+        .min(now)
+        .signed_duration_since(previous_block.timestamp)
+        .num_milliseconds();
+
+    if elapsed < rank.saturating_mul(params.block_time_max) {
+        return Err(BlockValidationError::NotElectedForger {
+            elected: ranking[0].hash_it(),
+            found: block.key.hash_it(),
+        });
+    }
+
+    Ok(())
+}
+
+/*
+ * Every transaction must be correctly signed and be applicable after the previous ones of the block.
+ * They are applied on a copy of the chainstate limited to the addresses the block touches.
+ */
+fn validate_transactions(
+    block: &Block,
+    chainstate: &Chainstate,
+    chain: &ChainId,
+) -> Result<(), BlockValidationError> {
+    let addresses = block
+        .transactions
+        .iter()
+        .flat_map(touched_addresses)
+        .collect::<Vec<String>>();
+    let mut block_chainstate = chainstate.with_only_addresses(addresses.iter());
+
+    // Only the stakes of the genesis block, which come from the chain spec, aren't signed
+    let is_genesis = block.previous_hash.is_none();
+
+    for tx in &block.transactions {
+        if is_transaction_well_formed(tx, is_genesis)
+            && block.verify_transaction(tx, chain)
+            && block_chainstate.verify_transaction_ammount(tx)
+            && block_chainstate.verify_transaction_history(tx)
+        {
+            block_chainstate.effect_transaction(tx);
+        } else {
+            return Err(BlockValidationError::InvalidTransaction(tx.get_hash()));
+        }
+    }
+
+    Ok(())
+}
+
+/*
+ * Malformed keys or signatures of a transaction can't be verified
+ */
+fn is_transaction_well_formed(tx: &Transaction, is_genesis: bool) -> bool {
+    match tx {
+        Transaction::COINBASE { .. } => true,
+        Transaction::STAKE {
+            author_public_key,
+            signature,
+            ..
+        } => {
+            is_key_well_formed(author_public_key)
+                && ((is_genesis && signature.0.is_empty()) || is_signature_well_formed(signature))
+        }
+        Transaction::DELEGATE {
+            validator,
+            signature,
+            ..
+        }
+        | Transaction::UNDELEGATE {
+            validator,
+            signature,
+            ..
+        } => is_key_well_formed(validator) && is_signature_well_formed(signature),
+        Transaction::MOVEMENT { signature, .. }
+        | Transaction::UNSTAKE { signature, .. }
+        | Transaction::BATCH { signature, .. } => is_signature_well_formed(signature),
+    }
+}
+
+fn is_key_well_formed(key: &Key) -> bool {
+    VerifyingKey::from_sec1_bytes(&key.0).is_ok()
+}
+
+fn is_signature_well_formed(signature: &Key) -> bool {
+    <recoverable::Signature as Signature>::from_bytes(&signature.0).is_ok()
+}
+
+fn touched_addresses(tx: &Transaction) -> Vec<String> {
+    match tx {
+        Transaction::MOVEMENT {
+            from_address,
+            to_address,
+            ..
+        } => vec![from_address.clone(), to_address.clone()],
+        Transaction::COINBASE { to_address, .. } => vec![to_address.clone()],
+        Transaction::STAKE { from_address, .. } | Transaction::UNSTAKE { from_address, .. } => {
+            vec![from_address.clone()]
+        }
+        Transaction::DELEGATE {
+            validator,
+            from_address,
+            ..
+        }
+        | Transaction::UNDELEGATE {
+            validator,
+            from_address,
+            ..
+        } => vec![from_address.clone(), validator.hash_it()],
+        Transaction::BATCH {
+            from_address,
+            outputs,
+            ..
+        } => std::iter::once(from_address)
+            .chain(outputs.iter().map(|output| &output.to_address))
+            .cloned()
+            .collect(),
+    }
+}
@@ -10,7 +10,6 @@ use k256::{
             Signer,
             Verifier,
         },
-        Signature as NormalSignature,
         SigningKey,
         VerifyingKey,
     },
@@ -40,7 +39,11 @@ impl std::fmt::Debug for Wallet {
 impl SignVerifier for Wallet {
     fn verify_signature(&self, signature: &Key, data: String) -> bool {
         let verify_key = VerifyingKey::from(&self.sign_key);
-        let signature: NormalSignature = NormalSignature::from_bytes(&signature.0).unwrap();
+        // The signatures of `sign_data` are recoverable
+        let signature = match RecoverableSignature::from_bytes(&signature.0) {
+            Ok(signature) => signature,
+            Err(_) => return false,
+        };
         verify_key.verify(data.as_bytes(), &signature).is_ok()
     }
 }
@@ -61,6 +64,7 @@ impl Wallet {
     /// # Example
     ///
     /// ```
+    /// # use blockchain::Wallet;
     /// let wallet = Wallet::new();
     /// // The signature now certifies that the wallet signed `Hello World`
     /// let signature = wallet.sign_data("Hello World".to_string());
@@ -77,6 +81,7 @@ impl Wallet {
     /// # Example
     ///
     /// ```
+    /// # use blockchain::Wallet;
     /// let wallet = Wallet::new();
     /// let public_key = wallet.get_public();
     /// // It can be hashed, like an address
@@ -93,6 +98,7 @@ impl Wallet {
     /// # Example
     ///
     /// ```
+    /// # use blockchain::Wallet;
     /// let wallet = Wallet::new();
     /// // You can save it and then use `from_private`
     /// let private_key = wallet.get_private();
@@ -108,7 +114,10 @@ impl Wallet {
     /// # Example
     ///
     /// ```
-    /// let wallet = Wallet::from_private(private_key, history);
+    /// # use blockchain::Wallet;
+    /// # let private_key = Wallet::new().get_private().0;
+    /// # let history = 0;
+    /// let wallet = Wallet::from_private(&private_key, history);
     /// ```
     ///
     pub fn from_private(private_key: &[u8], history: u64) -> Self {
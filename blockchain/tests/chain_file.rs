@@ -0,0 +1,200 @@
+mod common;
+
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use blockchain::{
+    BlockBuilder,
+    Blockchain,
+    BlockchainErrors,
+    ChainFileHeader,
+    Configuration,
+    ConsensusParams,
+    MemoryStore,
+    Metrics,
+    TransactionBuilder,
+    TransactionType,
+    Wallet,
+    CHAIN_FILE_VERSION,
+};
+use chrono::Utc;
+use common::reward;
+use tokio_test::block_on;
+
+fn create_blockchain(chain_name: &str) -> Blockchain {
+    let mut config = Configuration::new();
+    config.chain_name = chain_name.to_string();
+
+    block_on(Blockchain::with_store(
+        config,
+        Arc::new(MemoryStore::default()),
+        Arc::new(Mutex::new(Metrics::new(vec![]))),
+    ))
+}
+
+/*
+ * Create a chain where two forgers take turns and the first one pays the second one
+ */
+fn create_exported_chain() -> Vec<u8> {
+    let mut blockchain = create_blockchain("mars");
+    let chain = blockchain.chain_id();
+    let mut forgers = [Wallet::new(), Wallet::new()];
+
+    for i in 0..4 {
+        let to_address = forgers[(i + 1) % 2].get_public().hash_it();
+        let forger = &mut forgers[i % 2];
+
+        let reward = TransactionBuilder::new()
+            .to_address(&forger.get_public().hash_it())
+            .ammount(10)
+            .is_type(TransactionType::COINBASE)
+            .with_wallet(forger)
+            .build();
+
+        let mut transactions = vec![reward];
+
+        if i == 2 {
+            transactions.push(
+                TransactionBuilder::new()
+                    .to_address(&to_address)
+                    .ammount(3)
+                    .is_type(TransactionType::MOVEMENT)
+                    .chain(&chain)
+                    .with_wallet(forger)
+                    .build(),
+            );
+        }
+
+        let mut builder = BlockBuilder::new();
+        builder
+            .transactions(&transactions)
+            .timestamp(Utc::now())
+            .key(&forger.get_public());
+
+        if let Some(last_block_hash) = &blockchain.last_block_hash {
+            builder.previous_hash(last_block_hash);
+        }
+
+        blockchain
+            .add_block(&builder.hash_it().sign_with(forger, &chain).build())
+            .unwrap();
+    }
+
+    let mut file = Vec::new();
+    assert_eq!(blockchain.export_chain(&mut file).unwrap(), 4);
+
+    file
+}
+
+#[test]
+fn export_and_import() {
+    let file = create_exported_chain();
+
+    let header = ChainFileHeader::read_from(&mut file.as_slice()).unwrap();
+    assert_eq!(header.version, CHAIN_FILE_VERSION);
+    assert_eq!(header.chain_name, "mars");
+
+    let mut blockchain = create_blockchain("mars");
+    assert_eq!(blockchain.import_chain(&mut file.as_slice()).unwrap(), 4);
+
+    assert_eq!(blockchain.index, 4);
+    assert_eq!(
+        blockchain
+            .get_block_by_height(1)
+            .unwrap()
+            .map(|block| block.hash),
+        header.genesis_hash
+    );
+
+    let first_forger = blockchain
+        .get_block_by_height(1)
+        .unwrap()
+        .unwrap()
+        .key
+        .hash_it();
+    let second_forger = blockchain
+        .get_block_by_height(2)
+        .unwrap()
+        .unwrap()
+        .key
+        .hash_it();
+    assert_eq!(blockchain.state.get_address_ammount(first_forger), 17);
+    assert_eq!(blockchain.state.get_address_ammount(second_forger), 23);
+
+    // Only empty nodes can import a chain
+    assert!(matches!(
+        blockchain.import_chain(&mut file.as_slice()),
+        Err(BlockchainErrors::CouldntImportChain(_))
+    ));
+}
+
+#[test]
+fn import_rejects_other_chains() {
+    let file = create_exported_chain();
+
+    let mut blockchain = create_blockchain("venus");
+    assert!(matches!(
+        blockchain.import_chain(&mut file.as_slice()),
+        Err(BlockchainErrors::CouldntImportChain(_))
+    ));
+    assert_eq!(blockchain.index, 0);
+}
+
+#[test]
+fn import_rejects_tampered_blocks() {
+    let mut file = create_exported_chain();
+
+    // Raise the payment without changing the length of the block
+    let payment = b"\"ammount\":3";
+    let position = file
+        .windows(payment.len())
+        .position(|window| window == payment)
+        .unwrap();
+    file[position + payment.len() - 1] = b'9';
+
+    let mut blockchain = create_blockchain("mars");
+    assert!(blockchain.import_chain(&mut file.as_slice()).is_err());
+
+    // The blocks before the tampered one were imported
+    assert_eq!(blockchain.index, 2);
+}
+
+#[test]
+fn import_into_spec_nodes() {
+    let forger = Wallet::new();
+    let mut blockchain = common::create_blockchain(&[&forger], &[], ConsensusParams::default());
+    let chain = blockchain.chain_id();
+
+    let block = BlockBuilder::new()
+        .transactions(&[reward(&forger, 10)])
+        .timestamp(Utc::now())
+        .key(&forger.get_public())
+        .previous_hash(&blockchain.last_block_hash.clone().unwrap())
+        .hash_it()
+        .sign_with(&forger, &chain)
+        .build();
+    blockchain.import_block(&block).unwrap();
+
+    let mut file = Vec::new();
+    assert_eq!(blockchain.export_chain(&mut file).unwrap(), 2);
+
+    // The node of the same spec already has the genesis block, only the next ones are imported
+    let mut spec_blockchain =
+        common::create_blockchain(&[&forger], &[], ConsensusParams::default());
+    assert_eq!(spec_blockchain.index, 1);
+    assert_eq!(
+        spec_blockchain.import_chain(&mut file.as_slice()).unwrap(),
+        2
+    );
+    assert_eq!(spec_blockchain.last_block_hash, blockchain.last_block_hash);
+
+    // Nodes of a different genesis can't import it
+    let mut other_blockchain =
+        common::create_blockchain(&[&Wallet::new()], &[], ConsensusParams::default());
+    assert!(matches!(
+        other_blockchain.import_chain(&mut file.as_slice()),
+        Err(BlockchainErrors::CouldntImportChain(_))
+    ));
+}
@@ -0,0 +1,165 @@
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use blockchain::{
+    BlockImport,
+    Blockchain,
+    ChainId,
+    ChainSpec,
+    Configuration,
+    GenesisAllocation,
+    GenesisStaker,
+    Key,
+    MemoryStore,
+    Metrics,
+    Transaction,
+    Wallet,
+};
+use tokio_test::block_on;
+
+fn create_spec(stakers: &[Wallet]) -> ChainSpec {
+    ChainSpec {
+        chain_name: "venus".to_string(),
+        chain_id: 1,
+        genesis_timestamp: "2021-10-01T00:00:00Z".to_string(),
+        allocations: vec![GenesisAllocation {
+            address: "some-address".to_string(),
+            ammount: 500,
+        }],
+        stakers: stakers
+            .iter()
+            .map(|wallet| GenesisStaker {
+                public_key: wallet.get_public().to_hex(),
+                ammount: 20,
+            })
+            .collect(),
+        consensus: Default::default(),
+    }
+}
+
+fn create_blockchain(chain: ChainId, chain_spec: Option<ChainSpec>) -> Blockchain {
+    let mut config = Configuration::new();
+    config.chain_name = chain.chain_name;
+    config.chain_id = chain.id;
+    if let Some(chain_spec) = chain_spec {
+        config.with_chain_spec(chain_spec);
+    }
+
+    block_on(Blockchain::with_store(
+        config,
+        Arc::new(MemoryStore::default()),
+        Arc::new(Mutex::new(Metrics::new(vec![]))),
+    ))
+}
+
+#[test]
+fn the_same_spec_creates_the_same_genesis() {
+    let stakers = vec![Wallet::new(), Wallet::new()];
+    let chain_spec = create_spec(&stakers);
+
+    let json = serde_json::to_string(&chain_spec).unwrap();
+    let parsed_spec = ChainSpec::from_json(&json).unwrap();
+    assert_eq!(parsed_spec, chain_spec);
+
+    let genesis_block = chain_spec.genesis_block().unwrap();
+    assert_eq!(
+        genesis_block.hash,
+        parsed_spec.genesis_block().unwrap().hash
+    );
+    assert!(genesis_block.verify_integrity().is_ok());
+    assert!(genesis_block.previous_hash.is_none());
+
+    // Another chain gets another genesis
+    let mut other_spec = chain_spec.clone();
+    other_spec.chain_name = "mercury".to_string();
+    assert_ne!(other_spec.genesis_block().unwrap().hash, genesis_block.hash);
+
+    // Two nodes started from the spec share the genesis
+    let blockchain = create_blockchain(chain_spec.chain(), Some(chain_spec.clone()));
+    let other_blockchain = create_blockchain(chain_spec.chain(), Some(chain_spec));
+    assert_eq!(blockchain.index, 1);
+    assert_eq!(blockchain.last_block_hash, Some(genesis_block.hash));
+    assert_eq!(blockchain.last_block_hash, other_blockchain.last_block_hash);
+}
+
+#[test]
+fn the_genesis_applies_the_allocations_and_stakes() {
+    let stakers = vec![Wallet::new(), Wallet::new()];
+    let chain_spec = create_spec(&stakers);
+    let genesis_block = chain_spec.genesis_block().unwrap();
+
+    // The genesis block is verified like any other block by nodes without the spec,
+    // they only need to know the chain name and id
+    let mut blockchain = create_blockchain(chain_spec.chain(), None);
+    assert!(matches!(
+        blockchain.import_block(&genesis_block),
+        Ok(BlockImport::Extended)
+    ));
+
+    let state = &blockchain.state;
+    assert_eq!(state.addresses.get("some-address").unwrap().ammount, 500);
+    assert_eq!(state.last_staking_addresses.len(), 2);
+    for staker in &stakers {
+        assert_eq!(
+            state
+                .addresses
+                .get(&staker.get_public().hash_it())
+                .unwrap()
+                .ammount,
+            0
+        );
+    }
+
+    // The genesis wallet keeps nothing
+    let genesis_address = chain_spec.genesis_wallet().get_public().hash_it();
+    assert_eq!(state.addresses.get(&genesis_address).unwrap().ammount, 0);
+
+    // Unsigned stakes are only allowed in the genesis block
+    let stake = genesis_block
+        .transactions
+        .iter()
+        .find(|tx| matches!(tx, Transaction::STAKE { .. }))
+        .unwrap();
+    let chain = chain_spec.chain();
+    assert!(genesis_block.verify_transaction(stake, &chain));
+    assert!(stake.verify_genesis_with_version(genesis_block.hash.version, &chain));
+
+    let mut forged_stake = stake.clone();
+    if let Transaction::STAKE {
+        author_public_key, ..
+    } = &mut forged_stake
+    {
+        *author_public_key = Wallet::new().get_public();
+    }
+    assert!(!forged_stake.verify_genesis_with_version(genesis_block.hash.version, &chain));
+}
+
+#[test]
+fn invalid_specs() {
+    let chain_spec = create_spec(&[Wallet::new()]);
+
+    let mut no_stakers = chain_spec.clone();
+    no_stakers.stakers.clear();
+    assert!(no_stakers.genesis_block().is_err());
+
+    let mut bad_timestamp = chain_spec.clone();
+    bad_timestamp.genesis_timestamp = "yesterday".to_string();
+    assert!(bad_timestamp.genesis_block().is_err());
+
+    let mut bad_key = chain_spec.clone();
+    bad_key.stakers[0].public_key = "zz".to_string();
+    assert!(bad_key.genesis_block().is_err());
+
+    let mut overflow = chain_spec;
+    overflow.allocations[0].ammount = u64::MAX;
+    assert!(overflow.genesis_block().is_err());
+
+    assert!(ChainSpec::from_json("{}").is_err());
+
+    // Keys are written in hexadecimal
+    let key = Wallet::new().get_public();
+    assert_eq!(Key::from_hex(&key.to_hex()), Some(key));
+    assert!(Key::from_hex("abc").is_none());
+}
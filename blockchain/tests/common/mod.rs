@@ -0,0 +1,127 @@
+/*
+ * Helpers shared by the integration tests, each test only uses some of them
+ */
+#![allow(dead_code)]
+
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use blockchain::{
+    Block,
+    BlockBuilder,
+    BlockHash,
+    Blockchain,
+    ChainId,
+    ChainSpec,
+    Chainstate,
+    Configuration,
+    ConsensusParams,
+    GenesisAllocation,
+    GenesisStaker,
+    MemoryStore,
+    Metrics,
+    Transaction,
+    TransactionBuilder,
+    TransactionType,
+    Wallet,
+};
+use chrono::Utc;
+use tokio_test::block_on;
+
+/*
+ * Chain of the default configuration, the one the test blockchains run unless they are launched from a spec
+ */
+pub fn chain() -> ChainId {
+    Configuration::new().chain()
+}
+
+/*
+ * Chain launched from a spec whose genesis gives 100 to each funded wallet and a stake of 1 to each staker
+ */
+pub fn create_blockchain(
+    stakers: &[&Wallet],
+    funded: &[&Wallet],
+    consensus: ConsensusParams,
+) -> Blockchain {
+    let chain_spec = ChainSpec {
+        chain_name: chain().chain_name,
+        chain_id: chain().id,
+        genesis_timestamp: "2021-10-01T00:00:00Z".to_string(),
+        allocations: funded
+            .iter()
+            .map(|wallet| GenesisAllocation {
+                address: wallet.get_public().hash_it(),
+                ammount: 100,
+            })
+            .collect(),
+        stakers: stakers
+            .iter()
+            .map(|staker| GenesisStaker {
+                public_key: staker.get_public().to_hex(),
+                ammount: 1,
+            })
+            .collect(),
+        consensus,
+    };
+
+    let mut config = Configuration::new();
+    config.with_chain_spec(chain_spec);
+
+    block_on(Blockchain::with_store(
+        config,
+        Arc::new(MemoryStore::default()),
+        Arc::new(Mutex::new(Metrics::new(vec![]))),
+    ))
+}
+
+/*
+ * Coinbase that gives the ammount to the wallet
+ */
+pub fn reward(wallet: &Wallet, ammount: u64) -> Transaction {
+    TransactionBuilder::new()
+        .to_address(&wallet.get_public().hash_it())
+        .ammount(ammount)
+        .is_type(TransactionType::COINBASE)
+        .build()
+}
+
+/*
+ * Block on top of the given one, with a reward for the forger followed by the given transactions
+ */
+pub fn forge_block(
+    previous_hash: Option<&BlockHash>,
+    forger: &mut Wallet,
+    reward: u64,
+    transactions: &[Transaction],
+) -> Block {
+    let reward = TransactionBuilder::new()
+        .to_address(&forger.get_public().hash_it())
+        .ammount(reward)
+        .is_type(TransactionType::COINBASE)
+        .with_wallet(forger)
+        .build();
+
+    let mut block_transactions = vec![reward];
+    block_transactions.extend_from_slice(transactions);
+
+    let mut builder = BlockBuilder::new();
+    builder
+        .transactions(&block_transactions)
+        .timestamp(Utc::now())
+        .key(&forger.get_public());
+
+    if let Some(previous_hash) = previous_hash {
+        builder.previous_hash(previous_hash);
+    }
+
+    builder.hash_it().sign_with(forger, &chain()).build()
+}
+
+/*
+ * Serialized chainstate, so two chainstates can be compared
+ */
+pub fn dump(chainstate: &Chainstate) -> serde_json::Value {
+    serde_json::to_value(chainstate.snapshot(0)).unwrap()
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use std::sync::Arc;
+
+use blockchain::{
+    BlockBuilder,
+    ChainSpec,
+    Chainstate,
+    Configuration,
+    ConsensusParams,
+    ConsensusUpgrade,
+    MemoryStore,
+    TransactionBuilder,
+    TransactionType,
+    Wallet,
+};
+use chrono::Utc;
+use common::chain;
+
+#[test]
+fn upgrades_apply_from_their_height() {
+    let params = ConsensusParams {
+        upgrades: vec![
+            ConsensusUpgrade {
+                height: 20,
+                block_reward: Some(5),
+                ..Default::default()
+            },
+            ConsensusUpgrade {
+                height: 10,
+                block_reward: Some(8),
+                max_block_transactions: Some(1000),
+                weighted_election: Some(true),
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+
+    assert_eq!(params.at_height(9), ConsensusParams::default());
+
+    let upgraded = params.at_height(10);
+    assert_eq!(upgraded.block_reward, 8);
+    assert_eq!(upgraded.max_block_transactions, 1000);
+    assert_eq!(upgraded.staking_window, 100);
+    assert!(upgraded.weighted_election);
+    assert!(upgraded.upgrades.is_empty());
+
+    // Later upgrades only replace what they set
+    let upgraded = params.at_height(25);
+    assert_eq!(upgraded.block_reward, 5);
+    assert_eq!(upgraded.max_block_transactions, 1000);
+}
+
+#[test]
+fn chain_specs_set_the_parameters() {
+    let staker = Wallet::new();
+
+    let chain_spec = ChainSpec::from_json(&format!(
+        r#"{{
+            "chain_name": "venus",
+            "genesis_timestamp": "2021-10-01T00:00:00Z",
+            "stakers": [{{ "public_key": "{}", "ammount": 1 }}],
+            "consensus": {{
+                "block_reward": 20,
+                "upgrades": [{{ "height": 100, "block_reward": 15 }}]
+            }}
+        }}"#,
+        staker.get_public().to_hex()
+    ))
+    .unwrap();
+
+    // Missing parameters keep their default
+    assert_eq!(chain_spec.consensus.block_time_max, 8000);
+    assert_eq!(chain_spec.consensus.at_height(99).block_reward, 20);
+    assert_eq!(chain_spec.consensus.at_height(100).block_reward, 15);
+
+    // The node policy isn't part of the consensus rules, the spec doesn't change it
+    let mut config = Configuration::new();
+    config.min_block_transactions = 5;
+    config.transactions_chunk_size = 10;
+    config.with_chain_spec(chain_spec);
+    assert_eq!(config.min_block_transactions, 5);
+    assert_eq!(config.transactions_chunk_size, 10);
+}
+
+#[test]
+fn the_chainstate_follows_the_parameters_of_each_height() {
+    let mut chainstate = Chainstate::with_consensus(
+        Arc::new(MemoryStore::default()),
+        ConsensusParams {
+            staking_window: 3,
+            upgrades: vec![ConsensusUpgrade {
+                height: 2,
+                staking_window: Some(5),
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    );
+
+    let mut wallet = Wallet::new();
+    let chain = chain();
+
+    let create_block = |wallet: &mut Wallet, stakes: u64| {
+        let mut transactions = vec![TransactionBuilder::new()
+            .to_address(&wallet.get_public().hash_it())
+            .ammount(100 + stakes)
+            .is_type(TransactionType::COINBASE)
+            .with_wallet(wallet)
+            .build()];
+
+        for _ in 0..stakes {
+            transactions.push(
+                TransactionBuilder::new()
+                    .ammount(1)
+                    .is_type(TransactionType::STAKE)
+                    .chain(&chain)
+                    .with_wallet(wallet)
+                    .build(),
+            );
+        }
+
+        BlockBuilder::new()
+            .transactions(&transactions)
+            .timestamp(Utc::now())
+            .key(&wallet.get_public())
+            .hash_it()
+            .sign_with(wallet, &chain)
+            .build()
+    };
+
+    // Only 2 stakes are kept in the first block
+    chainstate.apply_block(&create_block(&mut wallet, 4));
+    assert_eq!(chainstate.height, 1);
+    assert_eq!(chainstate.last_staking_addresses.len(), 2);
+
+    // The window grows from the second block on
+    let undo = chainstate.apply_block(&create_block(&mut wallet, 4));
+    assert_eq!(chainstate.last_staking_addresses.len(), 4);
+
+    chainstate.revert_block(undo);
+    assert_eq!(chainstate.height, 1);
+    assert_eq!(chainstate.last_staking_addresses.len(), 2);
+
+    // Recent forgers follow the parameters too
+    chainstate.consensus.recent_forgers = 1;
+    chainstate.add_recent_forger("a");
+    chainstate.add_recent_forger("b");
+    assert!(!chainstate.has_recent_forger("a"));
+    assert!(chainstate.has_recent_forger("b"));
+}
@@ -0,0 +1,223 @@
+mod common;
+
+use blockchain::{
+    BlockBuilder,
+    BlockHash,
+    Encode,
+    Encoder,
+    Key,
+    Transaction,
+    TransactionBuilder,
+    TransactionType,
+    Wallet,
+    BLOCK_TAG,
+    HASH_VERSION,
+    UNDELEGATION_HASH_VERSION,
+};
+use chrono::{
+    Duration,
+    Utc,
+};
+use common::chain;
+use crypto::{
+    digest::Digest,
+    sha3::{
+        Sha3,
+        Sha3Mode,
+    },
+};
+
+fn keccak(bytes: &[u8]) -> String {
+    let mut hasher = Sha3::new(Sha3Mode::Keccak256);
+    hasher.input(bytes);
+    hasher.result_str()
+}
+
+#[test]
+fn encoding_layout() {
+    let mut encoder = Encoder::new();
+    encoder
+        .u8(7)
+        .u64(258)
+        .str("ab")
+        .option(Some(&"c"), |encoder, value| {
+            encoder.str(value);
+        })
+        .option(None::<&&str>, |encoder, value| {
+            encoder.str(value);
+        });
+
+    assert_eq!(
+        encoder.into_bytes(),
+        vec![
+            7, // u8
+            0, 0, 0, 0, 0, 0, 1, 2, // u64
+            0, 0, 0, 2, b'a', b'b', // string
+            1, 0, 0, 0, 1, b'c', // present option
+            0,    // missing option
+        ]
+    );
+}
+
+#[test]
+fn transaction_hashes_are_reproducible() {
+    let coinbase = Transaction::COINBASE {
+        to_address: "address".to_string(),
+        ammount: 10,
+        hash: String::new(),
+    };
+
+    // Hash version, coinbase tag, address and ammount
+    let mut expected = vec![HASH_VERSION, 1, 0, 0, 0, 7];
+    expected.extend_from_slice(b"address");
+    expected.extend_from_slice(&10_u64.to_be_bytes());
+    assert_eq!(coinbase.hash_it(), keccak(&expected));
+
+    // Fields can't be shifted into each other anymore
+    let movement = |from: &str, to: &str, ammount: u64, history: u64| Transaction::MOVEMENT {
+        signature: Key(Vec::new()),
+        from_address: from.to_string(),
+        to_address: to.to_string(),
+        ammount,
+        hash: String::new(),
+        history,
+        fee: 0,
+    };
+    assert_ne!(
+        movement("a", "b1", 1, 1).hash_it(),
+        movement("ab", "1", 1, 1).hash_it()
+    );
+    assert_ne!(
+        movement("a", "b", 11, 1).hash_it(),
+        movement("a", "b", 1, 11).hash_it()
+    );
+    assert_eq!(
+        movement("a", "b", 11, 1).hash_with_version(2),
+        movement("a", "b", 1, 11).hash_with_version(2)
+    );
+}
+
+#[test]
+fn blocks_use_the_canonical_encoding() {
+    let mut wallet = Wallet::new();
+    let receiver = Wallet::new();
+    let chain = chain();
+
+    let transactions = vec![
+        TransactionBuilder::new()
+            .to_address(&wallet.get_public().hash_it())
+            .ammount(10)
+            .is_type(TransactionType::COINBASE)
+            .with_wallet(&mut wallet)
+            .build(),
+        TransactionBuilder::new()
+            .to_address(&receiver.get_public().hash_it())
+            .ammount(3)
+            .is_type(TransactionType::MOVEMENT)
+            .chain(&chain)
+            .with_wallet(&mut wallet)
+            .build(),
+    ];
+    assert!(transactions.iter().all(|tx| tx.verify(&chain)));
+
+    let block = BlockBuilder::new()
+        .transactions(&transactions)
+        .timestamp(Utc::now())
+        .key(&wallet.get_public())
+        .hash_it()
+        .sign_with(&wallet, &chain)
+        .build();
+
+    assert_eq!(block.hash.version, HASH_VERSION);
+    assert!(block.verify_integrity().is_ok());
+    assert_eq!(block.to_bytes(), block.clone().to_bytes());
+
+    // Blocks of the previous version keep being valid with the previous transaction hashes
+    let mut legacy_transactions = transactions.clone();
+    for tx in &mut legacy_transactions {
+        let legacy_hash = tx.hash_with_version(2);
+        match tx {
+            Transaction::MOVEMENT { hash, .. }
+            | Transaction::COINBASE { hash, .. }
+            | Transaction::STAKE { hash, .. }
+            | Transaction::UNSTAKE { hash, .. }
+            | Transaction::DELEGATE { hash, .. }
+            | Transaction::UNDELEGATE { hash, .. }
+            | Transaction::BATCH { hash, .. } => *hash = legacy_hash,
+        }
+    }
+
+    let mut legacy_block = block.clone();
+    legacy_block.transactions = legacy_transactions;
+    legacy_block.hash = BlockHash::with_version(
+        2,
+        &legacy_block.transactions,
+        block.timestamp,
+        None,
+        block.key.clone(),
+    );
+    assert!(legacy_block.verify_integrity().is_ok());
+
+    // But their transactions must be hashed the way that version did
+    let mut mixed_block = legacy_block.clone();
+    mixed_block.transactions = transactions;
+    mixed_block.hash = BlockHash::with_version(
+        2,
+        &mixed_block.transactions,
+        block.timestamp,
+        None,
+        block.key.clone(),
+    );
+    assert!(mixed_block.verify_integrity().is_err());
+
+    // Unknown versions are rejected
+    let mut future_block = block;
+    future_block.hash.version = HASH_VERSION + 1;
+    assert!(future_block.verify_integrity().is_err());
+}
+
+#[test]
+fn block_timestamps_are_encoded_by_version() {
+    let wallet = Wallet::new();
+    let block = BlockBuilder::new()
+        .transactions(&[])
+        .timestamp(Utc::now())
+        .key(&wallet.get_public())
+        .hash_it()
+        .sign_with(&wallet, &chain())
+        .build();
+
+    // The timestamp is encoded as milliseconds since the epoch
+    let mut encoder = Encoder::new();
+    encoder
+        .u8(HASH_VERSION)
+        .u8(BLOCK_TAG)
+        .str(&block.merkle_root())
+        .i64(block.timestamp.timestamp_millis())
+        .bytes(&block.key.0)
+        .u8(0);
+    assert_eq!(block.hash.hash, keccak(&encoder.into_bytes()));
+
+    // A fraction of a millisecond wouldn't be committed to by the hash
+    let mut precise_block = block.clone();
+    precise_block.timestamp = block.timestamp + Duration::nanoseconds(1);
+    assert!(precise_block.verify_integrity().is_err());
+
+    // Older versions keep encoding it's text form
+    let older_hash = BlockHash::with_version(
+        UNDELEGATION_HASH_VERSION,
+        &[],
+        block.timestamp,
+        None,
+        block.key.clone(),
+    );
+    let mut encoder = Encoder::new();
+    encoder
+        .u8(UNDELEGATION_HASH_VERSION)
+        .u8(BLOCK_TAG)
+        .str(&block.merkle_root())
+        .str(&block.timestamp.to_string())
+        .bytes(&block.key.0)
+        .u8(0);
+    assert_eq!(older_hash.hash, keccak(&encoder.into_bytes()));
+}
@@ -0,0 +1,226 @@
+mod common;
+
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use blockchain::{
+    BlockImport,
+    BlockStore,
+    BlockValidationError,
+    Blockchain,
+    BlockchainErrors,
+    Configuration,
+    MemoryStore,
+    Metrics,
+    Transaction,
+    TransactionBuilder,
+    TransactionType,
+    Wallet,
+};
+use common::{
+    chain,
+    forge_block,
+};
+use tokio_test::block_on;
+
+fn create_blockchain(store: Arc<dyn BlockStore>) -> Blockchain {
+    let mut config = Configuration::new();
+    config.snapshot_interval = 2;
+    // The forgers reward themselves with different ammounts so their coinbases don't collide
+    config.consensus.block_reward = 100;
+
+    block_on(Blockchain::with_store(
+        config,
+        store,
+        Arc::new(Mutex::new(Metrics::new(vec![]))),
+    ))
+}
+
+fn pay(from: &mut Wallet, to: &Wallet, ammount: u64) -> Transaction {
+    TransactionBuilder::new()
+        .to_address(&to.get_public().hash_it())
+        .ammount(ammount)
+        .is_type(TransactionType::MOVEMENT)
+        .chain(&chain())
+        .with_wallet(from)
+        .build()
+}
+
+#[test]
+fn reorganize_to_longer_branch() {
+    let store: Arc<dyn BlockStore> = Arc::new(MemoryStore::default());
+    let mut blockchain = create_blockchain(store.clone());
+
+    let mut forger_a = Wallet::new();
+    let mut forger_b = Wallet::new();
+    let mut forger_c = Wallet::new();
+
+    // Main chain: genesis (A) -> block_1 (B) -> block_2 (A)
+    let genesis = forge_block(None, &mut forger_a, 10, &[]);
+    let block_1 = forge_block(Some(&genesis.hash), &mut forger_b, 11, &[]);
+
+    let payment = pay(&mut forger_a, &forger_b, 4);
+    let block_2 = forge_block(
+        Some(&block_1.hash),
+        &mut forger_a,
+        12,
+        std::slice::from_ref(&payment),
+    );
+
+    for block in [&genesis, &block_1, &block_2] {
+        assert!(matches!(
+            blockchain.import_block(block).unwrap(),
+            BlockImport::Extended
+        ));
+    }
+    assert!(matches!(
+        blockchain.import_block(&block_2).unwrap(),
+        BlockImport::AlreadyKnown
+    ));
+
+    // Competing branch: block_1 -> side_2 (C), the main chain is kept on a tie
+    let side_2 = forge_block(Some(&block_1.hash), &mut forger_c, 13, &[]);
+    assert!(matches!(
+        blockchain.import_block(&side_2).unwrap(),
+        BlockImport::SideBranch
+    ));
+    assert_eq!(blockchain.last_block_hash, Some(block_2.hash.clone()));
+    assert_eq!(blockchain.forks.len(), 1);
+
+    // The competing branch gets longer: side_2 -> side_3 (A)
+    let side_3 = forge_block(Some(&side_2.hash), &mut forger_a, 14, &[]);
+    match blockchain.import_block(&side_3).unwrap() {
+        BlockImport::Reorganized {
+            ancestor_height,
+            applied_blocks,
+            abandoned_transactions,
+        } => {
+            assert_eq!(ancestor_height, 2);
+            assert_eq!(applied_blocks.len(), 2);
+            assert_eq!(abandoned_transactions.len(), 1);
+            assert_eq!(abandoned_transactions[0].get_hash(), payment.get_hash());
+        }
+        import => panic!("Expected a reorganization, got {:?}", import),
+    }
+
+    assert_eq!(blockchain.index, 4);
+    assert_eq!(blockchain.last_block_hash, Some(side_3.hash.clone()));
+    assert_eq!(store.get_block_by_height(3).unwrap().hash, side_2.hash);
+
+    // The abandoned block is now a side block and it's payment isn't confirmed anymore
+    assert!(blockchain.forks.contains(&block_2.hash.hash));
+    assert!(blockchain
+        .get_transaction(&payment.get_hash())
+        .unwrap()
+        .is_none());
+
+    let balance = |blockchain: &Blockchain, wallet: &Wallet| {
+        blockchain
+            .state
+            .get_address_ammount(wallet.get_public().hash_it())
+    };
+    assert_eq!(balance(&blockchain, &forger_a), 10 + 14);
+    assert_eq!(balance(&blockchain, &forger_b), 11);
+    assert_eq!(balance(&blockchain, &forger_c), 13);
+
+    // The chainstate after a restart matches the reorganized one
+    let restarted_blockchain = create_blockchain(store);
+    assert_eq!(restarted_blockchain.index, 4);
+    assert_eq!(balance(&restarted_blockchain, &forger_a), 10 + 14);
+    assert_eq!(balance(&restarted_blockchain, &forger_c), 13);
+}
+
+#[test]
+fn invalid_branches_are_discarded() {
+    let mut blockchain = create_blockchain(Arc::new(MemoryStore::default()));
+
+    let mut forger_a = Wallet::new();
+    let mut forger_b = Wallet::new();
+    let mut forger_c = Wallet::new();
+
+    let genesis = forge_block(None, &mut forger_a, 10, &[]);
+    let block_1 = forge_block(Some(&genesis.hash), &mut forger_b, 11, &[]);
+
+    blockchain.import_block(&genesis).unwrap();
+    blockchain.import_block(&block_1).unwrap();
+
+    // Forger C spends more than it has
+    let side_1 = forge_block(Some(&genesis.hash), &mut forger_c, 12, &[]);
+    let overspend = pay(&mut forger_c, &forger_a, 100);
+    let side_2 = forge_block(Some(&side_1.hash), &mut forger_b, 13, &[overspend]);
+
+    assert!(matches!(
+        blockchain.import_block(&side_1).unwrap(),
+        BlockImport::SideBranch
+    ));
+    assert!(matches!(
+        blockchain.import_block(&side_2),
+        Err(BlockchainErrors::InvalidBlock(
+            BlockValidationError::InvalidTransaction(_)
+        ))
+    ));
+
+    assert_eq!(blockchain.index, 2);
+    assert_eq!(blockchain.last_block_hash, Some(block_1.hash.clone()));
+    assert!(!blockchain.forks.contains(&side_2.hash.hash));
+    assert!(blockchain.forks.contains(&side_1.hash.hash));
+
+    // Blocks whose previous block is unknown can't be placed anywhere
+    let orphan_parent = forge_block(Some(&block_1.hash), &mut forger_c, 14, &[]);
+    let orphan = forge_block(Some(&orphan_parent.hash), &mut forger_a, 15, &[]);
+    match blockchain.import_block(&orphan) {
+        Err(BlockchainErrors::UnknownPreviousBlock(hash)) => {
+            assert_eq!(hash, orphan_parent.hash.hash)
+        }
+        import => panic!("Expected an unknown previous block, got {:?}", import),
+    }
+
+    // Only the first block has no previous block
+    let second_genesis = forge_block(None, &mut forger_c, 16, &[]);
+    assert!(matches!(
+        blockchain.import_block(&second_genesis),
+        Err(BlockchainErrors::InvalidPreviousHash(_))
+    ));
+    assert!(matches!(
+        blockchain.add_block(&second_genesis),
+        Err(BlockchainErrors::InvalidPreviousHash(_))
+    ));
+    assert_eq!(blockchain.index, 2);
+}
+
+#[test]
+fn side_blocks_are_capped() {
+    let mut blockchain = create_blockchain(Arc::new(MemoryStore::default()));
+    blockchain.config.lock().unwrap().max_fork_blocks = 2;
+
+    let mut forger_a = Wallet::new();
+    let mut forger_b = Wallet::new();
+    let mut forger_c = Wallet::new();
+
+    let genesis = forge_block(None, &mut forger_a, 10, &[]);
+    let block_1 = forge_block(Some(&genesis.hash), &mut forger_b, 11, &[]);
+    let block_2 = forge_block(Some(&block_1.hash), &mut forger_a, 12, &[]);
+
+    for block in [&genesis, &block_1, &block_2] {
+        blockchain.import_block(block).unwrap();
+    }
+
+    let side_1 = forge_block(Some(&genesis.hash), &mut forger_c, 13, &[]);
+    let side_2 = forge_block(Some(&block_1.hash), &mut forger_c, 14, &[]);
+    let other_side_2 = forge_block(Some(&block_1.hash), &mut forger_b, 15, &[]);
+
+    for block in [&side_1, &side_2, &other_side_2] {
+        assert!(matches!(
+            blockchain.import_block(block).unwrap(),
+            BlockImport::SideBranch
+        ));
+    }
+
+    // The oldest side block is forgotten first
+    assert_eq!(blockchain.forks.len(), 2);
+    assert!(!blockchain.forks.contains(&side_1.hash.hash));
+    assert!(blockchain.forks.contains(&side_2.hash.hash));
+    assert!(blockchain.forks.contains(&other_side_2.hash.hash));
+}
@@ -9,6 +9,7 @@ use blockchain::{
     Configuration,
     Metrics,
     PublicAddress,
+    StorageBackend,
     Wallet,
 };
 use chrono::Utc;
@@ -17,8 +18,13 @@ use tokio_test::block_on;
 #[test]
 
 fn test() {
+    let mut config = Configuration::new();
+    config.storage = StorageBackend::Memory;
+
+    let chain = config.chain();
+
     let mut blockchain = block_on(Blockchain::new(
-        Configuration::new(),
+        config,
         Arc::new(Mutex::new(Metrics::new(vec![]))),
     ));
 
@@ -31,11 +37,11 @@ fn test() {
         blockchain
             .add_block(
                 &BlockBuilder::new()
-                    .transactions(&vec![])
+                    .transactions(&[])
                     .timestamp(Utc::now())
                     .key(&public_key)
                     .hash_it()
-                    .sign_with(&account_a)
+                    .sign_with(&account_a, &chain)
                     .build(),
             )
             .unwrap();
@@ -45,34 +51,34 @@ fn test() {
         blockchain
             .add_block(
                 &BlockBuilder::new()
-                    .transactions(&vec![])
+                    .transactions(&[])
                     .timestamp(Utc::now())
                     .previous_hash(&blockchain.last_block_hash.clone().unwrap())
                     .key(&public_key)
                     .hash_it()
-                    .sign_with(&account_a)
+                    .sign_with(&account_a, &chain)
                     .build(),
             )
             .unwrap();
     }
 
     let block_3 = BlockBuilder::new()
-        .transactions(&vec![])
+        .transactions(&[])
         .timestamp(Utc::now())
         .key(&public_key)
         .hash_it()
-        .sign_with(&account_a)
+        .sign_with(&account_a, &chain)
         .build();
 
     // Verifying the signing on the block should fail since this account hasn't signed it
     let account_b = Wallet::new();
 
-    assert!(block_3.verify_sign_with(&account_a));
-    assert!(!block_3.verify_sign_with(&account_b));
+    assert!(block_3.verify_sign_with(&account_a, &chain));
+    assert!(!block_3.verify_sign_with(&account_b, &chain));
 
     assert!(blockchain.verify_integrity().is_ok());
 
     let public_account_a = PublicAddress::from(&public_key);
 
-    assert!(block_3.verify_sign_with(&public_account_a));
+    assert!(block_3.verify_sign_with(&public_account_a, &chain));
 }
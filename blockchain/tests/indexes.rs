@@ -0,0 +1,149 @@
+mod common;
+
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use blockchain::{
+    Blockchain,
+    Configuration,
+    MemoryStore,
+    Metrics,
+    Transaction,
+    TransactionBuilder,
+    TransactionDirection,
+    TransactionType,
+    Wallet,
+};
+use common::{
+    chain,
+    forge_block,
+};
+use tokio_test::block_on;
+
+fn create_blockchain() -> Blockchain {
+    block_on(Blockchain::with_store(
+        Configuration::new(),
+        Arc::new(MemoryStore::default()),
+        Arc::new(Mutex::new(Metrics::new(vec![]))),
+    ))
+}
+
+#[test]
+fn transactions_index() {
+    let mut blockchain = create_blockchain();
+    let mut forger_a = Wallet::new();
+    let mut forger_b = Wallet::new();
+
+    blockchain
+        .add_block(&forge_block(
+            blockchain.last_block_hash.as_ref(),
+            &mut forger_a,
+            10,
+            &[],
+        ))
+        .unwrap();
+
+    let payment = TransactionBuilder::new()
+        .to_address(&forger_b.get_public().hash_it())
+        .ammount(4)
+        .is_type(TransactionType::MOVEMENT)
+        .chain(&chain())
+        .with_wallet(&mut forger_a)
+        .build();
+
+    blockchain
+        .add_block(&forge_block(
+            blockchain.last_block_hash.as_ref(),
+            &mut forger_b,
+            10,
+            std::slice::from_ref(&payment),
+        ))
+        .unwrap();
+
+    let (transaction, location) = blockchain
+        .get_transaction(&payment.get_hash())
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(transaction.get_hash(), payment.get_hash());
+    assert_eq!(location.height, 2);
+    assert_eq!(location.position, 1);
+    assert_eq!(
+        &location.block_hash,
+        blockchain.last_block_hash.as_ref().unwrap()
+    );
+
+    assert!(blockchain.get_transaction("unknown").unwrap().is_none());
+}
+
+#[test]
+fn address_history() {
+    let mut blockchain = create_blockchain();
+    let mut forger_a = Wallet::new();
+    let mut forger_b = Wallet::new();
+    let address_a = forger_a.get_public().hash_it();
+
+    blockchain
+        .add_block(&forge_block(
+            blockchain.last_block_hash.as_ref(),
+            &mut forger_a,
+            10,
+            &[],
+        ))
+        .unwrap();
+
+    let payments = (1..=3)
+        .map(|ammount| {
+            TransactionBuilder::new()
+                .to_address(&forger_b.get_public().hash_it())
+                .ammount(ammount)
+                .is_type(TransactionType::MOVEMENT)
+                .chain(&chain())
+                .with_wallet(&mut forger_a)
+                .build()
+        })
+        .collect::<Vec<Transaction>>();
+
+    blockchain
+        .add_block(&forge_block(
+            blockchain.last_block_hash.as_ref(),
+            &mut forger_b,
+            10,
+            &payments,
+        ))
+        .unwrap();
+
+    let history = blockchain.get_address_history(&address_a, 0, 10).unwrap();
+    assert_eq!(history.len(), 4);
+
+    // The first entry is the reward of the first block
+    assert_eq!(history[0].1.direction, TransactionDirection::Incoming);
+    assert_eq!(history[0].1.location.height, 1);
+
+    for ((transaction, entry), payment) in history[1..].iter().zip(payments.iter()) {
+        assert_eq!(transaction.get_hash(), payment.get_hash());
+        assert_eq!(entry.direction, TransactionDirection::Outgoing);
+        assert_eq!(entry.location.height, 2);
+    }
+
+    // Pages are capped, an unbounded limit doesn't reach the store as is
+    let history = blockchain
+        .get_address_history(&address_a, 0, usize::MAX)
+        .unwrap();
+    assert_eq!(history.len(), 4);
+
+    // Paging
+    let page = blockchain.get_address_history(&address_a, 2, 1).unwrap();
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].0.get_hash(), payments[1].get_hash());
+
+    let history_b = blockchain
+        .get_address_history(&forger_b.get_public().hash_it(), 0, 10)
+        .unwrap();
+    assert_eq!(history_b.len(), 4);
+    assert!(history_b
+        .iter()
+        .all(|(_, entry)| entry.direction == TransactionDirection::Incoming));
+}
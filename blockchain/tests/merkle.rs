@@ -0,0 +1,150 @@
+mod common;
+
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use blockchain::{
+    merkle_root,
+    Block,
+    BlockHash,
+    Blockchain,
+    Configuration,
+    MemoryStore,
+    MerkleProof,
+    Metrics,
+    Transaction,
+    TransactionBuilder,
+    TransactionType,
+    Wallet,
+};
+use common::{
+    chain,
+    forge_block,
+};
+use tokio_test::block_on;
+
+/*
+ * Create a block with a reward for the forger and `payments` movements
+ */
+fn create_block(forger: &mut Wallet, payments: u64) -> Block {
+    let receiver = Wallet::new();
+
+    let payments = (0..payments)
+        .map(|ammount| {
+            TransactionBuilder::new()
+                .to_address(&receiver.get_public().hash_it())
+                .ammount(ammount)
+                .is_type(TransactionType::MOVEMENT)
+                .chain(&chain())
+                .with_wallet(forger)
+                .build()
+        })
+        .collect::<Vec<Transaction>>();
+
+    forge_block(None, forger, 10, &payments)
+}
+
+#[test]
+fn merkle_proofs() {
+    for size in 1..10 {
+        let hashes = (0..size)
+            .map(|i| format!("transaction-{}", i))
+            .collect::<Vec<String>>();
+        let root = merkle_root(&hashes);
+
+        for index in 0..size {
+            let proof = MerkleProof::generate(&hashes, index).unwrap();
+            assert!(proof.verify(&root));
+
+            // The proof doesn't work for other transactions
+            let mut forged_proof = proof.clone();
+            forged_proof.transaction_hash = "another-transaction".to_string();
+            assert!(!forged_proof.verify(&root));
+        }
+
+        assert!(MerkleProof::generate(&hashes, size).is_none());
+    }
+
+    // The order of the transactions is committed
+    let hashes = vec!["a".to_string(), "b".to_string()];
+    let swapped_hashes = vec!["b".to_string(), "a".to_string()];
+    assert_ne!(merkle_root(&hashes), merkle_root(&swapped_hashes));
+}
+
+#[test]
+fn blocks_commit_to_the_merkle_root() {
+    let mut forger = Wallet::new();
+    let block = create_block(&mut forger, 4);
+
+    assert!(block.verify_integrity().is_ok());
+    assert_eq!(
+        block.hash,
+        BlockHash::from_merkle_root(
+            &block.merkle_root(),
+            block.timestamp,
+            None,
+            block.key.clone()
+        )
+    );
+
+    for tx in &block.transactions {
+        let proof = block.merkle_proof(&tx.get_hash()).unwrap();
+        assert!(proof.verify(&block.merkle_root()));
+    }
+
+    // Changing a transaction breaks the block hash
+    let mut altered_block = block.clone();
+    if let Transaction::MOVEMENT { ammount, .. } = &mut altered_block.transactions[1] {
+        *ammount += 1;
+    }
+    assert!(altered_block.verify_integrity().is_err());
+
+    // Blocks hashed the old way are still valid, but they don't have proofs
+    let mut legacy_block = block.clone();
+    legacy_block.hash = BlockHash::legacy(
+        &block.transactions,
+        block.timestamp,
+        None,
+        block.key.clone(),
+    );
+    assert!(legacy_block.verify_integrity().is_ok());
+    assert!(legacy_block
+        .merkle_proof(&block.transactions[0].get_hash())
+        .is_none());
+}
+
+#[test]
+fn transaction_proofs_from_the_chain() {
+    let mut blockchain = block_on(Blockchain::with_store(
+        Configuration::new(),
+        Arc::new(MemoryStore::default()),
+        Arc::new(Mutex::new(Metrics::new(vec![]))),
+    ));
+
+    let mut forger = Wallet::new();
+    let block = create_block(&mut forger, 2);
+    blockchain.add_block(&block).unwrap();
+
+    let transaction = &block.transactions[2];
+    let (proof, location) = blockchain
+        .get_transaction_proof(&transaction.get_hash())
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(location.height, 1);
+    assert_eq!(location.block_hash, block.hash);
+    assert_eq!(proof.index, 2);
+    assert!(proof.verify(&block.merkle_root()));
+
+    // A light client only needs the header to check the proof
+    let header = blockchain.get_header_by_height(1).unwrap();
+    assert!(header.verify(None, &chain()).is_ok());
+    assert!(proof.verify(&header.merkle_root));
+
+    assert!(blockchain
+        .get_transaction_proof("unknown")
+        .unwrap()
+        .is_none());
+}
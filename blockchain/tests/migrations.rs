@@ -0,0 +1,111 @@
+mod common;
+
+use std::sync::Arc;
+
+use blockchain::{
+    migrate,
+    Block,
+    BlockBuilder,
+    BlockStore,
+    BlockchainErrors,
+    DiskStore,
+    MemoryStore,
+    TransactionBuilder,
+    TransactionType,
+    Wallet,
+    SCHEMA_VERSION,
+};
+use chrono::Utc;
+use common::chain;
+
+/*
+ * Save some blocks the way a node without schema versions did
+ */
+fn fill_unversioned_store(store: &dyn BlockStore) -> Vec<Block> {
+    let mut wallet = Wallet::new();
+    let mut blocks: Vec<Block> = Vec::new();
+
+    for i in 0..3 {
+        let reward = TransactionBuilder::new()
+            .to_address(&wallet.get_public().hash_it())
+            .ammount(10 + i as u64)
+            .is_type(TransactionType::COINBASE)
+            .with_wallet(&mut wallet)
+            .build();
+
+        let mut builder = BlockBuilder::new();
+        builder
+            .transactions(&[reward])
+            .timestamp(Utc::now())
+            .key(&wallet.get_public());
+
+        if let Some(previous_block) = blocks.last() {
+            builder.previous_hash(&previous_block.hash);
+        }
+
+        let mut block = builder.hash_it().sign_with(&wallet, &chain()).build();
+        block.index = Some(i + 1);
+        store.add_block(&block).unwrap();
+        blocks.push(block);
+    }
+
+    blocks
+}
+
+fn check_migration(store: Arc<dyn BlockStore>) {
+    let blocks = fill_unversioned_store(store.as_ref());
+    assert_eq!(store.get_schema_version(), 0);
+
+    assert_eq!(migrate(store.as_ref()).unwrap(), 1);
+    assert_eq!(store.get_schema_version(), SCHEMA_VERSION);
+
+    // Blocks are indexed once, even if they were already indexed
+    let forger = blocks[0].key.hash_it();
+    assert_eq!(store.get_address_history(&forger, 0, 10).len(), 3);
+
+    let reward = &blocks[1].transactions[0];
+    let location = store.get_transaction_location(&reward.get_hash()).unwrap();
+    assert_eq!(location.height, 2);
+
+    // Every block has it's header
+    let headers = store.get_headers_range(1, 3);
+    assert_eq!(headers.len(), 3);
+    assert!(headers[2].verify(Some(&headers[1]), &chain()).is_ok());
+
+    // Nothing is left to do
+    assert_eq!(migrate(store.as_ref()).unwrap(), SCHEMA_VERSION);
+    assert_eq!(store.get_blocks().unwrap().len(), 3);
+}
+
+#[test]
+fn memory_store_migration() {
+    check_migration(Arc::new(MemoryStore::default()));
+}
+
+#[test]
+fn disk_store_migration() {
+    let path = std::env::temp_dir().join(format!("disk_store_migration_{}", std::process::id()));
+
+    check_migration(Arc::new(DiskStore::new(&path).unwrap()));
+
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[test]
+fn new_stores_use_the_current_schema() {
+    let store = MemoryStore::default();
+
+    assert_eq!(migrate(&store).unwrap(), SCHEMA_VERSION);
+    assert_eq!(store.get_schema_version(), SCHEMA_VERSION);
+}
+
+#[test]
+fn newer_schemas_are_rejected() {
+    let store = MemoryStore::default();
+    store.set_schema_version(SCHEMA_VERSION + 1).unwrap();
+
+    assert!(matches!(
+        migrate(&store),
+        Err(BlockchainErrors::UnsupportedSchemaVersion(version)) if version == SCHEMA_VERSION + 1
+    ));
+}
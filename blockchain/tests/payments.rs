@@ -0,0 +1,267 @@
+mod common;
+
+use std::sync::Arc;
+
+use blockchain::{
+    validate_block,
+    AddressHistoryEntry,
+    Block,
+    BlockBuilder,
+    BlockHash,
+    BlockImport,
+    BlockValidationError,
+    Chainstate,
+    ConsensusParams,
+    MemoryStore,
+    Transaction,
+    TransactionBuilder,
+    TransactionDirection,
+    TransactionType,
+    Wallet,
+    DELEGATION_HASH_VERSION,
+};
+use chrono::Utc;
+use common::{
+    chain,
+    create_blockchain,
+    dump,
+    reward,
+};
+
+fn payment(sender: &mut Wallet, to_address: &str, ammount: u64, fee: u64) -> Transaction {
+    TransactionBuilder::new()
+        .to_address(to_address)
+        .ammount(ammount)
+        .is_type(TransactionType::MOVEMENT)
+        .fee(fee)
+        .chain(&chain())
+        .with_wallet(sender)
+        .build()
+}
+
+fn create_block(previous_hash: &BlockHash, forger: &Wallet, transactions: &[Transaction]) -> Block {
+    BlockBuilder::new()
+        .transactions(transactions)
+        .timestamp(Utc::now())
+        .key(&forger.get_public())
+        .previous_hash(previous_hash)
+        .hash_it()
+        .sign_with(forger, &chain())
+        .build()
+}
+
+fn batch(sender: &mut Wallet, outputs: &[(&str, u64)], fee: u64) -> Transaction {
+    let mut builder = TransactionBuilder::new();
+    for (to_address, ammount) in outputs {
+        builder.output(to_address, *ammount);
+    }
+
+    builder
+        .is_type(TransactionType::BATCH)
+        .fee(fee)
+        .chain(&chain())
+        .with_wallet(sender)
+        .build()
+}
+
+#[test]
+fn fees_go_from_the_sender_to_the_forger() {
+    let forger = Wallet::new();
+    let mut sender = Wallet::new();
+    let receiver = Wallet::new().get_public().hash_it();
+    let forger_address = forger.get_public().hash_it();
+
+    let mut blockchain = create_blockchain(&[&forger], &[&sender], ConsensusParams::default());
+    let genesis_hash = blockchain.last_block_hash.clone().unwrap();
+    let params = blockchain.consensus_params();
+
+    // The sender must afford the ammount and the fee
+    let too_expensive = payment(&mut sender.clone(), &receiver, 95, 6);
+    assert!(!blockchain.state.verify_transaction_ammount(&too_expensive));
+
+    let first_payment = payment(&mut sender, &receiver, 50, 2);
+    let second_payment = payment(&mut sender, &receiver, 10, 3);
+    let transactions = [first_payment, second_payment];
+
+    // The forger can take the block reward and the fees
+    let block = create_block(
+        &genesis_hash,
+        &forger,
+        &[
+            &[reward(&forger, params.block_reward + 6)],
+            &transactions[..],
+        ]
+        .concat(),
+    );
+    assert_eq!(
+        validate_block(&block, &blockchain.state, &params, &chain()),
+        Err(BlockValidationError::InvalidCoinbaseAmmount {
+            max: params.block_reward + 5,
+            found: params.block_reward + 6,
+        })
+    );
+
+    let block = create_block(
+        &genesis_hash,
+        &forger,
+        &[
+            &[reward(&forger, params.block_reward + 5)],
+            &transactions[..],
+        ]
+        .concat(),
+    );
+    assert!(matches!(
+        blockchain.import_block(&block),
+        Ok(BlockImport::Extended)
+    ));
+
+    assert_eq!(
+        blockchain
+            .state
+            .get_address_ammount(sender.get_public().hash_it()),
+        100 - 50 - 2 - 10 - 3
+    );
+    assert_eq!(blockchain.state.get_address_ammount(receiver), 60);
+    assert_eq!(
+        blockchain.state.get_address_ammount(forger_address),
+        params.block_reward + 5
+    );
+}
+
+#[test]
+fn blocks_of_older_versions_cant_have_fees() {
+    let mut sender = Wallet::new();
+    let receiver = Wallet::new().get_public().hash_it();
+
+    // Transactions hashed like the previous version, which doesn't commit to the fee
+    let create_legacy_block = |sender: &mut Wallet, fee: u64| {
+        let mut tx = payment(sender, &receiver, 1, fee);
+        let legacy_hash = tx.hash_with_version(3);
+        if let Transaction::MOVEMENT { hash, .. } = &mut tx {
+            *hash = legacy_hash;
+        }
+
+        let mut block = create_block(&BlockHash::default(), sender, &[tx]);
+        block.hash = BlockHash::with_version(
+            3,
+            &block.transactions,
+            block.timestamp,
+            block.previous_hash.clone(),
+            block.key.clone(),
+        );
+        block
+    };
+
+    assert!(create_legacy_block(&mut sender, 0)
+        .verify_integrity()
+        .is_ok());
+    assert!(create_legacy_block(&mut sender, 1)
+        .verify_integrity()
+        .is_err());
+}
+
+#[test]
+fn batches_pay_every_output_with_a_single_history() {
+    let mut chainstate = Chainstate::new(Arc::new(MemoryStore::default()));
+    let mut sender = Wallet::new();
+    let sender_address = sender.get_public().hash_it();
+    let address_a = Wallet::new().get_public().hash_it();
+    let address_b = Wallet::new().get_public().hash_it();
+
+    let reward = reward(&sender, 100);
+    chainstate.effect_transaction(&reward);
+
+    let before = dump(&chainstate);
+
+    let tx = batch(
+        &mut sender,
+        &[(&address_a, 10), (&address_b, 20), (&address_a, 5)],
+        2,
+    );
+    assert!(tx.verify(&chain()));
+    assert_eq!(tx.get_cost(), Some(37));
+    assert!(chainstate.verify_transaction_ammount(&tx));
+    assert!(chainstate.verify_transaction_history(&tx));
+
+    let mut undo = chainstate.begin_undo();
+    chainstate.effect_transaction_journaled(&tx, &mut undo);
+
+    assert_eq!(chainstate.get_address_ammount(sender_address.clone()), 63);
+    assert_eq!(chainstate.get_address_ammount(address_a.clone()), 15);
+    assert_eq!(chainstate.get_address_ammount(address_b.clone()), 20);
+    assert_eq!(chainstate.addresses[&sender_address].history, 1);
+
+    // The next transaction of the sender uses the next history
+    let too_expensive = batch(&mut sender, &[(&address_a, 60), (&address_b, 3)], 1);
+    assert!(chainstate.verify_transaction_history(&too_expensive));
+    assert!(!chainstate.verify_transaction_ammount(&too_expensive));
+
+    chainstate.revert_block(undo);
+    assert_eq!(dump(&chainstate), before);
+
+    // Each address is indexed once per batch
+    let mut block = BlockBuilder::new()
+        .transactions(&[reward, tx])
+        .timestamp(Utc::now())
+        .key(&sender.get_public())
+        .hash_it()
+        .sign_with(&sender, &chain())
+        .build();
+    block.index = Some(1);
+
+    let incoming_a = AddressHistoryEntry::from_block(&block)
+        .into_iter()
+        .filter(|(address, entry)| {
+            address == &address_a && entry.direction == TransactionDirection::Incoming
+        })
+        .count();
+    assert_eq!(incoming_a, 1);
+}
+
+#[test]
+fn invalid_batches() {
+    let mut sender = Wallet::new();
+    let address = Wallet::new().get_public().hash_it();
+
+    // A batch must pay someone
+    assert!(!batch(&mut sender, &[], 1).verify(&chain()));
+
+    // The outputs are covered by the signature
+    let mut tx = batch(&mut sender, &[(&address, 1)], 0);
+    if let Transaction::BATCH { outputs, .. } = &mut tx {
+        outputs[0].ammount = 1000;
+    }
+    assert!(!tx.verify(&chain()));
+
+    // Outputs that add up to more than there can be are never affordable
+    let tx = batch(&mut sender, &[(&address, u64::MAX), (&address, 1)], 0);
+    assert_eq!(tx.get_cost(), None);
+}
+
+#[test]
+fn batches_arent_accepted_in_older_blocks() {
+    let mut sender = Wallet::new();
+    let address = Wallet::new().get_public().hash_it();
+
+    let mut tx = batch(&mut sender, &[(&address, 1)], 0);
+    let older_hash = tx.hash_with_version(DELEGATION_HASH_VERSION);
+    if let Transaction::BATCH { hash, .. } = &mut tx {
+        *hash = older_hash;
+    }
+
+    let mut block = BlockBuilder::new()
+        .transactions(&[tx])
+        .timestamp(Utc::now())
+        .key(&sender.get_public())
+        .hash_it()
+        .sign_with(&sender, &chain())
+        .build();
+    block.hash = BlockHash::with_version(
+        DELEGATION_HASH_VERSION,
+        &block.transactions,
+        block.timestamp,
+        block.previous_hash.clone(),
+        block.key.clone(),
+    );
+    assert!(block.verify_integrity().is_err());
+}
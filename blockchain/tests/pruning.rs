@@ -0,0 +1,163 @@
+mod common;
+
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use blockchain::{
+    BlockStore,
+    Blockchain,
+    BlockchainErrors,
+    Configuration,
+    MemoryStore,
+    Metrics,
+    PruningMode,
+    Transaction,
+    Wallet,
+    MAX_RANGE,
+};
+use common::forge_block;
+use tokio_test::block_on;
+
+/*
+ * Forge `length` blocks taking turns between two forgers, returns the reward of each block
+ */
+fn forge_chain(
+    config: &Configuration,
+    store: Arc<dyn BlockStore>,
+    length: usize,
+) -> (Blockchain, Vec<Transaction>) {
+    let mut blockchain = block_on(Blockchain::with_store(
+        config.clone(),
+        store,
+        Arc::new(Mutex::new(Metrics::new(vec![]))),
+    ));
+    let mut forgers = [Wallet::new(), Wallet::new()];
+    let mut rewards = Vec::new();
+
+    for i in 0..length {
+        let forger = &mut forgers[i % 2];
+
+        let block = forge_block(
+            blockchain.last_block_hash.as_ref(),
+            forger,
+            10 + i as u64,
+            &[],
+        );
+
+        blockchain.add_block(&block).unwrap();
+        rewards.push(block.transactions[0].clone());
+    }
+
+    (blockchain, rewards)
+}
+
+#[test]
+fn pruned_mode() {
+    let mut config = Configuration::new();
+    config.snapshot_interval = 2;
+    config.pruning = PruningMode::Pruned { keep_blocks: 2 };
+
+    // The blocks a reorganization can revert must keep their transactions
+    assert!(matches!(
+        config.verify(),
+        Err(BlockchainErrors::InvalidConfiguration(_))
+    ));
+    config.max_fork_depth = 2;
+    assert!(config.verify().is_ok());
+
+    let store: Arc<dyn BlockStore> = Arc::new(MemoryStore::default());
+    let (blockchain, rewards) = forge_chain(&config, store.clone(), 7);
+
+    // Pruned after the snapshot at height 6
+    assert_eq!(store.get_pruned_height(), 4);
+
+    assert!(matches!(
+        blockchain.get_block_by_height(3),
+        Err(BlockchainErrors::PrunedBlock(3))
+    ));
+    assert!(matches!(
+        blockchain.get_blocks_range(4, 6),
+        Err(BlockchainErrors::PrunedBlock(4))
+    ));
+    assert!(matches!(
+        blockchain.get_transaction(&rewards[0].get_hash()),
+        Err(BlockchainErrors::PrunedBlock(1))
+    ));
+
+    // The first forger forged the blocks 1, 3, 5 and 7
+    let forger = store.get_block_by_height(7).unwrap().key.hash_it();
+    assert!(matches!(
+        blockchain.get_address_history(&forger, 0, 10),
+        Err(BlockchainErrors::PrunedBlock(1))
+    ));
+    assert_eq!(
+        blockchain
+            .get_address_history(&forger, 2, 10)
+            .unwrap()
+            .len(),
+        2
+    );
+
+    let recent_blocks = blockchain.get_blocks_range(5, 7).unwrap();
+    assert_eq!(recent_blocks.len(), 3);
+    assert!(recent_blocks
+        .iter()
+        .all(|block| block.transactions.len() == 1));
+
+    let (transaction, location) = blockchain
+        .get_transaction(&rewards[5].get_hash())
+        .unwrap()
+        .unwrap();
+    assert_eq!(transaction.get_hash(), rewards[5].get_hash());
+    assert_eq!(location.height, 6);
+
+    // The node can still start from the snapshot
+    let restarted_blockchain = block_on(Blockchain::with_store(
+        config,
+        store,
+        Arc::new(Mutex::new(Metrics::new(vec![]))),
+    ));
+    assert_eq!(restarted_blockchain.index, 7);
+    assert_eq!(
+        restarted_blockchain.state.get_address_ammount(forger),
+        10 + 12 + 14 + 16
+    );
+
+    assert!(matches!(
+        restarted_blockchain.export_chain(&mut Vec::new()),
+        Err(BlockchainErrors::CouldntExportChain(_))
+    ));
+}
+
+#[test]
+fn archive_mode() {
+    let mut config = Configuration::new();
+    config.snapshot_interval = 2;
+
+    let store: Arc<dyn BlockStore> = Arc::new(MemoryStore::default());
+    let (blockchain, rewards) = forge_chain(&config, store.clone(), 7);
+
+    assert_eq!(store.get_pruned_height(), 0);
+    assert_eq!(blockchain.get_blocks_range(1, 7).unwrap().len(), 7);
+    assert!(blockchain
+        .get_transaction(&rewards[0].get_hash())
+        .unwrap()
+        .is_some());
+
+    // Ranges start at the first block and they can't be too long
+    assert!(matches!(
+        blockchain.get_blocks_range(0, 7),
+        Err(BlockchainErrors::InvalidRange(0, 7))
+    ));
+    assert!(matches!(
+        blockchain.get_headers_range(0, 7),
+        Err(BlockchainErrors::InvalidRange(0, 7))
+    ));
+    assert!(matches!(
+        blockchain.get_blocks_range(1, MAX_RANGE + 1),
+        Err(BlockchainErrors::InvalidRange(..))
+    ));
+    assert_eq!(blockchain.get_headers_range(1, MAX_RANGE).unwrap().len(), 7);
+}
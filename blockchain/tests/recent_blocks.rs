@@ -0,0 +1,87 @@
+mod common;
+
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use blockchain::{
+    Block,
+    BlockStore,
+    Blockchain,
+    Configuration,
+    MemoryStore,
+    Metrics,
+    Wallet,
+};
+use common::forge_block;
+use tokio_test::block_on;
+
+fn forge_blocks(blockchain: &mut Blockchain, length: usize) {
+    let mut forgers = [Wallet::new(), Wallet::new()];
+
+    for i in 0..length {
+        let forger = &mut forgers[i % 2];
+
+        let block = forge_block(blockchain.last_block_hash.as_ref(), forger, 10, &[]);
+
+        blockchain.add_block(&block).unwrap();
+    }
+}
+
+#[test]
+fn recent_blocks_window() {
+    let mut config = Configuration::new();
+    config.recent_blocks = 3;
+    config.snapshot_interval = 4;
+
+    let store: Arc<dyn BlockStore> = Arc::new(MemoryStore::default());
+    let metrics = Arc::new(Mutex::new(Metrics::new(vec![])));
+
+    let mut blockchain = block_on(Blockchain::with_store(
+        config.clone(),
+        store.clone(),
+        metrics.clone(),
+    ));
+    assert!(blockchain.recent(3).is_empty());
+
+    forge_blocks(&mut blockchain, 2);
+    assert_eq!(blockchain.recent(3).len(), 2);
+
+    forge_blocks(&mut blockchain, 4);
+    assert_eq!(blockchain.chain.len(), 3);
+
+    let heights = |blocks: &[Block]| {
+        blocks
+            .iter()
+            .map(|block| block.index.unwrap())
+            .collect::<Vec<usize>>()
+    };
+
+    assert_eq!(heights(blockchain.recent(3)), vec![4, 5, 6]);
+    assert_eq!(heights(blockchain.recent(2)), vec![5, 6]);
+    assert_eq!(heights(blockchain.recent(10)), vec![4, 5, 6]);
+    assert_eq!(blockchain.peek().unwrap().index, Some(6));
+
+    // Lookups are served from memory and from the storage
+    assert_eq!(
+        heights(&blockchain.get_blocks_range(5, 9).unwrap()),
+        vec![5, 6]
+    );
+    assert_eq!(
+        heights(&blockchain.get_blocks_range(2, 5).unwrap()),
+        vec![2, 3, 4, 5]
+    );
+    assert_eq!(
+        blockchain.get_block_by_height(5).unwrap().unwrap().hash,
+        store.get_block_by_height(5).unwrap().hash
+    );
+
+    // The window is filled again when the node restarts
+    let restarted_blockchain = block_on(Blockchain::with_store(config, store, metrics));
+    assert_eq!(heights(restarted_blockchain.recent(3)), vec![4, 5, 6]);
+    assert_eq!(
+        restarted_blockchain.last_block_hash,
+        blockchain.last_block_hash
+    );
+}
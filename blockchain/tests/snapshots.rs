@@ -0,0 +1,81 @@
+mod common;
+
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use blockchain::{
+    BlockBuilder,
+    BlockStore,
+    Blockchain,
+    Configuration,
+    MemoryStore,
+    Metrics,
+    TransactionBuilder,
+    TransactionType,
+    Wallet,
+};
+use chrono::Utc;
+use common::chain;
+use tokio_test::block_on;
+
+#[test]
+fn restart_from_snapshot() {
+    let mut config = Configuration::new();
+    config.snapshot_interval = 2;
+
+    let store: Arc<dyn BlockStore> = Arc::new(MemoryStore::default());
+    let metrics = Arc::new(Mutex::new(Metrics::new(vec![])));
+
+    let mut blockchain = block_on(Blockchain::with_store(
+        config.clone(),
+        store.clone(),
+        metrics.clone(),
+    ));
+
+    let mut forgers = [Wallet::new(), Wallet::new()];
+
+    for i in 0..5 {
+        let forger = &mut forgers[i % 2];
+
+        let reward = TransactionBuilder::new()
+            .to_address(&forger.get_public().hash_it())
+            .ammount(10)
+            .is_type(TransactionType::COINBASE)
+            .with_wallet(forger)
+            .build();
+
+        let mut builder = BlockBuilder::new();
+        builder
+            .transactions(&[reward])
+            .timestamp(Utc::now())
+            .key(&forger.get_public());
+
+        if let Some(last_block_hash) = &blockchain.last_block_hash {
+            builder.previous_hash(last_block_hash);
+        }
+
+        blockchain
+            .add_block(&builder.hash_it().sign_with(forger, &chain()).build())
+            .unwrap();
+    }
+
+    assert_eq!(store.get_last_snapshot().unwrap().height, 4);
+
+    let restarted_blockchain = block_on(Blockchain::with_store(config, store, metrics));
+
+    assert_eq!(restarted_blockchain.index, 5);
+    assert_eq!(
+        restarted_blockchain
+            .state
+            .get_address_ammount(forgers[0].get_public().hash_it()),
+        30
+    );
+    assert_eq!(
+        restarted_blockchain
+            .state
+            .get_address_ammount(forgers[1].get_public().hash_it()),
+        20
+    );
+}
@@ -0,0 +1,398 @@
+mod common;
+
+use std::sync::Arc;
+
+use blockchain::{
+    Block,
+    BlockBuilder,
+    BlockHash,
+    Chainstate,
+    ConsensusParams,
+    ConsensusUpgrade,
+    MemoryStore,
+    Transaction,
+    TransactionBuilder,
+    TransactionType,
+    Unbonding,
+    Wallet,
+    BATCH_HASH_VERSION,
+    CHAIN_ID_HASH_VERSION,
+    FEE_HASH_VERSION,
+    MAX_COMMISSION,
+};
+use chrono::Utc;
+use common::{
+    chain,
+    dump,
+    reward,
+};
+
+fn create_chainstate() -> Chainstate {
+    Chainstate::with_consensus(
+        Arc::new(MemoryStore::default()),
+        ConsensusParams {
+            unbonding_period: 2,
+            ..Default::default()
+        },
+    )
+}
+
+fn transaction(
+    wallet: &mut Wallet,
+    ammount: u64,
+    fee: u64,
+    type_tx: TransactionType,
+) -> Transaction {
+    TransactionBuilder::new()
+        .to_address(&wallet.get_public().hash_it())
+        .ammount(ammount)
+        .fee(fee)
+        .is_type(type_tx)
+        .chain(&chain())
+        .with_wallet(wallet)
+        .build()
+}
+
+fn create_block(forger: &Wallet, transactions: &[Transaction]) -> Block {
+    BlockBuilder::new()
+        .transactions(transactions)
+        .timestamp(Utc::now())
+        .key(&forger.get_public())
+        .hash_it()
+        .sign_with(forger, &chain())
+        .build()
+}
+
+fn stake(wallet: &mut Wallet, ammount: u64, commission: u32) -> Transaction {
+    TransactionBuilder::new()
+        .ammount(ammount)
+        .commission(commission)
+        .is_type(TransactionType::STAKE)
+        .chain(&chain())
+        .with_wallet(wallet)
+        .build()
+}
+
+fn delegate(wallet: &mut Wallet, validator: &Wallet, ammount: u64) -> Transaction {
+    TransactionBuilder::new()
+        .validator(&validator.get_public())
+        .ammount(ammount)
+        .is_type(TransactionType::DELEGATE)
+        .chain(&chain())
+        .with_wallet(wallet)
+        .build()
+}
+
+fn undelegate(wallet: &mut Wallet, validator: &Wallet, ammount: u64) -> Transaction {
+    TransactionBuilder::new()
+        .validator(&validator.get_public())
+        .ammount(ammount)
+        .is_type(TransactionType::UNDELEGATE)
+        .chain(&chain())
+        .with_wallet(wallet)
+        .build()
+}
+
+#[test]
+fn unstaked_funds_are_locked_for_the_unbonding_period() {
+    let mut chainstate = create_chainstate();
+    let mut wallet = Wallet::new();
+    let address = wallet.get_public().hash_it();
+
+    let reward = reward(&wallet, 100);
+    let stake = transaction(&mut wallet, 30, 0, TransactionType::STAKE);
+    chainstate.apply_block(&create_block(&wallet, &[reward, stake]));
+
+    // Staked funds aren't spendable
+    assert_eq!(chainstate.get_address_ammount(address.clone()), 70);
+    assert_eq!(chainstate.get_address_staked(&address), 30);
+
+    // Only the staked funds can be unstaked
+    let too_much = transaction(&mut wallet.clone(), 31, 0, TransactionType::UNSTAKE);
+    assert!(too_much.verify(&chain()));
+    assert!(!chainstate.verify_transaction_ammount(&too_much));
+
+    let unstake = transaction(&mut wallet, 10, 1, TransactionType::UNSTAKE);
+    assert!(chainstate.verify_transaction_ammount(&unstake));
+    chainstate.apply_block(&create_block(&wallet, &[unstake]));
+
+    assert_eq!(chainstate.get_address_staked(&address), 20);
+    assert_eq!(chainstate.get_address_ammount(address.clone()), 69);
+    assert_eq!(
+        chainstate.unbondings,
+        vec![Unbonding {
+            address: address.clone(),
+            ammount: 10,
+            release_height: 4,
+        }]
+    );
+
+    // The funds are given back once the block at the release height is applied
+    chainstate.apply_block(&create_block(&wallet, &[]));
+    assert_eq!(chainstate.get_address_ammount(address.clone()), 69);
+
+    let before = dump(&chainstate);
+    let undo = chainstate.apply_block(&create_block(&wallet, &[]));
+    assert_eq!(chainstate.get_address_ammount(address.clone()), 79);
+    assert!(chainstate.unbondings.is_empty());
+
+    // Reverting the block locks them again
+    chainstate.revert_block(undo);
+    assert_eq!(dump(&chainstate), before);
+}
+
+#[test]
+fn unstaking_everything_withdraws_from_the_election() {
+    let mut chainstate = create_chainstate();
+    let mut validator = Wallet::new();
+    let mut other_validator = Wallet::new();
+    let address = validator.get_public().hash_it();
+
+    let rewards = [reward(&validator, 100), reward(&other_validator, 100)];
+    for reward in &rewards {
+        chainstate.effect_transaction(reward);
+    }
+
+    let stakes = [
+        transaction(&mut validator, 5, 0, TransactionType::STAKE),
+        transaction(&mut other_validator, 5, 0, TransactionType::STAKE),
+        transaction(&mut validator, 5, 0, TransactionType::STAKE),
+    ];
+    chainstate.apply_block(&create_block(&validator, &stakes));
+    assert_eq!(chainstate.last_staking_addresses.len(), 3);
+
+    let before = dump(&chainstate);
+
+    // A partial unstake keeps the validator in the election
+    let mut undo = chainstate.begin_undo();
+    let partial_unstake = transaction(&mut validator, 4, 0, TransactionType::UNSTAKE);
+    chainstate.effect_transaction_journaled(&partial_unstake, &mut undo);
+    assert_eq!(chainstate.last_staking_addresses.len(), 3);
+
+    let unstake = transaction(&mut validator, 6, 0, TransactionType::UNSTAKE);
+    chainstate.effect_transaction_journaled(&unstake, &mut undo);
+    assert_eq!(chainstate.get_address_staked(&address), 0);
+    assert_eq!(chainstate.unbondings.len(), 2);
+
+    let stakers = chainstate
+        .last_staking_addresses
+        .iter()
+        .filter_map(|tx| match tx {
+            Transaction::STAKE { from_address, .. } => Some(from_address.clone()),
+            _ => None,
+        })
+        .collect::<Vec<String>>();
+    assert_eq!(stakers, vec![other_validator.get_public().hash_it()]);
+
+    chainstate.revert_block(undo);
+    assert_eq!(dump(&chainstate), before);
+}
+
+#[test]
+fn unstakes_arent_accepted_in_older_blocks() {
+    let mut wallet = Wallet::new();
+
+    let mut unstake = transaction(&mut wallet, 10, 0, TransactionType::UNSTAKE);
+    let older_hash = unstake.hash_with_version(FEE_HASH_VERSION);
+    if let Transaction::UNSTAKE { hash, .. } = &mut unstake {
+        *hash = older_hash;
+    }
+
+    let mut block = create_block(&wallet, &[unstake]);
+    block.hash = BlockHash::with_version(
+        FEE_HASH_VERSION,
+        &block.transactions,
+        block.timestamp,
+        block.previous_hash.clone(),
+        block.key.clone(),
+    );
+    assert!(block.verify_integrity().is_err());
+}
+
+#[test]
+fn delegators_share_the_rewards_of_their_validator() {
+    let mut chainstate = Chainstate::with_consensus(
+        Arc::new(MemoryStore::default()),
+        ConsensusParams {
+            block_reward: 91,
+            upgrades: vec![ConsensusUpgrade {
+                height: 2,
+                weighted_election: Some(true),
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    );
+    let mut validator = Wallet::new();
+    let mut delegator_a = Wallet::new();
+    let mut delegator_b = Wallet::new();
+    let validator_address = validator.get_public().hash_it();
+    let address_a = delegator_a.get_public().hash_it();
+    let address_b = delegator_b.get_public().hash_it();
+
+    for wallet in [&validator, &delegator_a, &delegator_b] {
+        chainstate.effect_transaction(&reward(wallet, 100));
+    }
+
+    let before = dump(&chainstate);
+
+    // The validator keeps 10% of the rewards
+    let mut undo = chainstate.begin_undo();
+    let transactions = [
+        stake(&mut validator, 20, 1000),
+        delegate(&mut delegator_a, &validator, 30),
+        delegate(&mut delegator_b, &validator, 50),
+    ];
+    for tx in &transactions {
+        assert!(tx.verify(&chain()));
+        assert!(chainstate.verify_transaction_ammount(tx));
+        chainstate.effect_transaction_journaled(tx, &mut undo);
+    }
+
+    assert_eq!(chainstate.get_address_ammount(address_a.clone()), 70);
+    assert_eq!(chainstate.get_election_weight(&validator_address), 100);
+    assert_eq!(chainstate.get_election_weight(&address_a), 0);
+    assert_eq!(chainstate.delegations[&validator_address][&address_b], 50);
+
+    // Before the weighted election is activated the validator keeps the whole reward
+    chainstate.effect_transaction_journaled(&reward(&validator, 101), &mut undo);
+
+    assert_eq!(chainstate.get_address_ammount(address_a.clone()), 70);
+    assert_eq!(
+        chainstate.get_address_ammount(validator_address.clone()),
+        80 + 101
+    );
+
+    // The next block activates it
+    chainstate.height = 1;
+
+    // After the commission, each one gets the share of the block reward for the funds it bonded,
+    // the validator keeps the fees and the rounding
+    chainstate.effect_transaction_journaled(&reward(&validator, 101), &mut undo);
+
+    assert_eq!(chainstate.get_address_ammount(address_a), 70 + 24);
+    assert_eq!(chainstate.get_address_ammount(address_b), 50 + 41);
+    assert_eq!(
+        chainstate.get_address_ammount(validator_address),
+        80 + 101 + 101 - 24 - 41
+    );
+
+    chainstate.height = 0;
+    chainstate.revert_block(undo);
+    assert_eq!(dump(&chainstate), before);
+}
+
+#[test]
+fn invalid_commissions_and_delegations() {
+    let mut validator = Wallet::new();
+    let mut delegator = Wallet::new();
+
+    // Validators can't give more than the whole reward
+    assert!(stake(&mut validator.clone(), 1, MAX_COMMISSION).verify(&chain()));
+    assert!(!stake(&mut validator, 1, MAX_COMMISSION + 1).verify(&chain()));
+
+    // Blocks hashed before delegations existed can't have them
+    let mut tx = delegate(&mut delegator, &validator, 1);
+    let legacy_hash = tx.hash_with_version(CHAIN_ID_HASH_VERSION);
+    if let Transaction::DELEGATE { hash, .. } = &mut tx {
+        *hash = legacy_hash;
+    }
+
+    let mut block = BlockBuilder::new()
+        .transactions(&[tx])
+        .timestamp(Utc::now())
+        .key(&delegator.get_public())
+        .hash_it()
+        .sign_with(&delegator, &chain())
+        .build();
+    block.hash = BlockHash::with_version(
+        CHAIN_ID_HASH_VERSION,
+        &block.transactions,
+        block.timestamp,
+        block.previous_hash.clone(),
+        block.key.clone(),
+    );
+    assert!(block.verify_integrity().is_err());
+}
+
+#[test]
+fn delegators_can_take_back_their_funds() {
+    let mut chainstate = Chainstate::new(Arc::new(MemoryStore::default()));
+    let mut validator = Wallet::new();
+    let mut delegator = Wallet::new();
+    let validator_address = validator.get_public().hash_it();
+    let delegator_address = delegator.get_public().hash_it();
+
+    for wallet in [&validator, &delegator] {
+        chainstate.effect_transaction(&reward(wallet, 100));
+    }
+    chainstate.effect_transaction(&stake(&mut validator, 20, 0));
+    chainstate.effect_transaction(&delegate(&mut delegator, &validator, 30));
+
+    let before = dump(&chainstate);
+
+    // Can't take back more than what was delegated
+    assert!(!chainstate.verify_transaction_ammount(&undelegate(
+        &mut delegator.clone(),
+        &validator,
+        31
+    )));
+
+    let mut undo = chainstate.begin_undo();
+    // The wallet is cloned, so the history is free again once the undelegation is reverted
+    let tx = undelegate(&mut delegator.clone(), &validator, 10);
+    assert!(tx.verify(&chain()));
+    assert!(chainstate.verify_transaction_ammount(&tx));
+    chainstate.effect_transaction_journaled(&tx, &mut undo);
+
+    // The funds stop counting for the validator but stay locked
+    assert_eq!(chainstate.get_election_weight(&validator_address), 40);
+    assert_eq!(
+        chainstate.delegations[&validator_address][&delegator_address],
+        20
+    );
+    assert_eq!(
+        chainstate.get_address_ammount(delegator_address.clone()),
+        70
+    );
+    assert_eq!(chainstate.unbondings.len(), 1);
+
+    chainstate.revert_block(undo);
+    assert_eq!(dump(&chainstate), before);
+
+    // Undelegating everything removes the delegator
+    let mut undo = chainstate.begin_undo();
+    chainstate.effect_transaction_journaled(&undelegate(&mut delegator, &validator, 30), &mut undo);
+    assert!(!chainstate.delegations.contains_key(&validator_address));
+
+    chainstate.revert_block(undo);
+    assert_eq!(dump(&chainstate), before);
+}
+
+#[test]
+fn undelegations_arent_accepted_in_older_blocks() {
+    let validator = Wallet::new();
+    let mut delegator = Wallet::new();
+
+    let mut tx = undelegate(&mut delegator, &validator, 1);
+    let older_hash = tx.hash_with_version(BATCH_HASH_VERSION);
+    if let Transaction::UNDELEGATE { hash, .. } = &mut tx {
+        *hash = older_hash;
+    }
+
+    let mut block = BlockBuilder::new()
+        .transactions(&[tx])
+        .timestamp(Utc::now())
+        .key(&delegator.get_public())
+        .hash_it()
+        .sign_with(&delegator, &chain())
+        .build();
+    block.hash = BlockHash::with_version(
+        BATCH_HASH_VERSION,
+        &block.transactions,
+        block.timestamp,
+        block.previous_hash.clone(),
+        block.key.clone(),
+    );
+    assert!(block.verify_integrity().is_err());
+}
@@ -0,0 +1,331 @@
+mod common;
+
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use blockchain::{
+    AddressHistoryEntry,
+    Block,
+    BlockBuilder,
+    BlockHeader,
+    BlockStore,
+    Blockchain,
+    BlockchainErrors,
+    ChainstateSnapshot,
+    Configuration,
+    DiskStore,
+    MemoryStore,
+    Metrics,
+    TransactionBuilder,
+    TransactionDirection,
+    TransactionLocation,
+    TransactionType,
+    Wallet,
+    SCHEMA_VERSION,
+};
+use chrono::Utc;
+use tokio_test::block_on;
+
+/// A store whose disk is always full
+#[derive(Debug)]
+struct FailingStore;
+
+impl BlockStore for FailingStore {
+    fn get_height(&self) -> usize {
+        0
+    }
+
+    fn get_block_by_height(&self, _height: usize) -> Option<Block> {
+        None
+    }
+
+    fn get_blocks_range(&self, _from: usize, _to: usize) -> Vec<Block> {
+        Vec::new()
+    }
+
+    fn add_block(&self, _block: &Block) -> Result<(), BlockchainErrors> {
+        Err(BlockchainErrors::CouldntPersistBlock(
+            "No space left on device".to_string(),
+        ))
+    }
+
+    fn save_header(&self, _header: &BlockHeader) -> Result<(), BlockchainErrors> {
+        Err(BlockchainErrors::CouldntPersistBlock(
+            "No space left on device".to_string(),
+        ))
+    }
+
+    fn get_header_by_height(&self, _height: usize) -> Option<BlockHeader> {
+        None
+    }
+
+    fn get_headers_range(&self, _from: usize, _to: usize) -> Vec<BlockHeader> {
+        Vec::new()
+    }
+
+    fn index_block(&self, _block: &Block) -> Result<(), BlockchainErrors> {
+        Err(BlockchainErrors::CouldntPersistBlock(
+            "No space left on device".to_string(),
+        ))
+    }
+
+    fn get_transaction_locations(&self, _hash: &str) -> Vec<TransactionLocation> {
+        Vec::new()
+    }
+
+    fn get_address_history(
+        &self,
+        _address: &str,
+        _skip: usize,
+        _limit: usize,
+    ) -> Vec<AddressHistoryEntry> {
+        Vec::new()
+    }
+
+    fn get_block_with_hash(&self, _hash: &str) -> Option<Block> {
+        None
+    }
+
+    fn get_block_with_prev_hash(&self, _prev_hash: &str) -> Option<Block> {
+        None
+    }
+
+    fn save_snapshot(&self, _snapshot: &ChainstateSnapshot) -> Result<(), BlockchainErrors> {
+        Err(BlockchainErrors::CouldntPersistSnapshot(
+            "No space left on device".to_string(),
+        ))
+    }
+
+    fn get_last_snapshot(&self) -> Option<ChainstateSnapshot> {
+        None
+    }
+
+    fn get_snapshot_at(&self, _max_height: usize) -> Option<ChainstateSnapshot> {
+        None
+    }
+
+    fn rollback_to(&self, _height: usize) -> Result<(), BlockchainErrors> {
+        Err(BlockchainErrors::CouldntRollbackBlocks(
+            "No space left on device".to_string(),
+        ))
+    }
+
+    fn get_pruned_height(&self) -> usize {
+        0
+    }
+
+    fn prune_blocks(&self, _up_to: usize) -> Result<(), BlockchainErrors> {
+        Err(BlockchainErrors::CouldntPruneBlocks(
+            "No space left on device".to_string(),
+        ))
+    }
+
+    fn get_schema_version(&self) -> u32 {
+        SCHEMA_VERSION
+    }
+
+    fn set_schema_version(&self, _version: u32) -> Result<(), BlockchainErrors> {
+        Err(BlockchainErrors::CouldntMigrateStorage(
+            "No space left on device".to_string(),
+        ))
+    }
+}
+
+fn create_chain(length: usize) -> Vec<Block> {
+    let mut wallet = Wallet::new();
+    let mut chain: Vec<Block> = Vec::new();
+
+    for i in 0..length {
+        // Every fourth block repeats the reward, so the coinbases have the same hash
+        let reward = TransactionBuilder::new()
+            .to_address(&wallet.get_public().hash_it())
+            .ammount(10 + (i % 4) as u64)
+            .is_type(TransactionType::COINBASE)
+            .with_wallet(&mut wallet)
+            .build();
+
+        let mut builder = BlockBuilder::new();
+        builder
+            .transactions(&[reward])
+            .timestamp(Utc::now())
+            .key(&wallet.get_public());
+
+        if let Some(previous_block) = chain.last() {
+            builder.previous_hash(&previous_block.hash);
+        }
+
+        let mut block = builder
+            .hash_it()
+            .sign_with(&wallet, &common::chain())
+            .build();
+        block.index = Some(i + 1);
+        chain.push(block);
+    }
+
+    chain
+}
+
+fn check_store(store: Arc<dyn BlockStore>) {
+    let chain = create_chain(5);
+
+    for block in chain.iter().rev() {
+        store.add_block(block).unwrap();
+    }
+
+    assert_eq!(store.get_height(), 5);
+
+    let blocks = store.get_blocks().unwrap();
+    assert_eq!(blocks.len(), 5);
+    for (block, stored_block) in chain.iter().zip(blocks.iter()) {
+        assert_eq!(block.hash, stored_block.hash);
+    }
+
+    let fourth_block = store.get_block_by_height(4).unwrap();
+    assert_eq!(fourth_block.hash, chain[3].hash);
+    assert!(store.get_block_by_height(6).is_none());
+
+    let range = store.get_blocks_range(2, 4);
+    assert_eq!(
+        range.iter().map(|block| block.index).collect::<Vec<_>>(),
+        vec![Some(2), Some(3), Some(4)]
+    );
+    assert_eq!(store.get_blocks_range(4, 10).len(), 2);
+    assert!(store.get_blocks_range(4, 2).is_empty());
+
+    let second_block = store.get_block_with_hash(&chain[1].hash.hash).unwrap();
+    assert_eq!(second_block.index, Some(2));
+
+    let third_block = store.get_block_with_prev_hash(&chain[1].hash.hash).unwrap();
+    assert_eq!(third_block.hash, chain[2].hash);
+
+    assert!(store.get_block_with_hash("unknown").is_none());
+
+    let headers = store.get_headers_range(2, 4);
+    assert_eq!(
+        headers
+            .iter()
+            .map(|header| header.index)
+            .collect::<Vec<_>>(),
+        vec![Some(2), Some(3), Some(4)]
+    );
+    assert_eq!(headers[0], second_block.header());
+    assert!(headers[1]
+        .verify(Some(&headers[0]), &common::chain())
+        .is_ok());
+    assert!(store.get_header_by_height(6).is_none());
+
+    let reward = &chain[2].transactions[0];
+    let location = store.get_transaction_location(&reward.get_hash()).unwrap();
+    assert_eq!(location.height, 3);
+    assert_eq!(location.block_hash, chain[2].hash);
+
+    // Equal transactions of different blocks keep all their locations
+    let repeated_reward = chain[4].transactions[0].get_hash();
+    assert_eq!(repeated_reward, chain[0].transactions[0].get_hash());
+    assert_eq!(
+        store
+            .get_transaction_locations(&repeated_reward)
+            .iter()
+            .map(|location| location.height)
+            .collect::<Vec<_>>(),
+        vec![1, 5]
+    );
+    assert_eq!(
+        store
+            .get_transaction_location(&repeated_reward)
+            .unwrap()
+            .height,
+        5
+    );
+
+    let forger = chain[0].key.hash_it();
+    let history = store.get_address_history(&forger, 1, 3);
+    assert_eq!(
+        history
+            .iter()
+            .map(|entry| entry.location.height)
+            .collect::<Vec<_>>(),
+        vec![2, 3, 4]
+    );
+    assert!(history
+        .iter()
+        .all(|entry| entry.direction == TransactionDirection::Incoming));
+
+    store.prune_blocks(3).unwrap();
+    assert_eq!(store.get_pruned_height(), 3);
+
+    // Pruned blocks keep their headers and indexes
+    let pruned_block = store.get_block_by_height(2).unwrap();
+    assert_eq!(pruned_block.hash, chain[1].hash);
+    assert!(pruned_block.transactions.is_empty());
+    assert!(store.get_transaction_location(&reward.get_hash()).is_some());
+    assert_eq!(store.get_block_by_height(4).unwrap().transactions.len(), 1);
+    assert_eq!(store.get_blocks().unwrap().len(), 5);
+    assert_eq!(store.get_header_by_height(2).unwrap(), chain[1].header());
+
+    // The pruned height never goes back
+    store.prune_blocks(1).unwrap();
+    assert_eq!(store.get_pruned_height(), 3);
+
+    for height in [2, 4] {
+        store
+            .save_snapshot(&ChainstateSnapshot {
+                height,
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    assert_eq!(store.get_snapshot_at(3).unwrap().height, 2);
+    assert!(store.get_snapshot_at(1).is_none());
+
+    // Rolling back removes the blocks, their headers and indexes and the snapshots after them
+    store.rollback_to(3).unwrap();
+    assert_eq!(store.get_height(), 3);
+    assert!(store.get_block_with_hash(&chain[3].hash.hash).is_none());
+    assert!(store.get_header_by_height(4).is_none());
+    assert_eq!(
+        store
+            .get_transaction_location(&repeated_reward)
+            .unwrap()
+            .height,
+        1
+    );
+    assert_eq!(store.get_address_history(&forger, 0, 10).len(), 3);
+    assert_eq!(store.get_last_snapshot().unwrap().height, 2);
+}
+
+#[test]
+fn memory_store() {
+    check_store(Arc::new(MemoryStore::default()));
+}
+
+#[test]
+fn disk_store() {
+    let path = std::env::temp_dir().join(format!("disk_store_{}", std::process::id()));
+
+    check_store(Arc::new(DiskStore::new(&path).unwrap()));
+
+    std::fs::remove_dir_all(path).ok();
+}
+
+#[test]
+fn persistence_errors_are_surfaced() {
+    let mut blockchain = block_on(Blockchain::with_store(
+        Configuration::new(),
+        Arc::new(FailingStore),
+        Arc::new(Mutex::new(Metrics::new(vec![]))),
+    ));
+
+    let block = create_chain(1).remove(0);
+
+    assert!(matches!(
+        blockchain.add_block(&block),
+        Err(BlockchainErrors::CouldntPersistBlock(_))
+    ));
+
+    // The chain must not move forward if the block wasn't persisted
+    assert_eq!(blockchain.index, 0);
+    assert!(blockchain.last_block_hash.is_none());
+}
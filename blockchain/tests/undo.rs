@@ -0,0 +1,114 @@
+mod common;
+
+use std::sync::Arc;
+
+use blockchain::{
+    BlockBuilder,
+    Chainstate,
+    MemoryStore,
+    Transaction,
+    TransactionBuilder,
+    TransactionType,
+    Wallet,
+};
+use chrono::Utc;
+use common::{
+    chain,
+    dump,
+};
+
+fn transaction(
+    from: &mut Wallet,
+    to: &Wallet,
+    ammount: u64,
+    type_tx: TransactionType,
+) -> Transaction {
+    TransactionBuilder::new()
+        .to_address(&to.get_public().hash_it())
+        .ammount(ammount)
+        .is_type(type_tx)
+        .chain(&chain())
+        .with_wallet(from)
+        .build()
+}
+
+#[test]
+fn revert_block_restores_the_chainstate() {
+    let mut chainstate = Chainstate::new(Arc::new(MemoryStore::default()));
+
+    let mut wallet_a = Wallet::new();
+    let mut wallet_b = Wallet::new();
+    let wallet_c = Wallet::new();
+    let (address_a, address_b) = (wallet_a.clone(), wallet_b.clone());
+
+    let reward = transaction(&mut wallet_a, &address_a, 50, TransactionType::COINBASE);
+    chainstate.effect_transaction(&reward);
+    chainstate.add_recent_forger(&wallet_a.get_public().hash_it());
+
+    let before = dump(&chainstate);
+
+    // Touches an existing address, creates new ones, stakes and pays back and forth
+    let transactions = vec![
+        transaction(&mut wallet_a, &address_b, 20, TransactionType::MOVEMENT),
+        transaction(&mut wallet_a, &address_a, 5, TransactionType::STAKE),
+        transaction(&mut wallet_b, &wallet_c, 7, TransactionType::MOVEMENT),
+        transaction(&mut wallet_b, &address_a, 3, TransactionType::MOVEMENT),
+        transaction(&mut wallet_b, &address_b, 1000, TransactionType::MOVEMENT),
+    ];
+
+    let block = BlockBuilder::new()
+        .transactions(&transactions)
+        .timestamp(Utc::now())
+        .key(&wallet_a.get_public())
+        .hash_it()
+        .sign_with(&wallet_a, &chain())
+        .build();
+
+    let undo = chainstate.apply_block(&block);
+
+    chainstate.add_recent_forger(&wallet_b.get_public().hash_it());
+    chainstate.last_forger_was_blocked = true;
+
+    assert_eq!(
+        chainstate.get_address_ammount(wallet_c.get_public().hash_it()),
+        7
+    );
+    assert_eq!(chainstate.last_staking_addresses.len(), 1);
+
+    chainstate.revert_block(undo);
+
+    assert_eq!(dump(&chainstate), before);
+    assert!(!chainstate
+        .addresses
+        .contains_key(&wallet_c.get_public().hash_it()));
+}
+
+#[test]
+fn revert_dropped_stakes() {
+    let mut chainstate = Chainstate::new(Arc::new(MemoryStore::default()));
+
+    let mut wallet = Wallet::new();
+    let address = wallet.clone();
+
+    let reward = transaction(&mut wallet, &address, 1000, TransactionType::COINBASE);
+    chainstate.effect_transaction(&reward);
+
+    // Fill the list of the last stakes, so new stakes drop the oldest ones
+    for _ in 0..99 {
+        let stake = transaction(&mut wallet, &address, 1, TransactionType::STAKE);
+        chainstate.effect_transaction(&stake);
+    }
+    assert_eq!(chainstate.last_staking_addresses.len(), 99);
+
+    let before = dump(&chainstate);
+
+    let mut undo = chainstate.begin_undo();
+    for _ in 0..3 {
+        let stake = transaction(&mut wallet, &address, 1, TransactionType::STAKE);
+        chainstate.effect_transaction_journaled(&stake, &mut undo);
+    }
+
+    chainstate.revert_block(undo);
+
+    assert_eq!(dump(&chainstate), before);
+}
@@ -0,0 +1,512 @@
+mod common;
+
+use blockchain::{
+    rank_forgers,
+    validate_block,
+    validate_block_at,
+    Block,
+    BlockBuilder,
+    BlockHash,
+    BlockImport,
+    BlockValidationError,
+    Blockchain,
+    BlockchainErrors,
+    ChainId,
+    ConsensusParams,
+    Encode,
+    Key,
+    Transaction,
+    TransactionBuilder,
+    TransactionType,
+    Wallet,
+    CHAIN_ID_HASH_VERSION,
+    HASH_VERSION,
+};
+use chrono::{
+    DateTime,
+    Duration,
+    Utc,
+};
+use common::{
+    chain,
+    create_blockchain,
+    reward,
+};
+
+fn create_block(
+    previous_hash: &BlockHash,
+    forger: &Wallet,
+    transactions: &[Transaction],
+    timestamp: DateTime<Utc>,
+) -> Block {
+    BlockBuilder::new()
+        .transactions(transactions)
+        .timestamp(timestamp)
+        .key(&forger.get_public())
+        .previous_hash(previous_hash)
+        .hash_it()
+        .sign_with(forger, &chain())
+        .build()
+}
+
+#[test]
+fn blocks_are_rejected_with_their_reason() {
+    let mut forger_a = Wallet::new();
+    let mut forger_b = Wallet::new();
+    let outsider = Wallet::new();
+    forger_a.history = 1;
+    forger_b.history = 1;
+
+    let mut blockchain = create_blockchain(
+        &[&forger_a, &forger_b],
+        &[&forger_a, &forger_b],
+        ConsensusParams::default(),
+    );
+    let genesis_hash = blockchain.last_block_hash.clone().unwrap();
+    let params = blockchain.consensus_params();
+
+    let validate = |blockchain: &Blockchain, block: &Block| {
+        validate_block(block, &blockchain.state, &params, &chain())
+    };
+
+    // A valid block
+    let payment = TransactionBuilder::new()
+        .to_address(&outsider.get_public().hash_it())
+        .ammount(30)
+        .is_type(TransactionType::MOVEMENT)
+        .chain(&chain())
+        .with_wallet(&mut forger_a)
+        .build();
+    let block = create_block(
+        &genesis_hash,
+        &forger_a,
+        &[reward(&forger_a, 10), payment.clone()],
+        Utc::now(),
+    );
+    assert_eq!(validate(&blockchain, &block), Ok(()));
+
+    // The coinbase must go first, only once, to the forger and within the reward
+    let wrong_blocks = [
+        (
+            vec![payment.clone(), reward(&forger_a, 10)],
+            BlockValidationError::MissingCoinbase,
+        ),
+        (
+            vec![reward(&forger_a, 10), reward(&forger_a, 9)],
+            BlockValidationError::MultipleCoinbase(reward(&forger_a, 9).get_hash()),
+        ),
+        (
+            vec![reward(&forger_b, 10)],
+            BlockValidationError::InvalidCoinbaseAddress(reward(&forger_b, 10).get_hash()),
+        ),
+        (
+            vec![reward(&forger_a, 11)],
+            BlockValidationError::InvalidCoinbaseAmmount { max: 10, found: 11 },
+        ),
+    ];
+    for (transactions, reason) in wrong_blocks {
+        let block = create_block(&genesis_hash, &forger_a, &transactions, Utc::now());
+        assert_eq!(validate(&blockchain, &block), Err(reason));
+    }
+
+    // Transactions must be applicable
+    let overspend = TransactionBuilder::new()
+        .to_address(&outsider.get_public().hash_it())
+        .ammount(1000)
+        .is_type(TransactionType::MOVEMENT)
+        .chain(&chain())
+        .with_wallet(&mut forger_b)
+        .build();
+    let block = create_block(
+        &genesis_hash,
+        &forger_a,
+        &[reward(&forger_a, 10), overspend.clone()],
+        Utc::now(),
+    );
+    assert_eq!(
+        validate(&blockchain, &block),
+        Err(BlockValidationError::InvalidTransaction(
+            overspend.get_hash()
+        ))
+    );
+
+    // Only stakers can forge
+    let block = create_block(
+        &genesis_hash,
+        &outsider,
+        &[reward(&outsider, 10)],
+        Utc::now(),
+    );
+    assert_eq!(
+        validate(&blockchain, &block),
+        Err(BlockValidationError::IneligibleForger(
+            outsider.get_public().hash_it()
+        ))
+    );
+
+    // The signature must be the forger's
+    let mut block = create_block(&genesis_hash, &forger_a, &[], Utc::now());
+    block.signature = forger_b.sign_data(chain().signing_data(&block.hash.unite()));
+    assert_eq!(
+        validate(&blockchain, &block),
+        Err(BlockValidationError::InvalidSignature)
+    );
+
+    // Blocks can't go back in time, or stay at the same time as the previous one
+    let genesis_timestamp = blockchain.peek().unwrap().timestamp;
+    for timestamp in [genesis_timestamp - Duration::days(1), genesis_timestamp] {
+        let block = create_block(&genesis_hash, &forger_a, &[], timestamp);
+        assert_eq!(
+            validate(&blockchain, &block),
+            Err(BlockValidationError::TimestampNotAfterPrevious {
+                previous: genesis_timestamp,
+                found: timestamp,
+            })
+        );
+    }
+
+    // Blocks can only be a bit ahead of the clock
+    let now = Utc::now();
+    let drift = Duration::milliseconds(params.max_future_drift);
+    let block = create_block(&genesis_hash, &forger_a, &[], now + drift);
+    assert_eq!(
+        validate_block_at(&block, &blockchain.state, &params, &chain(), now),
+        Ok(())
+    );
+
+    let block = create_block(
+        &genesis_hash,
+        &forger_a,
+        &[],
+        now + drift + Duration::milliseconds(1),
+    );
+    assert_eq!(
+        validate_block_at(&block, &blockchain.state, &params, &chain(), now),
+        Err(BlockValidationError::TimestampTooFarInFuture {
+            max: now + drift,
+            found: block.timestamp,
+        })
+    );
+
+    // Blocks are limited in size
+    let mut small_params = params.clone();
+    small_params.max_block_transactions = 0;
+    let block = create_block(
+        &genesis_hash,
+        &forger_a,
+        &[reward(&forger_a, 10), payment.clone()],
+        Utc::now(),
+    );
+    assert_eq!(
+        validate_block(&block, &blockchain.state, &small_params, &chain()),
+        Err(BlockValidationError::TooManyTransactions { max: 0, found: 1 })
+    );
+
+    let size = block.to_bytes().len();
+    let mut small_params = params.clone();
+    small_params.max_block_size = size;
+    assert_eq!(
+        validate_block(&block, &blockchain.state, &small_params, &chain()),
+        Ok(())
+    );
+    small_params.max_block_size = size - 1;
+    assert_eq!(
+        validate_block(&block, &blockchain.state, &small_params, &chain()),
+        Err(BlockValidationError::BlockTooLarge {
+            max: size - 1,
+            found: size,
+        })
+    );
+
+    // Importing reports the same reasons
+    let block = create_block(
+        &genesis_hash,
+        &forger_a,
+        &[reward(&forger_a, 10), payment],
+        Utc::now(),
+    );
+    assert!(matches!(
+        blockchain.import_block(&block),
+        Ok(BlockImport::Extended)
+    ));
+
+    let next_block = create_block(
+        &block.hash,
+        &forger_a,
+        &[reward(&forger_a, 8)],
+        Utc::now() + Duration::seconds(1),
+    );
+    assert!(matches!(
+        blockchain.import_block(&next_block),
+        Err(BlockchainErrors::InvalidBlock(
+            BlockValidationError::RepeatedForger(_)
+        ))
+    ));
+
+    let unlinked_block = create_block(
+        &genesis_hash,
+        &forger_b,
+        &[reward(&forger_b, 8)],
+        Utc::now(),
+    );
+    assert!(matches!(
+        validate(&blockchain, &unlinked_block),
+        Err(BlockValidationError::InvalidPreviousHash { .. })
+    ));
+}
+
+#[test]
+fn only_the_elected_forger_can_forge() {
+    let forgers = [Wallet::new(), Wallet::new(), Wallet::new()];
+    let forgers_refs = forgers.iter().collect::<Vec<&Wallet>>();
+    let mut blockchain =
+        create_blockchain(&forgers_refs, &forgers_refs, ConsensusParams::default());
+    let genesis_hash = blockchain.last_block_hash.clone().unwrap();
+    let genesis_timestamp = blockchain.peek().unwrap().timestamp;
+    let params = blockchain.consensus_params();
+
+    // Every staker is ranked once
+    let ranking = rank_forgers(&blockchain.state);
+    assert_eq!(ranking.len(), 3);
+
+    let forger_at = |rank: usize| {
+        forgers
+            .iter()
+            .find(|forger| forger.get_public() == ranking[rank])
+            .unwrap()
+    };
+    let validate_at_time = |blockchain: &Blockchain, rank: usize, elapsed: i64, now| {
+        let forger = forger_at(rank);
+        let block = create_block(
+            &genesis_hash,
+            forger,
+            &[reward(forger, 10)],
+            genesis_timestamp + Duration::milliseconds(elapsed),
+        );
+        validate_block_at(&block, &blockchain.state, &params, &chain(), now)
+    };
+    let validate_at = |blockchain: &Blockchain, rank: usize, elapsed: i64| {
+        validate_at_time(blockchain, rank, elapsed, Utc::now())
+    };
+
+    // The next ones take the slot of the elected forger one by one, each time it's missed
+    assert_eq!(validate_at(&blockchain, 0, 1), Ok(()));
+    assert_eq!(
+        validate_at(&blockchain, 1, params.block_time_max - 1),
+        Err(BlockValidationError::NotElectedForger {
+            elected: ranking[0].hash_it(),
+            found: ranking[1].hash_it(),
+        })
+    );
+    assert_eq!(validate_at(&blockchain, 1, params.block_time_max), Ok(()));
+    assert!(validate_at(&blockchain, 2, params.block_time_max).is_err());
+    assert_eq!(
+        validate_at(&blockchain, 2, 2 * params.block_time_max),
+        Ok(())
+    );
+
+    // A slot can't be taken early with a timestamp within the drift, it must have passed for the receiving node
+    let early = genesis_timestamp + Duration::milliseconds(params.block_time_max - 1);
+    assert_eq!(
+        validate_at_time(&blockchain, 1, params.block_time_max, early),
+        Err(BlockValidationError::NotElectedForger {
+            elected: ranking[0].hash_it(),
+            found: ranking[1].hash_it(),
+        })
+    );
+
+    // The forger goes to the end of the ranking
+    let forger = forger_at(0);
+    let block = create_block(
+        &genesis_hash,
+        forger,
+        &[reward(forger, 10)],
+        genesis_timestamp + Duration::milliseconds(1),
+    );
+    assert!(matches!(
+        blockchain.import_block(&block),
+        Ok(BlockImport::Extended)
+    ));
+    assert_eq!(rank_forgers(&blockchain.state)[2], ranking[0]);
+}
+
+#[test]
+fn every_staker_is_ranked() {
+    // Find a chain whose staker's stake hash doesn't contain the first digit of the seed
+    let (staker, blockchain) = (0..10_000)
+        .map(|_| {
+            let staker = Wallet::new();
+            let blockchain = create_blockchain(&[&staker], &[], ConsensusParams::default());
+            (staker, blockchain)
+        })
+        .find(|(_, blockchain)| {
+            let seed = &blockchain.last_block_hash.as_ref().unwrap().hash;
+            blockchain
+                .state
+                .last_staking_addresses
+                .iter()
+                .all(|stake| !stake.get_hash().contains(&seed[0..1]))
+        })
+        .unwrap();
+
+    // It's ranked by the distance of it's stake hash to the seed
+    assert_eq!(rank_forgers(&blockchain.state), vec![staker.get_public()]);
+}
+
+#[test]
+fn oversized_blocks_are_rejected_on_import() {
+    let mut forger = Wallet::new();
+    let receiver = Wallet::new();
+    forger.history = 1;
+
+    let consensus = ConsensusParams {
+        max_block_size: 1000,
+        ..Default::default()
+    };
+    let mut blockchain = create_blockchain(&[&forger], &[&forger], consensus);
+    let genesis_hash = blockchain.last_block_hash.clone().unwrap();
+
+    let mut transactions = vec![reward(&forger, 10)];
+    for _ in 0..5 {
+        transactions.push(
+            TransactionBuilder::new()
+                .to_address(&receiver.get_public().hash_it())
+                .ammount(1)
+                .is_type(TransactionType::MOVEMENT)
+                .chain(&chain())
+                .with_wallet(&mut forger)
+                .build(),
+        );
+    }
+
+    let block = create_block(&genesis_hash, &forger, &transactions, Utc::now());
+    assert!(matches!(
+        blockchain.import_block(&block),
+        Err(BlockchainErrors::InvalidBlock(
+            BlockValidationError::BlockTooLarge { max: 1000, .. }
+        ))
+    ));
+
+    // Blocks of side branches are limited too
+    let small_block = create_block(&genesis_hash, &forger, &transactions[..2], Utc::now());
+    assert!(matches!(
+        blockchain.import_block(&small_block),
+        Ok(BlockImport::Extended)
+    ));
+    let block = create_block(&genesis_hash, &forger, &transactions, Utc::now());
+    assert!(matches!(
+        blockchain.import_block(&block),
+        Err(BlockchainErrors::InvalidBlock(
+            BlockValidationError::BlockTooLarge { .. }
+        ))
+    ));
+}
+
+#[test]
+fn signatures_are_bound_to_the_chain() {
+    let mut forger = Wallet::new();
+    let outsider = Wallet::new();
+    forger.history = 1;
+
+    let blockchain = create_blockchain(&[&forger], &[&forger], ConsensusParams::default());
+    let genesis_hash = blockchain.last_block_hash.clone().unwrap();
+    let params = blockchain.consensus_params();
+    assert_eq!(blockchain.chain_id(), chain());
+
+    // Same wallets, but the chain id or the name differ
+    for other_chain in [ChainId::new("venus", 2), ChainId::new("mars", 1)] {
+        let replayed_payment = TransactionBuilder::new()
+            .to_address(&outsider.get_public().hash_it())
+            .ammount(1)
+            .is_type(TransactionType::MOVEMENT)
+            .chain(&other_chain)
+            .with_wallet(&mut forger.clone())
+            .build();
+        assert!(replayed_payment.verify(&other_chain));
+        assert!(!replayed_payment.verify(&chain()));
+
+        let block = create_block(
+            &genesis_hash,
+            &forger,
+            &[reward(&forger, 10), replayed_payment.clone()],
+            Utc::now(),
+        );
+        assert_eq!(
+            validate_block(&block, &blockchain.state, &params, &chain()),
+            Err(BlockValidationError::InvalidTransaction(
+                replayed_payment.get_hash()
+            ))
+        );
+
+        let replayed_block = BlockBuilder::new()
+            .transactions(&[reward(&forger, 10)])
+            .timestamp(Utc::now())
+            .key(&forger.get_public())
+            .previous_hash(&genesis_hash)
+            .hash_it()
+            .sign_with(&forger, &other_chain)
+            .build();
+        assert_eq!(
+            validate_block(&replayed_block, &blockchain.state, &params, &chain()),
+            Err(BlockValidationError::InvalidSignature)
+        );
+    }
+
+    // Blocks can't go back to a version whose signatures weren't bound to the chain
+    let mut block = create_block(&genesis_hash, &forger, &[], Utc::now());
+    block.hash = BlockHash::with_version(
+        CHAIN_ID_HASH_VERSION - 1,
+        &block.transactions,
+        block.timestamp,
+        block.previous_hash.clone(),
+        block.key.clone(),
+    );
+    block.signature = forger.sign_data(block.hash.unite());
+    assert_eq!(
+        validate_block(&block, &blockchain.state, &params, &chain()),
+        Err(BlockValidationError::HashVersionDowngrade {
+            previous: HASH_VERSION,
+            found: CHAIN_ID_HASH_VERSION - 1,
+        })
+    );
+}
+
+#[test]
+fn malformed_transactions_are_rejected() {
+    let mut forger = Wallet::new();
+    let outsider = Wallet::new();
+    forger.history = 1;
+
+    let blockchain = create_blockchain(&[&forger], &[&forger], ConsensusParams::default());
+    let genesis_hash = blockchain.last_block_hash.clone().unwrap();
+    let params = blockchain.consensus_params();
+
+    let payment = TransactionBuilder::new()
+        .to_address(&outsider.get_public().hash_it())
+        .ammount(1)
+        .is_type(TransactionType::MOVEMENT)
+        .chain(&chain())
+        .with_wallet(&mut forger)
+        .build();
+
+    // The signature isn't hashed, so the block of a peer can carry a corrupted one
+    for corrupted_signature in [Key(vec![1, 2, 3]), Key(vec![0; 65])] {
+        let mut corrupted_payment = payment.clone();
+        if let Transaction::MOVEMENT { signature, .. } = &mut corrupted_payment {
+            *signature = corrupted_signature;
+        }
+
+        let block = create_block(
+            &genesis_hash,
+            &forger,
+            &[reward(&forger, 10), corrupted_payment.clone()],
+            Utc::now(),
+        );
+        assert_eq!(
+            validate_block(&block, &blockchain.state, &params, &chain()),
+            Err(BlockValidationError::InvalidTransaction(
+                corrupted_payment.get_hash()
+            ))
+        );
+    }
+}
@@ -1,10 +1,110 @@
-use blockchain::Configuration;
+use std::{
+    fs::File,
+    io::{
+        BufReader,
+        BufWriter,
+    },
+    sync::{
+        Arc,
+        Mutex,
+    },
+};
+
+use blockchain::{
+    Blockchain,
+    BlockchainErrors,
+    ChainSpec,
+    Configuration,
+    Key,
+    Metrics,
+    Wallet,
+};
 
 #[tokio::main]
 async fn main() {
-    let config = Configuration::new();
+    let mut config = Configuration::new();
+
+    let args = std::env::args().collect::<Vec<String>>();
+
+    match (args.get(1).map(String::as_str), args.get(2)) {
+        // Dump the whole chain into a file, optionally of the chain described by a chain spec
+        (Some("export"), Some(path)) => {
+            if let Some(chain_spec) = args.get(3) {
+                if let Err(err) = load_chain_spec(&mut config, chain_spec) {
+                    return eprintln!("Invalid chain spec: {:?}", err);
+                }
+            }
+
+            let blockchain =
+                Blockchain::new(config, Arc::new(Mutex::new(Metrics::new(vec![])))).await;
+            let mut file = BufWriter::new(File::create(path).unwrap());
+
+            match blockchain.export_chain(&mut file) {
+                Ok(blocks) => println!("Exported {} blocks into {}", blocks, path),
+                Err(err) => eprintln!("Couldn't export the chain: {:?}", err),
+            }
+        }
+        // Load a chain file into an empty node, optionally of the chain described by a chain spec
+        (Some("import"), Some(path)) => {
+            if let Some(chain_spec) = args.get(3) {
+                if let Err(err) = load_chain_spec(&mut config, chain_spec) {
+                    return eprintln!("Invalid chain spec: {:?}", err);
+                }
+            }
+
+            let mut blockchain =
+                Blockchain::new(config, Arc::new(Mutex::new(Metrics::new(vec![])))).await;
+            let mut file = BufReader::new(File::open(path).unwrap());
 
-    let mut node = node::Node::new(config).await;
+            match blockchain.import_chain(&mut file) {
+                Ok(blocks) => println!("Imported {} blocks from {}", blocks, path),
+                Err(err) => eprintln!("Couldn't import the chain: {:?}", err),
+            }
+        }
+        // Create a wallet to use in a chain spec or to run a node with
+        (Some("wallet"), _) => {
+            let wallet = Wallet::new();
+
+            println!("Private key: {}", wallet.get_private().to_hex());
+            println!("Public key: {}", wallet.get_public().to_hex());
+            println!("Address: {}", wallet.get_public().hash_it());
+        }
+        // Show the genesis block that a chain spec generates
+        (Some("genesis"), Some(path)) => {
+            match ChainSpec::from_file(path).and_then(|chain_spec| chain_spec.genesis_block()) {
+                Ok(genesis_block) => println!("Genesis hash: {}", genesis_block.hash.unite()),
+                Err(err) => eprintln!("Invalid chain spec: {:?}", err),
+            }
+        }
+        // Run a node of the chain described by a chain spec, optionally with the given private key
+        (Some("run"), Some(path)) => {
+            if let Err(err) = load_chain_spec(&mut config, path) {
+                return eprintln!("Invalid chain spec: {:?}", err);
+            }
+
+            if let Some(private_key) = args.get(3) {
+                match Key::from_hex(private_key) {
+                    Some(private_key) => config.wallet = Wallet::from_private(&private_key.0, 0),
+                    None => return eprintln!("Invalid private key"),
+                }
+            }
+
+            let mut node = node::Node::new(config).await;
+
+            node.run().await;
+        }
+        _ => {
+            let mut node = node::Node::new(config).await;
+
+            node.run().await;
+        }
+    }
+}
 
-    node.run().await;
+/*
+ * Make the node run the chain described by the chain spec in the given file
+ */
+fn load_chain_spec(config: &mut Configuration, path: &str) -> Result<(), BlockchainErrors> {
+    config.with_chain_spec(ChainSpec::from_file(path)?);
+    Ok(())
 }
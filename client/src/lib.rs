@@ -1,6 +1,10 @@
 use blockchain::{
+    AddressHistoryEntry,
     Block,
+    BlockHeader,
+    MerkleProof,
     Transaction,
+    TransactionLocation,
 };
 use jsonrpc_client_transports::{
     transports::{
@@ -100,4 +104,73 @@ impl NodeClient {
         self.0
             .call_method("add_transactions", "()", (transactions,))
     }
+
+    pub fn get_block_by_height(
+        &self,
+        height: usize,
+    ) -> impl Future<Output = RpcResult<Option<Block>>> {
+        self.0
+            .call_method("get_block_by_height", "Option<Block>", (height,))
+    }
+
+    pub fn get_blocks_range(
+        &self,
+        from: usize,
+        to: usize,
+    ) -> impl Future<Output = RpcResult<Vec<Block>>> {
+        self.0
+            .call_method("get_blocks_range", "Vec<Block>", (from, to))
+    }
+
+    pub fn get_header_by_height(
+        &self,
+        height: usize,
+    ) -> impl Future<Output = RpcResult<Option<BlockHeader>>> {
+        self.0
+            .call_method("get_header_by_height", "Option<BlockHeader>", (height,))
+    }
+
+    pub fn get_headers_range(
+        &self,
+        from: usize,
+        to: usize,
+    ) -> impl Future<Output = RpcResult<Vec<BlockHeader>>> {
+        self.0
+            .call_method("get_headers_range", "Vec<BlockHeader>", (from, to))
+    }
+
+    pub fn get_transaction(
+        &self,
+        hash: String,
+    ) -> impl Future<Output = RpcResult<Option<(Transaction, TransactionLocation)>>> {
+        self.0.call_method(
+            "get_transaction",
+            "Option<(Transaction, TransactionLocation)>",
+            (hash,),
+        )
+    }
+
+    pub fn get_transaction_proof(
+        &self,
+        hash: String,
+    ) -> impl Future<Output = RpcResult<Option<(MerkleProof, TransactionLocation)>>> {
+        self.0.call_method(
+            "get_transaction_proof",
+            "Option<(MerkleProof, TransactionLocation)>",
+            (hash,),
+        )
+    }
+
+    pub fn get_address_history(
+        &self,
+        address: String,
+        skip: usize,
+        limit: usize,
+    ) -> impl Future<Output = RpcResult<Vec<(Transaction, AddressHistoryEntry)>>> {
+        self.0.call_method(
+            "get_address_history",
+            "Vec<(Transaction, AddressHistoryEntry)>",
+            (address, skip, limit),
+        )
+    }
 }
@@ -1,4 +1,3 @@
-#![feature(async_closure)]
 use blockchain::Block;
 use futures_util::{
     SinkExt,
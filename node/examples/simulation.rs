@@ -1,10 +1,11 @@
-#![feature(slice_pattern)]
-#![feature(async_closure)]
 use blockchain::{
-    Block,
-    BlockBuilder,
+    ChainId,
+    ChainSpec,
     Configuration,
-    Transaction,
+    ConsensusParams,
+    GenesisAllocation,
+    GenesisStaker,
+    StorageBackend,
     TransactionBuilder,
     TransactionType,
     Wallet,
@@ -27,7 +28,7 @@ use tracing_subscriber::{
 fn create_configs() -> Vec<Configuration> {
     (0..5)
         .map(|i| {
-            Configuration::from_params(
+            let mut config = Configuration::from_params(
                 i,
                 5000 + i,
                 7000 + i,
@@ -35,7 +36,10 @@ fn create_configs() -> Vec<Configuration> {
                 Wallet::default(),
                 5,
                 "mars",
-            )
+            );
+            // Every simulation starts from a fresh chain
+            config.storage = StorageBackend::Memory;
+            config
         })
         .collect()
 }
@@ -59,97 +63,57 @@ async fn main() {
 
     tracing::subscriber::set_global_default(subscriber).expect("Unable to set global subscriber");
 
-    let mut node_configurations = create_configs();
+    let node_configurations = create_configs();
 
     let mut nodes_runtimes = Vec::new();
 
-    let mut genesis_wallet = Wallet::default();
-
     tracing::info!("Starting simulation");
 
-    let genesis_transaction = TransactionBuilder::new()
-        .to_address(&genesis_wallet.get_public().hash_it())
-        .ammount(200000000000)
-        .is_type(TransactionType::COINBASE)
-        .with_wallet(&mut genesis_wallet)
-        .build();
-
-    // Make a coinbase and a stake transaction fore very node
-    let mut staking_transactions = node_configurations
-        .iter_mut()
-        .flat_map(|config| {
-            vec![
-                TransactionBuilder::new()
-                    .to_address(&config.wallet.get_public().hash_it())
-                    .ammount(10)
-                    .is_type(TransactionType::MOVEMENT)
-                    .with_wallet(&mut genesis_wallet)
-                    .build(),
-                TransactionBuilder::new()
-                    .ammount(2)
-                    .is_type(TransactionType::STAKE)
-                    .with_wallet(&mut config.wallet)
-                    .build(),
-            ]
-        })
-        .collect::<Vec<Transaction>>();
-
-    let mut transactions = vec![genesis_transaction];
-    transactions.append(&mut staking_transactions);
+    let sender_wallets = (0..5).map(|_| Wallet::default()).collect::<Vec<Wallet>>();
+
+    // Every node stakes a bit, and every sender gets funds to send transactions with
+    let chain_spec = ChainSpec {
+        chain_name: "mars".to_string(),
+        chain_id: 1,
+        genesis_timestamp: Utc::now().to_rfc3339(),
+        allocations: node_configurations
+            .iter()
+            .map(|config| GenesisAllocation {
+                address: config.wallet.get_public().hash_it(),
+                ammount: 8,
+            })
+            .chain(sender_wallets.iter().map(|wallet| GenesisAllocation {
+                address: wallet.get_public().hash_it(),
+                ammount: 2000000,
+            }))
+            .collect(),
+        stakers: node_configurations
+            .iter()
+            .map(|config| GenesisStaker {
+                public_key: config.wallet.get_public().to_hex(),
+                ammount: 2,
+            })
+            .collect(),
+        consensus: ConsensusParams::default(),
+    };
 
     let mut senders_threads = Vec::new();
 
-    for _ in 0..1 {
-        for i in 0..5 {
-            let (tx, sender) = create_sender(&mut genesis_wallet, i);
-            transactions.push(tx);
-            senders_threads.push(sender);
-        }
+    for (i, sender_wallet) in sender_wallets.into_iter().enumerate() {
+        senders_threads.push(create_sender(sender_wallet, chain_spec.chain(), i as u16));
     }
 
-    let genesis_block = BlockBuilder::new()
-        .transactions(&transactions)
-        .timestamp(Utc::now())
-        .key(&genesis_wallet.get_public())
-        .hash_it()
-        .sign_with(&genesis_wallet)
-        .build();
-
-    for config in node_configurations {
-        let mongo_client = config.mongo_client.clone();
-
-        let db = mongo_client.database(&format!("db_{}", config.id));
-
-        let coll = db.collection::<Block>("blocks");
-
-        coll.drop(None).await.unwrap();
+    for mut config in node_configurations {
+        // The genesis block is created from the chain spec
+        config.with_chain_spec(chain_spec.clone());
 
         let mut node = Node::new(config.clone()).await;
-        let genesis_block = genesis_block.clone();
 
         nodes_runtimes.push(thread::spawn(move || {
             let rt = tokio::runtime::Runtime::new().unwrap();
             rt.block_on(async {
                 node.sync_from_discovery_server().await;
 
-                // Create a genesis block if there isn't
-                if node
-                    .state
-                    .lock()
-                    .unwrap()
-                    .blockchain
-                    .last_block_hash
-                    .is_none()
-                {
-                    node.state
-                        .lock()
-                        .unwrap()
-                        .blockchain
-                        .add_block(&genesis_block)
-                        .unwrap();
-                    node.state.lock().unwrap().elect_new_forger();
-                }
-
                 node.run().await;
             })
         }));
@@ -165,29 +129,25 @@ async fn main() {
     futures::future::join_all(senders_threads).await;
 }
 
-fn create_sender(genesis_wallet: &mut Wallet, i: u16) -> (Transaction, impl Future<Output = ()>) {
-    let mut sender_wallet = Wallet::default();
-
-    let transaction = TransactionBuilder::new()
-        .to_address(&sender_wallet.get_public().hash_it())
-        .ammount(2000000)
-        .is_type(TransactionType::MOVEMENT)
-        .with_wallet(genesis_wallet)
-        .build();
-
-    let sender = std::thread::spawn(async move || {
+fn create_sender(mut sender_wallet: Wallet, chain: ChainId, i: u16) -> impl Future<Output = ()> {
+    std::thread::spawn(async move || {
         let client = NodeClient::new_ws(&format!("ws://127.0.0.1:{}", 7000 + i))
             .await
             .unwrap();
 
-        let temp_wallet = Wallet::default();
+        let temp_wallets = (0..10).map(|_| Wallet::default()).collect::<Vec<Wallet>>();
+
+        for _ in 0..10000 {
+            // Build a transaction that pays every temporary wallet
+            let mut builder = TransactionBuilder::new();
+            for temp_wallet in &temp_wallets {
+                builder.output(&temp_wallet.get_public().hash_it(), 1);
+            }
 
-        for _ in 0..100000 {
-            // Build the transaction
-            let sample_tx = TransactionBuilder::new()
-                .to_address(&temp_wallet.get_public().hash_it())
-                .ammount(1)
-                .is_type(TransactionType::MOVEMENT)
+            let sample_tx = builder
+                .is_type(TransactionType::BATCH)
+                .fee(1)
+                .chain(&chain)
                 .with_wallet(&mut sender_wallet)
                 .build();
 
@@ -195,7 +155,5 @@ fn create_sender(genesis_wallet: &mut Wallet, i: u16) -> (Transaction, impl Futu
         }
     })
     .join()
-    .unwrap();
-
-    (transaction, sender)
+    .unwrap()
 }
@@ -1,4 +1,3 @@
-#![feature(async_closure)]
 use std::sync::{
     mpsc::Sender,
     Arc,
@@ -67,7 +66,7 @@ pub struct NodeState {
 impl NodeState {
     /// Calculate a new block forger given the current state of the blockchain
     pub fn elect_new_forger(&mut self) {
-        let next_forger = consensus::elect_forger(&mut self.blockchain).unwrap();
+        let next_forger = consensus::elect_forger(&self.blockchain).unwrap();
         self.next_forger = Some(next_forger);
     }
 }
@@ -104,6 +103,11 @@ impl Node {
             peers: HashMap::new(),
         }));
 
+        // Chains launched from a spec already have their first stakers
+        if config.chain_spec.is_some() {
+            state.lock().unwrap().elect_new_forger();
+        }
+
         Self { config, state }
     }
 
@@ -139,9 +143,7 @@ impl Node {
 
         let address = wallet.get_public().hash_it();
 
-        if peers.get(&address).is_some() {
-            peers.remove(&address);
-        }
+        peers.remove(&address);
 
         for (hostname, _, rpc_ws_port) in peers.values() {
             self.state
@@ -1,7 +1,15 @@
-use std::collections::HashMap;
+use std::{
+    cmp::Reverse,
+    collections::{
+        BinaryHeap,
+        HashMap,
+        HashSet,
+    },
+};
 
 use blockchain::{
     Chainstate,
+    Encode,
     Transaction,
 };
 #[derive(Default, Clone)]
@@ -37,51 +45,104 @@ impl Mempool {
     }
 
     /*
-     * Apply a vector of transactions into a temporal chainstate to make sure all of them are correct
+     * Get the pending transactions with the highest fees first,
+     * the transactions of the same sender keep the order of their history so they can be applied
      */
-    pub fn verify_veracity_of_incoming_transactions(
-        transactions: &[Transaction],
-        temporal_chainstate: &mut Chainstate,
-    ) -> bool {
-        for tx in transactions {
-            if tx.verify()
-                && temporal_chainstate.verify_transaction_ammount(tx)
-                && temporal_chainstate.verify_transaction_history(tx)
-            {
-                temporal_chainstate.effect_transaction(tx);
-            } else {
-                return false;
+    pub fn transactions_by_fee(&self) -> Vec<Transaction> {
+        let mut senders: HashMap<&str, Vec<&Transaction>> = HashMap::new();
+
+        for tx in self.pending_transactions.values() {
+            senders.entry(sender_of(tx)).or_default().push(tx);
+        }
+
+        // Next transaction of every sender, the ones with the lowest history are kept at the end
+        let mut next_transactions = BinaryHeap::new();
+
+        for (sender, transactions) in senders.iter_mut() {
+            transactions.sort_by_key(|tx| Reverse(tx.get_history()));
+
+            if let Some(tx) = transactions.last() {
+                next_transactions.push((tx.get_fee(), Reverse(tx.get_history()), *sender));
             }
         }
-        true
+
+        let mut sorted_transactions = Vec::new();
+
+        while let Some((_, _, sender)) = next_transactions.pop() {
+            let transactions = senders.get_mut(sender).unwrap();
+            sorted_transactions.push(transactions.pop().unwrap().clone());
+
+            if let Some(tx) = transactions.last() {
+                next_transactions.push((tx.get_fee(), Reverse(tx.get_history()), sender));
+            }
+        }
+
+        sorted_transactions
     }
 
     /*
-     * Apply a vector of transactions (up to 500) into a temporal chainstate and separeate the correct ones from the bad
+     * Apply a vector of transactions (up to the maximum of a block) into the chainstate and separeate the correct ones from the bad,
+     * the chainstate is reverted afterwards.
+     * `block_size` is the size in bytes of the block without the pending transactions, each one adds it's own encoding.
      */
     pub fn verify_veracity_of_transactions(
         pending_transactions: &[Transaction],
-        temporal_chainstate: &mut Chainstate,
+        chainstate: &mut Chainstate,
+        block_size: usize,
     ) -> (Vec<Transaction>, Vec<Transaction>) {
+        let consensus = chainstate.consensus_params();
+        let mut undo = chainstate.begin_undo();
         let mut ok_txs = Vec::new();
         let mut bad_txs = Vec::new();
+        let mut block_size = block_size;
+        // Senders with a transaction left for the next block, their following transactions must wait too
+        let mut skipped_senders = HashSet::new();
 
         for tx in pending_transactions {
-            if ok_txs.len() > 700 {
+            if ok_txs.len() >= consensus.max_block_transactions {
                 break;
             }
 
+            let sender = sender_of(tx);
+            if skipped_senders.contains(sender) {
+                continue;
+            }
+
+            // Transactions that don't fit anymore are left for the next block
+            let tx_size = tx.to_bytes().len();
+            if block_size + tx_size > consensus.max_block_size {
+                skipped_senders.insert(sender);
+                continue;
+            }
+
             // Make sure the funds are enough and the history is accurate
-            if temporal_chainstate.verify_transaction_ammount(tx)
-                && temporal_chainstate.verify_transaction_history(tx)
+            if chainstate.verify_transaction_ammount(tx)
+                && chainstate.verify_transaction_history(tx)
             {
-                temporal_chainstate.effect_transaction(tx);
+                chainstate.effect_transaction_journaled(tx, &mut undo);
                 ok_txs.push(tx.clone());
+                block_size += tx_size;
             } else {
                 bad_txs.push(tx.clone());
             }
         }
 
+        chainstate.revert_block(undo);
         (ok_txs, bad_txs)
     }
 }
+
+/*
+ * Address whose history the transaction follows, coinbases don't have a sender so they are their own
+ */
+fn sender_of(tx: &Transaction) -> &str {
+    match tx {
+        Transaction::MOVEMENT { from_address, .. }
+        | Transaction::STAKE { from_address, .. }
+        | Transaction::UNSTAKE { from_address, .. }
+        | Transaction::DELEGATE { from_address, .. }
+        | Transaction::UNDELEGATE { from_address, .. }
+        | Transaction::BATCH { from_address, .. } => from_address,
+        Transaction::COINBASE { hash, .. } => hash,
+    }
+}
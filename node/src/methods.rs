@@ -1,10 +1,17 @@
 mod add_block;
 mod add_transaction;
 mod get_address_ammount;
+mod get_address_history;
+mod get_block_by_height;
 mod get_block_with_hash;
 mod get_block_with_prev_hash;
+mod get_blocks_range;
 mod get_chain_length;
+mod get_header_by_height;
+mod get_headers_range;
 mod get_node_address;
+mod get_transaction;
+mod get_transaction_proof;
 mod make_handshake;
 
 pub use add_block::add_block;
@@ -13,8 +20,68 @@ pub use add_transaction::{
     TransactionResult,
 };
 pub use get_address_ammount::get_address_ammount;
+pub use get_address_history::get_address_history;
+pub use get_block_by_height::get_block_by_height;
 pub use get_block_with_hash::get_block_with_hash;
 pub use get_block_with_prev_hash::get_block_with_prev_hash;
+pub use get_blocks_range::get_blocks_range;
 pub use get_chain_length::get_chain_length;
+pub use get_header_by_height::get_header_by_height;
+pub use get_headers_range::get_headers_range;
 pub use get_node_address::get_node_address;
+pub use get_transaction::get_transaction;
+pub use get_transaction_proof::get_transaction_proof;
 pub use make_handshake::make_handshake;
+
+use blockchain::{
+    BlockchainErrors,
+    MAX_RANGE,
+};
+use jsonrpc_http_server::jsonrpc_core::{
+    serde_json,
+    Error,
+    ErrorCode,
+};
+
+/// RPC error code returned when the requested data was pruned from the node
+pub const PRUNED_DATA_ERROR: i64 = 1;
+
+/// RPC error code returned when a block is rejected, the reason is sent as the error data
+pub const INVALID_BLOCK_ERROR: i64 = 2;
+
+/// RPC error code returned when a range starts before the first block or it's too long
+pub const INVALID_RANGE_ERROR: i64 = 3;
+
+/*
+ * Turn a blockchain error into an RPC error
+ */
+pub fn blockchain_error(err: BlockchainErrors) -> Error {
+    match err {
+        BlockchainErrors::PrunedBlock(height) => Error {
+            code: ErrorCode::ServerError(PRUNED_DATA_ERROR),
+            message: format!(
+                "The block at height {} has been pruned from this node, an archive node is needed",
+                height
+            ),
+            data: None,
+        },
+        BlockchainErrors::InvalidRange(from, to) => Error {
+            code: ErrorCode::ServerError(INVALID_RANGE_ERROR),
+            message: format!(
+                "The range from {} to {} is invalid, it must start at 1 and have up to {} blocks",
+                from, to, MAX_RANGE
+            ),
+            data: None,
+        },
+        BlockchainErrors::InvalidBlock(reason) => Error {
+            code: ErrorCode::ServerError(INVALID_BLOCK_ERROR),
+            message: format!("The block is invalid: {:?}", reason),
+            data: serde_json::to_value(reason).ok(),
+        },
+        err => Error {
+            code: ErrorCode::InternalError,
+            message: format!("{:?}", err),
+            data: None,
+        },
+    }
+}
@@ -3,97 +3,82 @@ use std::sync::{
     Mutex,
 };
 
-use crate::{
-    mempool::Mempool,
-    NodeState,
-};
+use super::blockchain_error;
+use crate::NodeState;
 use blockchain::{
     Block,
-    PublicAddress,
+    BlockImport,
+    BlockchainErrors,
 };
 use client::NodeClient;
+use jsonrpc_http_server::jsonrpc_core::*;
 
-pub async fn add_block(state: &Arc<Mutex<NodeState>>, block: Block) {
-    let is_block_ok = {
-        /*
-         * Make sure the the signer is the block creator by verifying the block
-         * signature using the creator's public key.
-         * Also, make sure of the veracity of the incoming transactions by checking
-         * that it's hash, history, and ammount are correct according the current chainstate.
-         * If not, the block will be saved into the lost blocks list, and everytime there is a new incoming block,
-         * this lost block will be tried to be added. Having a lost block might be due to latency.
-         */
-        if block.verify_sign_with(&PublicAddress::from(&block.key)) {
-            let mut chainstate = state.lock().unwrap().blockchain.state.clone();
-            Mempool::verify_veracity_of_incoming_transactions(&block.transactions, &mut chainstate)
-        } else {
-            false
-        }
-    };
+pub async fn add_block(state: &Arc<Mutex<NodeState>>, block: Block) -> Result<()> {
+    /*
+     * The blockchain validates the block (hash, signature, coinbase, forger, timestamp, size and transactions)
+     * against the chainstate of the branch the block belongs to, invalid blocks are rejected with the reason.
+     * The blockchain makes sure of the veracity of the incoming transactions by checking that it's hash, history,
+     * and ammount are correct according the chainstate of the branch the block belongs to.
+     * If the previous block is unknown, the block will be saved into the lost blocks list, and everytime there is a new incoming block,
+     * this lost block will be tried to be added. Having a lost block might be due to latency.
+     */
+    let import = state.lock().unwrap().blockchain.import_block(&block);
 
-    if is_block_ok {
-        if state
-            .lock()
-            .unwrap()
-            .blockchain
-            .add_block(&block.clone())
-            .is_ok()
-        {
+    let result = match import {
+        Ok(import) => {
             let mut state = state.lock().unwrap();
-            // Elect the next forger
-            state.elect_new_forger();
+            on_block_imported(&mut state, &block, import);
+            Ok(())
+        }
+        Err(BlockchainErrors::UnknownPreviousBlock(prev_hash)) => {
+            let state = state.clone();
 
-            // Remove the block transactions from the mempool
-            for tx in &block.transactions {
-                state.mempool.remove_transaction(&tx.get_hash())
+            let peers = state.lock().unwrap().peers.clone();
+
+            // Ask known peers for the missing block
+            for (hostname, rpc_port, _) in peers.values() {
+                let hostname = hostname.clone();
+                let rpc_port = *rpc_port;
+
+                let client = NodeClient::new(&format!("http://{}:{}", hostname, rpc_port))
+                    .await
+                    .unwrap();
+
+                let remote_block = client.get_block_with_hash(prev_hash.clone()).await;
+
+                if let Ok(Some(block)) = remote_block {
+                    state
+                        .lock()
+                        .unwrap()
+                        .lost_blocks
+                        .insert(block.hash.unite(), block);
+                    break;
+                }
             }
+
+            // Added the new block to the lost queue
+            state
+                .lock()
+                .unwrap()
+                .lost_blocks
+                .insert(block.hash.unite(), block);
+
+            tracing::warn!(
+                "(Node.{}) Tried to add a block without it's previous block.",
+                state.lock().unwrap().id
+            );
+            Ok(())
         }
-    } else {
-        let state = state.clone();
-
-        let peers = state.lock().unwrap().peers.clone();
-        let prev_hash = state
-            .lock()
-            .unwrap()
-            .blockchain
-            .last_block_hash
-            .as_ref()
-            .unwrap()
-            .unite();
-
-        // Ask known peers for the missing block
-        for (hostname, rpc_port, _) in peers.values() {
-            let hostname = hostname.clone();
-            let rpc_port = *rpc_port;
-
-            let client = NodeClient::new(&format!("http://{}:{}", hostname, rpc_port))
-                .await
-                .unwrap();
-
-            let remote_block = client.get_block_with_prev_hash(prev_hash.clone()).await;
-
-            if let Ok(Some(block)) = remote_block {
-                state
-                    .lock()
-                    .unwrap()
-                    .lost_blocks
-                    .insert(block.hash.unite(), block);
-                break;
-            }
+        Err(err) => {
+            tracing::warn!(
+                "(Node.{}) Rejected the block ({}): {:?}",
+                state.lock().unwrap().id,
+                block.hash.unite(),
+                err
+            );
+            Err(blockchain_error(err))
         }
-
-        // Added the new block to the lost queue
-        state
-            .lock()
-            .unwrap()
-            .lost_blocks
-            .insert(block.hash.unite(), block);
-
-        tracing::warn!(
-            "(Node.{}) Tried to add a broken block.",
-            state.lock().unwrap().id
-        );
-    }
+    };
 
     let mut state = state.lock().unwrap();
 
@@ -102,36 +87,76 @@ pub async fn add_block(state: &Arc<Mutex<NodeState>>, block: Block) {
      * This tries to append previously lost blocks (probably due to latency) into the chain
      */
     if !state.lost_blocks.is_empty() {
-        let mut blocks_iter = state.lost_blocks.clone().into_iter().peekable();
-        let mut blocks = state.lost_blocks.clone();
-        let mut any_recovered_block = false;
-
-        while blocks_iter.peek().is_some() {
-            let (_, block) = blocks_iter.next().unwrap();
-            let is_block_ok = state.blockchain.add_block(&block.clone()).is_ok();
-            if is_block_ok {
-                blocks.remove(&block.hash.unite());
-                blocks_iter = blocks.clone().into_iter().peekable();
-
-                any_recovered_block = true;
-
-                // Remove confirmed transactions from the mempool
-                for tx in &block.transactions {
-                    state.mempool.remove_transaction(&tx.get_hash());
+        let mut any_recovered_block = true;
+
+        while any_recovered_block {
+            any_recovered_block = false;
+
+            for (hash, block) in state.lost_blocks.clone() {
+                match state.blockchain.import_block(&block) {
+                    Ok(import) => {
+                        state.lost_blocks.remove(&hash);
+                        on_block_imported(&mut state, &block, import);
+                        any_recovered_block = true;
+                    }
+                    // Keep waiting for the previous block
+                    Err(BlockchainErrors::UnknownPreviousBlock(_)) => {}
+                    // The block will never be valid
+                    Err(err) => {
+                        tracing::warn!(
+                            "(Node.{}) Rejected the lost block ({}): {:?}",
+                            state.id,
+                            hash,
+                            err
+                        );
+                        state.lost_blocks.remove(&hash);
+                    }
                 }
             }
         }
 
-        // If any block has been recovered then elect a new forger
-        if any_recovered_block {
-            state.elect_new_forger();
-            state.lost_blocks = blocks;
-        }
-
         tracing::warn!(
             "(Node.{}) Length of lost blocks is <{}>",
             state.id,
             state.lost_blocks.len()
         );
     }
+
+    result
+}
+
+/*
+ * Update the mempool and elect the next forger once the main chain changed
+ */
+fn on_block_imported(state: &mut NodeState, block: &Block, import: BlockImport) {
+    match import {
+        BlockImport::Extended => {
+            // Remove the block transactions from the mempool
+            for tx in &block.transactions {
+                state.mempool.remove_transaction(&tx.get_hash())
+            }
+
+            // Elect the next forger
+            state.elect_new_forger();
+        }
+        BlockImport::Reorganized {
+            applied_blocks,
+            abandoned_transactions,
+            ..
+        } => {
+            // The transactions of the abandoned blocks can be confirmed again
+            for tx in &abandoned_transactions {
+                state.mempool.add_transaction(tx);
+            }
+
+            for applied_block in &applied_blocks {
+                for tx in &applied_block.transactions {
+                    state.mempool.remove_transaction(&tx.get_hash())
+                }
+            }
+
+            state.elect_new_forger();
+        }
+        BlockImport::SideBranch | BlockImport::AlreadyKnown => {}
+    }
 }
@@ -4,32 +4,25 @@ use crate::{
     ThreadMsg,
 };
 use blockchain::{
+    validate_block,
     BlockBuilder,
+    Encode,
     Transaction,
     TransactionBuilder,
     TransactionType,
+    Wallet,
 };
-use chrono::{
-    DateTime,
-    Utc,
-};
+use chrono::Utc;
 use jsonrpc_http_server::jsonrpc_core::*;
 use serde::{
     Deserialize,
     Serialize,
 };
-use std::{
-    str::FromStr,
-    sync::{
-        Arc,
-        Mutex,
-    },
+use std::sync::{
+    Arc,
+    Mutex,
 };
 
-static BLOCK_TIME_MAX: i64 = 8000;
-static MINIMUM_MEMPOOL_SIZE: usize = 100;
-static TRANSACTIONS_CHUNK_SIZE: usize = 4;
-
 #[derive(Serialize, Deserialize)]
 pub enum TransactionResult {
     Verified,
@@ -50,8 +43,9 @@ pub async fn add_transaction(state: &Arc<Mutex<NodeState>>, transaction: Transac
         return;
     }
 
-    // Make the transaction signature, hash... are ok and that the funds can be spent
-    let tx_verification_is_ok = transaction.verify()
+    // Make the transaction signature (for this chain), hash... are ok and that the funds can be spent
+    let chain = state.lock().unwrap().blockchain.chain_id();
+    let tx_verification_is_ok = transaction.verify(&chain)
         && state
             .lock()
             .unwrap()
@@ -62,6 +56,17 @@ pub async fn add_transaction(state: &Arc<Mutex<NodeState>>, transaction: Transac
     if tx_verification_is_ok {
         let mut state = state.lock().unwrap();
 
+        let consensus = state.blockchain.consensus_params();
+
+        // How transactions are propagated and batched into blocks is up to each node
+        let (min_block_transactions, transactions_chunk_size) = {
+            let config = state.blockchain.config.lock().unwrap();
+            (
+                config.min_block_transactions,
+                config.transactions_chunk_size,
+            )
+        };
+
         // Add the transaction to the memory pool
         state.mempool.add_transaction(&transaction);
 
@@ -69,7 +74,7 @@ pub async fn add_transaction(state: &Arc<Mutex<NodeState>>, transaction: Transac
         state.mempool.chunked_transactions.push(transaction);
 
         // Propagate transactions as chunks
-        if state.mempool.chunked_transactions.len() > TRANSACTIONS_CHUNK_SIZE {
+        if state.mempool.chunked_transactions.len() > transactions_chunk_size {
             // Propagate the transactions chunk to known peers
             let transaction_senders = state.transaction_senders.clone();
             for tx_sender in transaction_senders {
@@ -85,62 +90,93 @@ pub async fn add_transaction(state: &Arc<Mutex<NodeState>>, transaction: Transac
 
         let mempool_len = state.mempool.pending_transactions.len();
 
-        // Minimum transactions per block are harcoded for now
-        if mempool_len > MINIMUM_MEMPOOL_SIZE {
+        // Minimum transactions per block are set by the node configuration
+        if mempool_len > min_block_transactions {
             let elected_forger = state.next_forger.as_ref().unwrap().hash_it();
 
             // Only the elected forger can create new blocks
             if elected_forger == state.wallet.get_public().hash_it() {
-                // Transform the pending transactions from a hashmap into a vector
-                let mut pending_transactions = state
-                    .mempool
-                    .pending_transactions
-                    .values()
-                    .cloned()
-                    .collect::<Vec<Transaction>>();
+                // Transactions paying higher fees go first
+                let pending_transactions = state.mempool.transactions_by_fee();
 
-                // Sort transactions from lower history to higher
-                pending_transactions.sort_by_key(|tx| tx.get_history());
+                let reward_tx = |ammount: u64, wallet: &mut Wallet| {
+                    TransactionBuilder::new()
+                        .to_address(&wallet.get_public().hash_it())
+                        .ammount(ammount)
+                        .is_type(TransactionType::COINBASE)
+                        .with_wallet(wallet)
+                        .build()
+                };
+
+                let previous_hash = state.blockchain.last_block_hash.clone().unwrap();
+                let chain = state.blockchain.chain_id();
+                let forge_block = |transactions: &[Transaction], wallet: &Wallet| {
+                    BlockBuilder::new()
+                        .transactions(transactions)
+                        .timestamp(Utc::now())
+                        .key(&wallet.get_public())
+                        .previous_hash(&previous_hash)
+                        .hash_it()
+                        .sign_with(wallet, &chain)
+                        .build()
+                };
+
+                // Size of the block with only the reward, the pending transactions must fit in the rest
+                let block_size = forge_block(
+                    &[reward_tx(consensus.block_reward, &mut state.wallet)],
+                    &state.wallet,
+                )
+                .to_bytes()
+                .len();
 
                 // Only get transactions that can be applied in the current chainstate (funds and history are ok)
-                let mut chainstate = state.blockchain.state.clone();
-                let (mut ok_txs, mut bad_txs) = Mempool::verify_veracity_of_transactions(
+                let (mut ok_txs, bad_txs) = Mempool::verify_veracity_of_transactions(
                     &pending_transactions,
-                    &mut chainstate,
+                    &mut state.blockchain.state,
+                    block_size,
                 );
 
-                // Make sure there is still a the minimum ammount of valid transactions to create a mempool
-                if true {
-                    // Coinbase transaction sent to the block forger as a reward
-                    let reward_tx = TransactionBuilder::new()
-                        .to_address(&state.wallet.get_public().hash_it())
-                        .ammount(10)
-                        .is_type(TransactionType::COINBASE)
-                        .with_wallet(&mut state.wallet)
-                        .build();
-
-                    // Also add the block forging reward to the block
-                    ok_txs.push(reward_tx);
+                // Bad transactions will never be valid, so they are removed from the mempool either way
+                for tx in &bad_txs {
+                    state.mempool.remove_transaction(&tx.get_hash());
+                }
 
-                    let new_block = BlockBuilder::new()
-                        .transactions(&ok_txs)
-                        .timestamp(Utc::now())
-                        .key(&state.wallet.get_public())
-                        .previous_hash(&state.blockchain.last_block_hash.clone().unwrap())
-                        .hash_it()
-                        .sign_with(&state.wallet)
-                        .build();
+                // Make sure there is still the minimum ammount of valid transactions to create a block
+                if ok_txs.len() >= min_block_transactions {
+                    // The forger is rewarded with the fees of the transactions too
+                    let reward = ok_txs
+                        .iter()
+                        .map(|tx| tx.get_fee())
+                        .fold(consensus.block_reward, u64::saturating_add);
+
+                    // The block forging reward must be the first transaction of the block
+                    ok_txs.insert(0, reward_tx(reward, &mut state.wallet));
+
+                    let new_block = forge_block(&ok_txs, &state.wallet);
+
+                    // The forged block must pass the same validation as the blocks of the peers
+                    if let Err(err) =
+                        validate_block(&new_block, &state.blockchain.state, &consensus, &chain)
+                    {
+                        tracing::error!("(Node.{}) Forged an invalid block: {:?}", state.id, err);
+                        return;
+                    }
 
-                    // Add the block to the blockchain
-                    state.blockchain.add_block(&new_block).unwrap();
+                    // Add the block to the blockchain, it must be persisted before announcing it
+                    if let Err(err) = state.blockchain.add_block(&new_block) {
+                        tracing::error!(
+                            "(Node.{}) Couldn't add the forged block: {:?}",
+                            state.id,
+                            err
+                        );
+                        return;
+                    }
 
                     state.blockchain.state.last_forger_was_blocked = false;
 
                     state.elect_new_forger();
 
-                    ok_txs.append(&mut bad_txs);
-
-                    // Remove all good and bad transactions from the mempool
+                    // Remove the confirmed transactions from the mempool
                     for tx in ok_txs {
                         state.mempool.remove_transaction(&tx.get_hash());
                     }
@@ -188,9 +224,8 @@ pub async fn add_transaction(state: &Arc<Mutex<NodeState>>, transaction: Transac
                      * since the time from the last block hasn't change.
                      */
                     if !last_forger_was_blocked {
-                        let last_block = state.blockchain.chain.last().unwrap();
-                        let last_block_time: DateTime<Utc> =
-                            DateTime::from_str(&last_block.timestamp).unwrap();
+                        let last_block = state.blockchain.peek().unwrap();
+                        let last_block_time = last_block.timestamp;
 
                         let current_time = Utc::now();
 
@@ -198,7 +233,7 @@ pub async fn add_transaction(state: &Arc<Mutex<NodeState>>, transaction: Transac
 
                         // Punish the forger if he missed for configured time
 
-                        if time_diff.num_milliseconds() > BLOCK_TIME_MAX {
+                        if time_diff.num_milliseconds() > consensus.block_time_max {
                             // Block creation timeout
                             let block_index = state.blockchain.index;
                             state
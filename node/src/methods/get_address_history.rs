@@ -0,0 +1,29 @@
+use super::blockchain_error;
+use crate::NodeState;
+use blockchain::{
+    AddressHistoryEntry,
+    Transaction,
+};
+use jsonrpc_http_server::jsonrpc_core::*;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+/*
+ * Return the confirmed transactions of an address, from the oldest to the newest, up to `MAX_RANGE` at once
+ */
+pub fn get_address_history(
+    state: &Arc<Mutex<NodeState>>,
+    address: String,
+    skip: usize,
+    limit: usize,
+) -> Result<Vec<(Transaction, AddressHistoryEntry)>> {
+    let res = state
+        .lock()
+        .unwrap()
+        .blockchain
+        .get_address_history(&address, skip, limit);
+
+    res.map_err(blockchain_error)
+}
@@ -0,0 +1,14 @@
+use super::blockchain_error;
+use crate::NodeState;
+use blockchain::Block;
+use jsonrpc_http_server::jsonrpc_core::*;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+pub fn get_block_by_height(state: &Arc<Mutex<NodeState>>, height: usize) -> Result<Option<Block>> {
+    let res = state.lock().unwrap().blockchain.get_block_by_height(height);
+
+    res.map_err(blockchain_error)
+}
@@ -1,3 +1,4 @@
+use super::blockchain_error;
 use crate::NodeState;
 use blockchain::Block;
 use jsonrpc_http_server::jsonrpc_core::*;
@@ -6,16 +7,8 @@ use std::sync::{
     Mutex,
 };
 
-pub async fn get_block_with_hash(
-    state: &Arc<Mutex<NodeState>>,
-    hash: String,
-) -> Result<Option<Block>> {
-    let res = state
-        .lock()
-        .unwrap()
-        .blockchain
-        .get_block_with_hash(hash)
-        .await;
+pub fn get_block_with_hash(state: &Arc<Mutex<NodeState>>, hash: String) -> Result<Option<Block>> {
+    let res = state.lock().unwrap().blockchain.get_block_with_hash(hash);
 
-    Ok(res)
+    res.map_err(blockchain_error)
 }
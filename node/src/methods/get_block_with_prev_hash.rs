@@ -1,3 +1,4 @@
+use super::blockchain_error;
 use crate::NodeState;
 use blockchain::Block;
 use jsonrpc_http_server::jsonrpc_core::*;
@@ -6,7 +7,7 @@ use std::sync::{
     Mutex,
 };
 
-pub async fn get_block_with_prev_hash(
+pub fn get_block_with_prev_hash(
     state: &Arc<Mutex<NodeState>>,
     previous_hash: String,
 ) -> Result<Option<Block>> {
@@ -14,8 +15,7 @@ pub async fn get_block_with_prev_hash(
         .lock()
         .unwrap()
         .blockchain
-        .get_block_with_prev_hash(previous_hash)
-        .await;
+        .get_block_with_prev_hash(previous_hash);
 
-    Ok(res)
+    res.map_err(blockchain_error)
 }
@@ -0,0 +1,18 @@
+use crate::NodeState;
+use blockchain::BlockHeader;
+use jsonrpc_http_server::jsonrpc_core::*;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+pub fn get_header_by_height(
+    state: &Arc<Mutex<NodeState>>,
+    height: usize,
+) -> Result<Option<BlockHeader>> {
+    Ok(state
+        .lock()
+        .unwrap()
+        .blockchain
+        .get_header_by_height(height))
+}
@@ -0,0 +1,18 @@
+use super::blockchain_error;
+use crate::NodeState;
+use blockchain::BlockHeader;
+use jsonrpc_http_server::jsonrpc_core::*;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+pub fn get_headers_range(
+    state: &Arc<Mutex<NodeState>>,
+    from: usize,
+    to: usize,
+) -> Result<Vec<BlockHeader>> {
+    let res = state.lock().unwrap().blockchain.get_headers_range(from, to);
+
+    res.map_err(blockchain_error)
+}
@@ -0,0 +1,20 @@
+use super::blockchain_error;
+use crate::NodeState;
+use blockchain::{
+    Transaction,
+    TransactionLocation,
+};
+use jsonrpc_http_server::jsonrpc_core::*;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+pub fn get_transaction(
+    state: &Arc<Mutex<NodeState>>,
+    hash: String,
+) -> Result<Option<(Transaction, TransactionLocation)>> {
+    let res = state.lock().unwrap().blockchain.get_transaction(&hash);
+
+    res.map_err(blockchain_error)
+}
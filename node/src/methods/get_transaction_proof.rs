@@ -0,0 +1,24 @@
+use super::blockchain_error;
+use crate::NodeState;
+use blockchain::{
+    MerkleProof,
+    TransactionLocation,
+};
+use jsonrpc_http_server::jsonrpc_core::*;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+pub fn get_transaction_proof(
+    state: &Arc<Mutex<NodeState>>,
+    hash: String,
+) -> Result<Option<(MerkleProof, TransactionLocation)>> {
+    let res = state
+        .lock()
+        .unwrap()
+        .blockchain
+        .get_transaction_proof(&hash);
+
+    res.map_err(blockchain_error)
+}
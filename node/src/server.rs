@@ -1,24 +1,35 @@
 use blockchain::{
+    AddressHistoryEntry,
     Block,
+    BlockHeader,
+    MerkleProof,
     Transaction,
+    TransactionLocation,
 };
 use jsonrpc_derive::rpc;
 
 use crate::methods::{
     add_block,
     get_address_ammount,
+    get_address_history,
+    get_block_by_height,
     get_block_with_hash,
     get_block_with_prev_hash,
+    get_blocks_range,
     get_chain_length,
+    get_header_by_height,
+    get_headers_range,
     get_node_address,
+    get_transaction,
+    get_transaction_proof,
     make_handshake,
 };
 use client::{
     HandshakeRequest,
     NodeClient,
 };
-use futures::executor::block_on;
 use jsonrpc_core::{
+    BoxFuture,
     IoHandler,
     Result,
 };
@@ -148,7 +159,7 @@ pub trait RpcMethods {
     fn add_transaction(&self, transaction: Transaction) -> Result<()>;
 
     #[rpc(name = "add_block")]
-    fn add_block(&self, block: Block) -> Result<()>;
+    fn add_block(&self, block: Block) -> BoxFuture<Result<()>>;
 
     #[rpc(name = "get_block_with_prev_hash")]
     fn get_block_with_prev_hash(&self, prev_hash: String) -> Result<Option<Block>>;
@@ -164,6 +175,35 @@ pub trait RpcMethods {
 
     #[rpc(name = "add_transactions")]
     fn add_transactions(&self, transactions: Vec<Transaction>) -> Result<()>;
+
+    #[rpc(name = "get_block_by_height")]
+    fn get_block_by_height(&self, height: usize) -> Result<Option<Block>>;
+
+    #[rpc(name = "get_blocks_range")]
+    fn get_blocks_range(&self, from: usize, to: usize) -> Result<Vec<Block>>;
+
+    #[rpc(name = "get_header_by_height")]
+    fn get_header_by_height(&self, height: usize) -> Result<Option<BlockHeader>>;
+
+    #[rpc(name = "get_headers_range")]
+    fn get_headers_range(&self, from: usize, to: usize) -> Result<Vec<BlockHeader>>;
+
+    #[rpc(name = "get_transaction")]
+    fn get_transaction(&self, hash: String) -> Result<Option<(Transaction, TransactionLocation)>>;
+
+    #[rpc(name = "get_transaction_proof")]
+    fn get_transaction_proof(
+        &self,
+        hash: String,
+    ) -> Result<Option<(MerkleProof, TransactionLocation)>>;
+
+    #[rpc(name = "get_address_history")]
+    fn get_address_history(
+        &self,
+        address: String,
+        skip: usize,
+        limit: usize,
+    ) -> Result<Vec<(Transaction, AddressHistoryEntry)>>;
 }
 
 pub struct RpcManager {
@@ -213,18 +253,15 @@ impl RpcMethods for RpcManager {
         Ok(())
     }
 
-    /// Try to add a block to the blockchain
-    fn add_block(&self, block: Block) -> Result<()> {
+    /// Try to add a block to the blockchain, invalid blocks are rejected with the reason
+    fn add_block(&self, block: Block) -> BoxFuture<Result<()>> {
         let state = self.state.clone();
-        tokio::spawn(async move {
-            add_block(&state, block).await;
-        });
-        Ok(())
+        Box::pin(async move { add_block(&state, block).await })
     }
 
     /// Get a block by it's previous hash
     fn get_block_with_prev_hash(&self, prev_hash: String) -> Result<Option<Block>> {
-        block_on(get_block_with_prev_hash(&self.state, prev_hash))
+        get_block_with_prev_hash(&self.state, prev_hash)
     }
 
     /// Return the Node's address
@@ -239,7 +276,50 @@ impl RpcMethods for RpcManager {
 
     /// Get a block by the given hash
     fn get_block_with_hash(&self, hash: String) -> Result<Option<Block>> {
-        block_on(get_block_with_hash(&self.state, hash))
+        get_block_with_hash(&self.state, hash)
+    }
+
+    /// Get the block at the given height
+    fn get_block_by_height(&self, height: usize) -> Result<Option<Block>> {
+        get_block_by_height(&self.state, height)
+    }
+
+    /// Get the blocks from the height `from` up to `to` (both included)
+    fn get_blocks_range(&self, from: usize, to: usize) -> Result<Vec<Block>> {
+        get_blocks_range(&self.state, from, to)
+    }
+
+    /// Get the header of the block at the given height
+    fn get_header_by_height(&self, height: usize) -> Result<Option<BlockHeader>> {
+        get_header_by_height(&self.state, height)
+    }
+
+    /// Get the headers from the height `from` up to `to` (both included)
+    fn get_headers_range(&self, from: usize, to: usize) -> Result<Vec<BlockHeader>> {
+        get_headers_range(&self.state, from, to)
+    }
+
+    /// Get a confirmed transaction by it's hash and the block where it was confirmed
+    fn get_transaction(&self, hash: String) -> Result<Option<(Transaction, TransactionLocation)>> {
+        get_transaction(&self.state, hash)
+    }
+
+    /// Get a Merkle inclusion proof of a confirmed transaction and the block where it was confirmed
+    fn get_transaction_proof(
+        &self,
+        hash: String,
+    ) -> Result<Option<(MerkleProof, TransactionLocation)>> {
+        get_transaction_proof(&self.state, hash)
+    }
+
+    /// Get a page of the confirmed transactions of an address
+    fn get_address_history(
+        &self,
+        address: String,
+        skip: usize,
+        limit: usize,
+    ) -> Result<Vec<(Transaction, AddressHistoryEntry)>> {
+        get_address_history(&self.state, address, skip, limit)
     }
 }
 
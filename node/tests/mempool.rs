@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use blockchain::{
+    ChainId,
+    Chainstate,
+    ConsensusParams,
+    Encode,
+    MemoryStore,
+    Transaction,
+    TransactionBuilder,
+    TransactionType,
+    Wallet,
+};
+use node::mempool::Mempool;
+
+fn payment(sender: &mut Wallet, fee: u64) -> Transaction {
+    TransactionBuilder::new()
+        .to_address(&Wallet::new().get_public().hash_it())
+        .ammount(1)
+        .is_type(TransactionType::MOVEMENT)
+        .fee(fee)
+        .chain(&ChainId::new("venus", 1))
+        .with_wallet(sender)
+        .build()
+}
+
+#[test]
+fn transactions_with_higher_fees_go_first() {
+    let mut sender_a = Wallet::new();
+    let mut sender_b = Wallet::new();
+
+    let a_0 = payment(&mut sender_a, 1);
+    let a_1 = payment(&mut sender_a, 9);
+    let b_0 = payment(&mut sender_b, 5);
+    let b_1 = payment(&mut sender_b, 2);
+
+    let mut mempool = Mempool::default();
+    for tx in [&a_1, &b_1, &a_0, &b_0] {
+        mempool.add_transaction(tx);
+    }
+
+    // The second transaction of a sender can't go before it's first one, even if it pays more
+    let hashes = mempool
+        .transactions_by_fee()
+        .iter()
+        .map(|tx| tx.get_hash())
+        .collect::<Vec<String>>();
+
+    assert_eq!(
+        hashes,
+        vec![
+            b_0.get_hash(),
+            b_1.get_hash(),
+            a_0.get_hash(),
+            a_1.get_hash()
+        ]
+    );
+}
+
+#[test]
+fn transactions_left_for_the_next_block_hold_back_their_sender() {
+    let mut sender_a = Wallet::new();
+    let mut sender_b = Wallet::new();
+
+    let mut batch_builder = TransactionBuilder::new();
+    for _ in 0..20 {
+        batch_builder.output(&Wallet::new().get_public().hash_it(), 1);
+    }
+    let a_0 = batch_builder
+        .is_type(TransactionType::BATCH)
+        .chain(&ChainId::new("venus", 1))
+        .with_wallet(&mut sender_a)
+        .build();
+    let a_1 = payment(&mut sender_a, 1);
+    let b_0 = payment(&mut sender_b, 1);
+
+    // Only small transactions fit in the block
+    let mut chainstate = Chainstate::with_consensus(
+        Arc::new(MemoryStore::default()),
+        ConsensusParams {
+            max_block_size: a_1.to_bytes().len() * 2,
+            ..Default::default()
+        },
+    );
+    for sender in [&sender_a, &sender_b] {
+        let reward = TransactionBuilder::new()
+            .to_address(&sender.get_public().hash_it())
+            .ammount(100)
+            .is_type(TransactionType::COINBASE)
+            .build();
+        chainstate.effect_transaction(&reward);
+    }
+
+    // The payment of A can't go before it's batch, but it isn't a bad transaction
+    let (ok_txs, bad_txs) =
+        Mempool::verify_veracity_of_transactions(&[a_0, a_1, b_0.clone()], &mut chainstate, 0);
+
+    assert_eq!(
+        ok_txs
+            .iter()
+            .map(|tx| tx.get_hash())
+            .collect::<Vec<String>>(),
+        vec![b_0.get_hash()]
+    );
+    assert!(bad_txs.is_empty());
+}